@@ -0,0 +1,52 @@
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+
+use crate::tone::{Oscillator, Tone, SAMPLE_RATE};
+
+impl AudioCallback for Oscillator {
+    type Channel = f32;
+
+    fn callback(&mut self, samples: &mut [f32]) {
+        self.fill(samples);
+    }
+}
+
+/// # Audio
+/// Plays a Tone through SDL audio while the sound timer is active.
+pub struct Audio {
+    device: AudioDevice<Oscillator>,
+    playing: bool,
+}
+
+impl Audio {
+    /// Opens the default audio device, which stays silent until the tone is played
+    ///
+    /// # Arguments
+    /// * `sdl` an sdl2 context with which to play audio
+    /// * `tone` the tone to play
+    pub fn new(sdl: &sdl2::Sdl, tone: Tone) -> Result<Self, String> {
+        let desired = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE),
+            channels: Some(1),
+            samples: None,
+        };
+        let device = sdl
+            .audio()?
+            .open_playback(None, &desired, |spec| Oscillator::new(tone, spec.freq))?;
+        device.resume();
+        Ok(Audio {
+            device,
+            playing: false,
+        })
+    }
+
+    /// Starts or stops the tone, only locking the device when that changes
+    ///
+    /// # Arguments
+    /// * `playing` whether the tone should be audible
+    pub fn set_playing(&mut self, playing: bool) {
+        if playing != self.playing {
+            self.device.lock().set_playing(playing);
+            self.playing = playing;
+        }
+    }
+}
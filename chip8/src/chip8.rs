@@ -1,131 +1,624 @@
-use std::collections::VecDeque;
-use std::io::Error;
+use std::io::{Error, ErrorKind};
 
-use core::constants::{CPU_CYCLES_PER_TIMER_CYCLE, MAX_SAVED_STATES};
+use core::access::Accesses;
+use core::constants::{
+    INSTRUCTIONS_PER_FRAME, MEMORY_SIZE, REWIND_FRAMES, REWIND_MEMORY, ROM_ADDRESS,
+};
+use core::error::{self, ExecutionError};
 use core::instruction;
-use core::state::{FrameBuffer, State};
+use core::quirks::Quirks;
+use core::rng::Rng;
+use core::state::{FrameBuffer, Resolution, State};
+
+use crate::hash::rom_hash;
+use crate::movie::{KeyEvent, Movie, MovieError, Playback};
+use crate::rewind::Rewind;
+use crate::trace::Trace;
+
+/// # Options
+/// How a Chip8 should be configured when it's created.
+///
+/// - `quirks` which interpretation of ambiguous opcodes to follow
+/// - `seed` the seed for the random number generator, or `None` to use a hash of the loaded ROM
+/// - `instructions_per_frame` how many instructions are executed each 60Hz frame
+/// - `rewind_frames` how many frames of history to keep for rewinding
+/// - `rewind_memory` the most bytes of memory the history may use
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Options {
+    pub quirks: Quirks,
+    pub seed: Option<u64>,
+    pub instructions_per_frame: usize,
+    pub rewind_frames: usize,
+    pub rewind_memory: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            quirks: Quirks::default(),
+            seed: None,
+            instructions_per_frame: INSTRUCTIONS_PER_FRAME,
+            rewind_frames: REWIND_FRAMES,
+            rewind_memory: REWIND_MEMORY,
+        }
+    }
+}
 
 /// # Chip-8
 /// Chip-8 is a virtual machine and corresponding interpreted language.
 ///
 /// Tracks:
 ///  - current `state`
-///  - `previous_states` for rewinding
+///  - `rewind` the states at the start of previous frames
 ///  - `pressed_keys` with public interfaces for manipulating them
+///  - `error` the reason execution halted, if it has
+///  - `accesses` the locations the last cycle's instruction read and wrote
+///  - `rom_hash` identifies the loaded ROM
+///  - `seed` overrides the ROM hash as the random number generator's seed
+///  - `instructions_per_frame` how many cycles pass between ticks of the timers
+///  - `trace` where executed instructions are logged, if anywhere
+///  - `recording` the movie key presses and releases are recorded into, if any
+///  - `playback` the movie key presses and releases are played back from, if any
 ///
 /// Supplies interfaces for:
 /// - loading roms
 /// - pressing and releasing keys
+/// - stepping through cycles and frames
 /// - advancing and reversing the CPU
 /// - advancing its timers
+/// - tracing the instructions it executes
+/// - recording and playing back movies of key presses
 /// - inspecting its frame buffer for rendering by some display
+/// - inspecting and poking its state, and the locations its last instruction accessed
+/// - diagnosing why execution halted
+///
+/// ```
+/// use chip8::Chip8;
+///
+/// let mut chip8 = Chip8::new();
+/// // 6A2A: set VA to 0x2A
+/// chip8.load_rom(&mut &[0x6A, 0x2A][..]).unwrap();
+/// chip8.step().unwrap();
+/// assert_eq!(chip8.state().v[0xA], 0x2A);
+/// ```
 pub struct Chip8 {
     state: State,
-    previous_states: VecDeque<State>,
+    rewind: Rewind,
     pressed_keys: [u8; 16],
+    error: Option<ExecutionError>,
+    accesses: Accesses,
+    rom_hash: u64,
+    seed: Option<u64>,
+    instructions_per_frame: usize,
+    trace: Option<Trace>,
+    recording: Option<Movie>,
+    playback: Option<Playback>,
 }
 
-// TODO explore time/memory efficiency of more compact representations of past states (e.g. diffs)
 impl Chip8 {
     pub fn new() -> Self {
-        Chip8 {
-            state: State::new(),
-            previous_states: VecDeque::with_capacity(MAX_SAVED_STATES),
+        Self::with_quirks(Quirks::default())
+    }
+
+    /// Creates a Chip-8 whose operations follow the given quirks
+    ///
+    /// # Arguments
+    /// * `quirks` which interpretation of ambiguous opcodes to follow
+    pub fn with_quirks(quirks: Quirks) -> Self {
+        Self::with_options(Options {
+            quirks,
+            ..Options::default()
+        })
+    }
+
+    /// Creates a Chip-8 configured with the given options
+    ///
+    /// # Arguments
+    /// * `options` how the Chip-8 should be configured
+    ///
+    /// ```
+    /// use chip8::{Chip8, Options, Profile};
+    ///
+    /// let chip8 = Chip8::with_options(Options {
+    ///     quirks: Profile::SuperChip.into(),
+    ///     seed: Some(1234),
+    ///     instructions_per_frame: 15,
+    ///     ..Options::default()
+    /// });
+    /// assert!(!chip8.state().quirks.shift_vy);
+    /// ```
+    pub fn with_options(options: Options) -> Self {
+        let mut chip8 = Chip8 {
+            state: State::with_quirks(options.quirks),
+            rewind: Rewind::new(options.rewind_frames, options.rewind_memory),
             pressed_keys: [0; 16],
-        }
+            error: None,
+            accesses: Accesses::default(),
+            rom_hash: rom_hash(&[]),
+            seed: options.seed,
+            instructions_per_frame: options.instructions_per_frame.max(1),
+            trace: None,
+            recording: None,
+            playback: None,
+        };
+        chip8.seed_rng(options.seed.unwrap_or(chip8.rom_hash));
+        chip8
     }
 
     /// Load a rom from a source file
+    /// Unless a seed was given, the random number generator is seeded with the ROM's hash so runs are reproducible
     ///
     /// # Arguments
     /// * `reader` a file reader that contains a ROM
     pub fn load_rom(&mut self, reader: &mut dyn std::io::Read) -> Result<(), Error> {
-        reader.read_exact(&mut self.state.memory[0x200..])
+        let mut rom = Vec::new();
+        reader.read_to_end(&mut rom)?;
+        let start = ROM_ADDRESS as usize;
+        if rom.len() > MEMORY_SIZE - start {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "ROM is {} bytes but at most {} fit in memory",
+                    rom.len(),
+                    MEMORY_SIZE - start
+                ),
+            ));
+        }
+        self.state.memory[start..start + rom.len()].copy_from_slice(&rom);
+        self.rom_hash = rom_hash(&rom);
+        self.seed_rng(self.seed.unwrap_or(self.rom_hash));
+        Ok(())
+    }
+
+    /// Reseeds the random number generator
+    ///
+    /// # Arguments
+    /// * `seed` the seed to generate random numbers from
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.state.rng = Rng::new(seed);
+    }
+
+    /// Returns the hash of the loaded ROM
+    pub fn rom_hash(&self) -> u64 {
+        self.rom_hash
+    }
+
+    /// Returns the seed the random number generator was seeded with when the ROM was loaded
+    pub fn seed(&self) -> u64 {
+        self.seed.unwrap_or(self.rom_hash)
     }
 
-    /// Returns the FrameBuffer if the display should be redrawn
-    pub fn get_frame(&self) -> Option<FrameBuffer> {
+    /// Returns the FrameBuffer if the display should be redrawn, unsetting the draw flag
+    pub fn get_frame(&mut self) -> Option<FrameBuffer> {
         if self.state.draw_flag {
+            self.state.draw_flag = false;
             Some(self.state.frame_buffer)
         } else {
             None
         }
     }
 
+    /// Returns the current contents of the FrameBuffer regardless of whether it's changed
+    pub fn frame(&self) -> &FrameBuffer {
+        &self.state.frame_buffer
+    }
+
+    /// Returns a read-only view of the current state, including its registers, memory, and stack
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    /// Returns which keys are currently pressed, indexed by key
+    pub fn pressed_keys(&self) -> [u8; 16] {
+        self.pressed_keys
+    }
+
+    /// Returns which portion of the FrameBuffer is in use
+    pub fn resolution(&self) -> Resolution {
+        self.state.resolution
+    }
+
+    /// Returns whether the ROM has executed the SUPER-CHIP exit instruction
+    pub fn has_exited(&self) -> bool {
+        self.state.exited
+    }
+
+    /// Returns whether a tone should be playing, i.e. the sound timer is active and execution hasn't halted
+    pub fn is_sounding(&self) -> bool {
+        self.state.sound_timer > 0 && self.error.is_none()
+    }
+
+    /// Returns the error that halted execution, if any
+    pub fn error(&self) -> Option<ExecutionError> {
+        self.error
+    }
+
+    /// Returns the locations the instruction executed by the last cycle read and wrote
+    /// Nothing was accessed if the cycle didn't execute an instruction, e.g. while awaiting a keypress
+    pub fn accesses(&self) -> &Accesses {
+        &self.accesses
+    }
+
+    /// Describes why execution halted along with the registers at the time, if it has halted
+    pub fn diagnostic(&self) -> Option<String> {
+        self.error
+            .map(|error| format!("halted: {}\n{}", error, self.registers()))
+    }
+
+    /// Describes the registers, timers, and stack
+    pub fn registers(&self) -> String {
+        let state = &self.state;
+        let registers: Vec<String> = state
+            .v
+            .iter()
+            .enumerate()
+            .map(|(register, value)| format!("V{:X}={:02X}", register, value))
+            .collect();
+        format!(
+            "{}\nPC={:04X} I={:04X} SP={:02X} DT={:02X} ST={:02X}\nstack: {:04X?}",
+            registers.join(" "),
+            state.pc,
+            state.i,
+            state.sp,
+            state.delay_timer,
+            state.sound_timer,
+            &state.stack[1..=state.sp as usize],
+        )
+    }
+
+    /// Copies bytes into memory starting at an address
+    ///
+    /// # Arguments
+    /// * `address` the address the first byte is written to
+    /// * `bytes` the bytes to write
+    ///
+    /// ```
+    /// use chip8::Chip8;
+    ///
+    /// let mut chip8 = Chip8::new();
+    /// chip8.write_memory(0x300, &[0xDE, 0xAD]).unwrap();
+    /// assert_eq!(chip8.state().memory[0x300..0x302], [0xDE, 0xAD]);
+    /// assert!(chip8.write_memory(0xFFFF, &[0x00, 0x00]).is_err());
+    /// ```
+    pub fn write_memory(&mut self, address: u16, bytes: &[u8]) -> Result<(), error::ErrorKind> {
+        let start = address as usize;
+        if start + bytes.len() > MEMORY_SIZE {
+            return Err(error::ErrorKind::MemoryOutOfRange {
+                address,
+                len: bytes.len(),
+            });
+        }
+        self.state.memory[start..start + bytes.len()].copy_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Sets one of the V registers
+    ///
+    /// # Arguments
+    /// * `register` the index of the register, from 0x0 to 0xF
+    /// * `value` the value to store in it
+    pub fn set_v(&mut self, register: usize, value: u8) {
+        self.state.v[register & 0xF] = value;
+    }
+
+    /// Sets the I register
+    ///
+    /// # Arguments
+    /// * `value` the address to store in it
+    pub fn set_i(&mut self, value: u16) {
+        self.state.i = value;
+    }
+
+    /// Moves the program counter, e.g. to resume execution somewhere else after halting
+    ///
+    /// # Arguments
+    /// * `address` the address of the next instruction to execute
+    pub fn set_pc(&mut self, address: u16) {
+        self.state.pc = address;
+        self.error = None;
+    }
+
+    /// Starts logging every instruction executed from now on, or stops if given None
+    ///
+    /// # Arguments
+    /// * `trace` where instructions are logged
+    pub fn set_trace(&mut self, trace: Option<Trace>) {
+        self.trace = trace;
+    }
+
+    /// Stops logging instructions, returning the trace so it can be finished
+    pub fn take_trace(&mut self) -> Option<Trace> {
+        self.trace.take()
+    }
+
+    /// Starts recording a movie of every key press and release, which should happen right after
+    /// the ROM is loaded so the movie covers the whole session
+    /// Rewinding while recording drops what was recorded after the point rewound to
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Movie {
+            rom_hash: self.rom_hash,
+            seed: self.seed(),
+            instructions_per_frame: self.instructions_per_frame,
+            quirks: self.state.quirks,
+            events: Vec::new(),
+        });
+    }
+
+    /// Stops recording, returning the movie recorded so far
+    pub fn take_recording(&mut self) -> Option<Movie> {
+        self.recording.take()
+    }
+
+    /// Returns whether key presses and releases are being recorded
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Starts playing back a movie, which should happen right after the ROM is loaded
+    /// The movie's seed, quirks, and instructions per frame replace the ones the Chip-8 was created with
+    /// Playback stops as soon as a key is pressed or released by `key_press` or `key_release`,
+    /// which lets a recording take over from the movie
+    ///
+    /// # Arguments
+    /// * `movie` the movie to play, which must have been recorded with the loaded ROM
+    pub fn play(&mut self, movie: Movie) -> Result<(), MovieError> {
+        if movie.rom_hash != self.rom_hash {
+            return Err(MovieError::DifferentRom {
+                rom_hash: movie.rom_hash,
+            });
+        }
+        self.seed = Some(movie.seed);
+        self.seed_rng(movie.seed);
+        self.state.quirks = movie.quirks;
+        self.instructions_per_frame = movie.instructions_per_frame.max(1);
+        self.playback = Some(Playback::new(movie));
+        Ok(())
+    }
+
+    /// Returns whether a movie is being played back
+    pub fn is_playing(&self) -> bool {
+        self.playback.is_some()
+    }
+
     /// Set the pressed status of key
+    /// Stops playing back a movie, if one is playing; keys above 0xF don't exist and are ignored
     ///
     /// # Arguments
     /// * `key` the 8-bit representation of the key that was pressed
     pub fn key_press(&mut self, key: u8) {
-        self.pressed_keys[key as usize] = 0x1;
-        if let Some(register) = self.state.register_needing_key {
-            self.state.v[register as usize] = key;
-            self.state.register_needing_key = None;
-        }
+        self.playback = None;
+        self.set_key(key, true);
     }
 
     /// Unset the pressed status of key
+    /// Stops playing back a movie, if one is playing; keys above 0xF don't exist and are ignored
     ///
     /// # Arguments
     /// * `key` the 8-bit representation of the key that was released
     pub fn key_release(&mut self, key: u8) {
-        self.pressed_keys[key as usize] = 0x0;
+        self.playback = None;
+        self.set_key(key, false);
+    }
+
+    /// Presses or releases a key, recording it if a movie is being recorded
+    ///
+    /// # Arguments
+    /// * `key` the 8-bit representation of the key
+    /// * `pressed` whether the key was pressed or released
+    fn set_key(&mut self, key: u8, pressed: bool) {
+        if usize::from(key) >= self.pressed_keys.len() {
+            return;
+        }
+        if let Some(recording) = self.recording.as_mut() {
+            recording.events.push(KeyEvent {
+                cycle: self.state.cycles,
+                key,
+                pressed,
+            });
+        }
+        self.pressed_keys[key as usize] = pressed as u8;
+        if let (true, Some(register)) = (pressed, self.state.register_needing_key) {
+            self.state.v[register as usize] = key;
+            self.state.register_needing_key = None;
+        }
+    }
+
+    /// Presses and releases keys as the movie being played back did by the current cycle
+    fn play_events(&mut self) {
+        let cycle = self.state.cycles;
+        while let Some(event) = self
+            .playback
+            .as_mut()
+            .and_then(|playback| playback.next_event(cycle))
+        {
+            self.set_key(event.key, event.pressed);
+        }
+    }
+
+    /// Brings movies in line with a state that's been rewound to
+    /// - playback carries on from the rewound cycle with the keys pressed then
+    /// - recording drops what happened from then on, and records the keys pressed now as
+    ///   being pressed then
+    fn rewind_movies(&mut self) {
+        let cycle = self.state.cycles;
+        if let Some(playback) = self.playback.as_mut() {
+            playback.seek(cycle);
+            self.pressed_keys = playback.movie.keys_at(cycle);
+        }
+        let pressed_keys = self.pressed_keys;
+        if let Some(recording) = self.recording.as_mut() {
+            recording.truncate(cycle);
+            let recorded = recording.keys_at(cycle);
+            let changed = (0..16).filter(|&key| recorded[key] != pressed_keys[key]);
+            for key in changed {
+                recording.events.push(KeyEvent {
+                    cycle,
+                    key: key as u8,
+                    pressed: pressed_keys[key] != 0,
+                });
+            }
+        }
+    }
+
+    /// Executes a single cycle: advances the CPU, and the timers if it was the last cycle of a frame
+    /// Returns the error that halted execution, if it has halted
+    /// The state at the start of each frame is saved for rewinding
+    pub fn step(&mut self) -> Result<(), ExecutionError> {
+        if self.error.is_none() && self.is_frame_boundary() {
+            self.save_state();
+        }
+        if self.playback.is_some() {
+            self.play_events();
+        }
+        self.advance_cpu();
+        if self.error.is_none() && self.is_frame_boundary() {
+            self.advance_timers();
+        }
+        self.error.map_or(Ok(()), Err)
+    }
+
+    /// Executes cycles until the end of the current 60Hz frame, when the timers tick
+    /// Stops early and returns the error if execution halts
+    ///
+    /// ```
+    /// use chip8::Chip8;
+    ///
+    /// let mut chip8 = Chip8::new();
+    /// // F515: set the delay timer to V5, 1200: jump back to 0x200
+    /// chip8.load_rom(&mut &[0xF5, 0x15, 0x12, 0x02][..]).unwrap();
+    /// chip8.set_v(0x5, 10);
+    /// chip8.step().unwrap();
+    /// let delay = chip8.state().delay_timer;
+    /// chip8.run_frame().unwrap();
+    /// assert_eq!(chip8.state().delay_timer, delay - 1);
+    /// ```
+    pub fn run_frame(&mut self) -> Result<(), ExecutionError> {
+        loop {
+            self.step()?;
+            if self.is_frame_boundary() {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Executes a number of cycles
+    /// Stops early and returns the error if execution halts
+    ///
+    /// # Arguments
+    /// * `cycles` how many cycles to execute
+    ///
+    /// ```
+    /// use chip8::Chip8;
+    ///
+    /// let mut chip8 = Chip8::new();
+    /// // 7001: add 1 to V0, 1200: jump back to 0x200
+    /// chip8.load_rom(&mut &[0x70, 0x01, 0x12, 0x00][..]).unwrap();
+    /// chip8.run_cycles(10).unwrap();
+    /// assert_eq!(chip8.state().v[0x0], 5);
+    ///
+    /// // 0000 isn't an instruction, so execution halts on it
+    /// chip8.set_pc(0x204);
+    /// let error = chip8.run_cycles(10).unwrap_err();
+    /// assert_eq!(error.pc, 0x204);
+    /// ```
+    pub fn run_cycles(&mut self, cycles: usize) -> Result<(), ExecutionError> {
+        for _ in 0..cycles {
+            self.step()?;
+        }
+        Ok(())
     }
 
     /// Advances the CPU by a single cycle
-    /// - breaks if awaiting a keypress
-    /// - gets and executes the next opcode
+    /// - breaks if execution has halted
+    /// - gets and executes the next opcode unless awaiting a keypress or the ROM has exited
+    /// - halts if the opcode can't be executed, leaving the state as it was before it
+    /// - counts the cycle
     pub fn advance_cpu(&mut self) {
-        if self.state.register_needing_key == None {
+        self.accesses = Accesses::default();
+        if self.error.is_some() {
+            return;
+        }
+        if self.state.register_needing_key.is_none() && !self.state.exited {
             let op: u16 = self.get_op();
-            self.state = instruction::from_op(&op)(&op, &self.state, self.pressed_keys);
+            if let Some(trace) = self.trace.as_mut() {
+                trace.record(&self.state, op);
+            }
+            match instruction::execute(op, &self.state, self.pressed_keys) {
+                Ok((state, accesses)) => {
+                    self.state = state;
+                    self.accesses = accesses;
+                }
+                Err(error) => {
+                    self.error = Some(error);
+                    return;
+                }
+            }
         };
-        self.save_state();
+        self.state.cycles += 1;
     }
 
-    /// Reverses the CPU by a single cycle if possible
-    /// - if there are previous_states, pops the last one and restores it
-    pub fn reverse_cpu(&mut self) {
-        let maybe_old_state: Option<State> = self.previous_states.pop_front();
-        if let Some(state) = maybe_old_state {
-            self.state = state
-        }
+    /// Returns whether the cycles executed so far make up a whole number of frames
+    pub fn is_frame_boundary(&self) -> bool {
+        self.state
+            .cycles
+            .is_multiple_of(self.instructions_per_frame as u64)
     }
 
-    /// Puts the current state in previous_states
-    /// - if there are already MAX_SAVED_STATES saved then the oldest is dropped
-    fn save_state(&mut self) {
-        if self.previous_states.len() == MAX_SAVED_STATES {
-            self.previous_states.pop_back();
+    /// Reverses the CPU to the start of the current frame, or of the previous one if it's just started
+    /// - clears any error that halted execution
+    /// - does nothing once the oldest saved state has been restored
+    pub fn reverse_frame(&mut self) {
+        self.error = None;
+        if let Some(state) = self.rewind.pop() {
+            self.state = state;
+            self.rewind_movies();
         }
-        self.previous_states.push_front(self.state);
     }
 
-    /// Handles delay counter and timers
-    /// - decrements the delay counter
-    /// - decrements timers when the counter hits 0 and resets the counter to `CPU_TIMERS_PER_CYCLE`
-    pub fn advance_timers(&mut self) {
-        if self.state.delay_counter == 0 {
-            self.state.delay_counter = CPU_CYCLES_PER_TIMER_CYCLE;
+    /// Replaces the machine's state and pressed keys, e.g. with a save state
+    /// - clears any error that halted execution
+    /// - flags the frame to be redrawn
+    ///
+    /// # Arguments
+    /// * `state` the state to continue from
+    /// * `pressed_keys` which keys are pressed, indexed by key
+    pub(crate) fn restore(&mut self, state: State, pressed_keys: [u8; 16]) {
+        self.state = State {
+            draw_flag: true,
+            ..state
+        };
+        self.pressed_keys = pressed_keys;
+        self.error = None;
+    }
 
-            if self.state.delay_timer > 0 {
-                self.state.delay_timer -= 1;
-            }
+    /// Changes how much history is kept for rewinding, dropping the oldest frames if there's now too much
+    ///
+    /// # Arguments
+    /// * `frames` how many frames of history to keep
+    /// * `memory` the most bytes of memory the history may use
+    pub fn set_rewind_limits(&mut self, frames: usize, memory: usize) {
+        self.rewind.set_limits(frames, memory);
+    }
 
-            if self.state.sound_timer > 0 {
-                // TODO make some sound
-                self.state.sound_timer -= 1;
-            }
-        } else {
-            self.state.delay_counter -= 1;
+    /// Saves the current state for rewinding, dropping the oldest if there's too much history
+    fn save_state(&mut self) {
+        self.rewind.push(&self.state);
+    }
+
+    /// Decrements the timers, which should happen once per 60Hz frame
+    pub fn advance_timers(&mut self) {
+        if self.error.is_some() {
+            return;
         }
+        self.state.delay_timer = self.state.delay_timer.saturating_sub(1);
+        self.state.sound_timer = self.state.sound_timer.saturating_sub(1);
     }
 
     /// Gets the opcode currently pointed at by the pc.
     /// Memory is stored as bytes, but opcodes are 16 bits so we combine two subsequent bytes.
-    fn get_op(&self) -> u16 {
+    pub fn get_op(&self) -> u16 {
         let left = u16::from(self.state.memory[self.state.pc as usize]);
-        let right = u16::from(self.state.memory[self.state.pc as usize + 1]);
+        let right = u16::from(self.state.memory[self.state.pc.wrapping_add(1) as usize]);
         left << 8 | right
     }
 }
@@ -147,11 +640,47 @@ mod tests {
         assert_eq!(chip8.get_op(), 0xAABB);
     }
 
+    #[test]
+    fn test_chip8_loads_rom() {
+        let mut chip8 = Chip8::new();
+        chip8.load_rom(&mut &[0xAA, 0xBB][..]).unwrap();
+        assert_eq!(chip8.state.memory[0x200..0x203], [0xAA, 0xBB, 0x00]);
+    }
+
+    #[test]
+    fn test_chip8_seeds_rng_from_rom() {
+        let mut a = Chip8::new();
+        a.load_rom(&mut &[0xC0, 0xFF][..]).unwrap();
+        let mut b = Chip8::new();
+        b.load_rom(&mut &[0xC0, 0xFF][..]).unwrap();
+        a.advance_cpu();
+        b.advance_cpu();
+        assert_eq!(a.state.v[0x0], b.state.v[0x0]);
+        assert_eq!(a.state.rng, Rng::new(a.rom_hash()).next_byte().1);
+    }
+
+    #[test]
+    fn test_chip8_seed_option_overrides_rom_hash() {
+        let mut chip8 = Chip8::with_options(Options {
+            seed: Some(7),
+            ..Options::default()
+        });
+        chip8.load_rom(&mut &[0xC0, 0xFF][..]).unwrap();
+        assert_eq!(chip8.state.rng, Rng::new(7));
+    }
+
+    #[test]
+    fn test_chip8_rejects_oversized_rom() {
+        let mut chip8 = Chip8::new();
+        let rom = vec![0; MEMORY_SIZE - 0x200 + 1];
+        assert!(chip8.load_rom(&mut &rom[..]).is_err());
+    }
+
     #[test]
     fn test_cycles_while_no_register_needs_key() {
         let mut chip8 = Chip8::new();
         let starting_pc = chip8.state.pc;
-        // insert a cls opcode so we don't panic at reading from empty memory
+        // insert a cls opcode so we don't halt at reading from empty memory
         chip8.state.memory[0x200..0x202].copy_from_slice(&[0x00, 0xE0]);
         chip8.advance_cpu();
         assert_eq!(chip8.state.pc, starting_pc + 0x2);
@@ -166,6 +695,18 @@ mod tests {
         assert_eq!(chip8.state.v[0x1], 0xE);
     }
 
+    #[test]
+    fn test_ignores_keys_that_dont_exist() {
+        let mut chip8 = Chip8::new();
+        chip8.state.register_needing_key = Some(0x1);
+        chip8.start_recording();
+        chip8.key_press(0x10);
+        chip8.key_release(0x10);
+        assert_eq!(chip8.pressed_keys(), [0; 16]);
+        assert_eq!(chip8.state.register_needing_key, Some(0x1));
+        assert!(chip8.recording.unwrap().events.is_empty());
+    }
+
     #[test]
     fn test_doesnt_cycle_while_register_needs_key() {
         let mut chip8 = Chip8::new();
@@ -175,22 +716,112 @@ mod tests {
         assert_eq!(chip8.state.pc, starting_pc);
     }
 
+    #[test]
+    fn test_doesnt_cycle_once_exited() {
+        let mut chip8 = Chip8::new();
+        chip8.state.memory[0x200..0x204].copy_from_slice(&[0x00, 0xFD, 0x00, 0xE0]);
+        chip8.advance_cpu();
+        chip8.advance_cpu();
+        assert!(chip8.has_exited());
+        assert_eq!(chip8.state.pc, 0x200);
+    }
+
+    #[test]
+    fn test_halts_on_execution_error() {
+        let mut chip8 = Chip8::new();
+        chip8.state.memory[0x200..0x204].copy_from_slice(&[0x00, 0xE0, 0x00, 0xEE]);
+        chip8.advance_cpu();
+        chip8.advance_cpu();
+        let error = chip8.error().unwrap();
+        assert_eq!((error.pc, error.op), (0x202, 0x00EE));
+        assert_eq!(chip8.state.pc, 0x202);
+        assert!(chip8
+            .diagnostic()
+            .unwrap()
+            .starts_with("halted: stack underflow"));
+
+        // Halted machines stay put until they're rewound
+        chip8.advance_cpu();
+        assert_eq!(chip8.state.pc, 0x202);
+        chip8.reverse_frame();
+        assert!(chip8.error().is_none());
+    }
+
+    #[test]
+    fn test_set_pc_resumes_after_error() {
+        let mut chip8 = Chip8::new();
+        chip8.write_memory(0x200, &[0x00, 0xEE]).unwrap();
+        chip8.write_memory(0x300, &[0x00, 0xE0]).unwrap();
+        assert!(chip8.step().is_err());
+        chip8.set_pc(0x300);
+        assert!(chip8.step().is_ok());
+        assert_eq!(chip8.state.pc, 0x302);
+    }
+
+    #[test]
+    fn test_timers_tick_once_per_frame() {
+        let mut chip8 = Chip8::with_options(Options {
+            instructions_per_frame: 4,
+            ..Options::default()
+        });
+        // 1200: jump to 0x200 forever
+        chip8.write_memory(0x200, &[0x12, 0x00]).unwrap();
+        chip8.state.delay_timer = 10;
+        chip8.run_cycles(3).unwrap();
+        assert_eq!(chip8.state.delay_timer, 10);
+        chip8.step().unwrap();
+        assert_eq!(chip8.state.delay_timer, 9);
+        chip8.run_frame().unwrap();
+        assert_eq!((chip8.state.cycles, chip8.state.delay_timer), (8, 8));
+    }
+
+    #[test]
+    fn test_get_frame_only_returns_changed_frames() {
+        let mut chip8 = Chip8::new();
+        chip8.write_memory(0x200, &[0x00, 0xE0]).unwrap();
+        chip8.step().unwrap();
+        assert!(chip8.get_frame().is_some());
+        assert!(chip8.get_frame().is_none());
+    }
+
     #[test]
     fn test_chip8_saves_state() {
         let mut chip8 = Chip8::new();
         chip8.save_state();
-        assert_eq!(chip8.previous_states.len(), 1);
+        assert_eq!(chip8.rewind.len(), 1);
     }
 
-    // TODO this test is unnecessarily slow because we can't parameterize MAX_SAVED_STATES
     #[test]
     fn test_chip8_drops_old_saved_states() {
-        let mut chip8 = Chip8::new();
-        for _ in 0..MAX_SAVED_STATES {
+        let mut chip8 = Chip8::with_options(Options {
+            rewind_frames: 10,
+            ..Options::default()
+        });
+        for _ in 0..10 {
             chip8.save_state();
         }
-        assert_eq!(MAX_SAVED_STATES, chip8.previous_states.len());
+        assert_eq!(10, chip8.rewind.len());
         chip8.save_state();
-        assert_eq!(MAX_SAVED_STATES, chip8.previous_states.len());
+        assert_eq!(10, chip8.rewind.len());
+    }
+
+    #[test]
+    fn test_chip8_rewinds_by_frame() {
+        let mut chip8 = Chip8::with_options(Options {
+            instructions_per_frame: 2,
+            ..Options::default()
+        });
+        // 7001: add 1 to V0, 1200: jump back to 0x200
+        chip8
+            .write_memory(0x200, &[0x70, 0x01, 0x12, 0x00])
+            .unwrap();
+        chip8.run_cycles(9).unwrap();
+        assert_eq!(chip8.state.v[0x0], 5);
+        // Back to the start of the current frame, then the one before it
+        chip8.reverse_frame();
+        assert_eq!(chip8.state.cycles, 8);
+        chip8.reverse_frame();
+        assert_eq!(chip8.state.cycles, 6);
+        assert_eq!(chip8.state.v[0x0], 3);
     }
 }
@@ -0,0 +1,470 @@
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error, Write};
+use std::path::PathBuf;
+
+use crate::chip8::{Chip8, Options};
+use crate::session::Session;
+use core::access::Location;
+use core::mnemonic;
+
+/// The most cycles `continue`, `next`, and `finish` run for before giving up on reaching their target
+const MAX_CYCLES: usize = 10_000_000;
+
+const HELP: &str = "\
+break <addr>      stop when the pc reaches an address (b)
+delete <addr>     remove a breakpoint
+breakpoints       list breakpoints
+watch <loc> [len] stop when a register (v0-vf, i, sp, dt, st) or memory is read or written (w)
+log <loc> [len]   log reads and writes of a register or memory without stopping
+unwatch <loc>     remove a watchpoint
+watchpoints       list watchpoints
+step [count]      execute instructions (s)
+next              step, running through any subroutine called (n)
+finish            run until the current subroutine returns (f)
+continue          run until a breakpoint, error, or exit (c)
+print <what>      print v, i, pc, sp, stack, timers, or regs (p)
+memory <addr> [len]  print a range of memory (x)
+press <key>       press a key (0-f)
+release <key>     release a key
+quit              exit the debugger (q)";
+
+/// # Debugger
+/// A command-line debugger built on a Chip8.
+///
+/// Tracks:
+/// - `chip8` the machine being debugged
+/// - `breakpoints` the addresses execution stops at
+/// - `watchpoints` the registers and memory whose reads and writes are logged or stop execution
+pub struct Debugger {
+    chip8: Chip8,
+    breakpoints: BTreeSet<u16>,
+    watchpoints: Vec<Watchpoint>,
+}
+
+/// A location whose reads and writes are logged, and whether they stop execution
+struct Watchpoint {
+    location: Location,
+    stop: bool,
+}
+
+/// Runs the debugger against a ROM, reading commands from `input` until it's exhausted or `quit` is entered
+///
+/// # Arguments
+/// * `rom` the path of the ROM to debug
+/// * `options` how the Chip-8 should be configured
+/// * `session` what to log, play back, and record while the ROM runs
+/// * `input` where commands are read from
+/// * `output` where responses are written to
+pub fn debug(
+    rom: PathBuf,
+    options: Options,
+    mut session: Session,
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> Result<(), Error> {
+    let mut chip8 = Chip8::with_options(Options {
+        rewind_frames: 0,
+        ..options
+    });
+    chip8.load_rom(&mut BufReader::new(File::open(rom)?))?;
+    session.start(&mut chip8)?;
+    let mut debugger = Debugger::new(chip8);
+
+    writeln!(output, "{}", debugger.location())?;
+    loop {
+        write!(output, "(emu8) ")?;
+        output.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            break;
+        }
+        match debugger.execute(&line) {
+            Ok(Some(response)) => writeln!(output, "{}", response)?,
+            Ok(None) => break,
+            Err(e) => writeln!(output, "error: {}", e)?,
+        }
+    }
+    session.finish(&mut debugger.chip8)
+}
+
+impl Debugger {
+    pub fn new(chip8: Chip8) -> Self {
+        Debugger {
+            chip8,
+            breakpoints: BTreeSet::new(),
+            watchpoints: Vec::new(),
+        }
+    }
+
+    /// Executes a command, returning its response or None if the debugger should quit
+    ///
+    /// # Arguments
+    /// * `command` a line entered by the user
+    pub fn execute(&mut self, command: &str) -> Result<Option<String>, String> {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        let response = match words.as_slice() {
+            [] => String::new(),
+            ["quit"] | ["q"] => return Ok(None),
+            ["help"] | ["h"] => HELP.to_string(),
+            ["break", address] | ["b", address] => {
+                let address = parse_number(address)?;
+                self.breakpoints.insert(address);
+                format!("breakpoint at {:#06X}", address)
+            }
+            ["delete", address] => {
+                let address = parse_number(address)?;
+                if !self.breakpoints.remove(&address) {
+                    return Err(format!("no breakpoint at {:#06X}", address));
+                }
+                format!("deleted breakpoint at {:#06X}", address)
+            }
+            ["breakpoints"] => self
+                .breakpoints
+                .iter()
+                .map(|address| format!("{:#06X}", address))
+                .collect::<Vec<String>>()
+                .join("\n"),
+            ["watch", location @ ..] | ["w", location @ ..] => self.watch(location, true)?,
+            ["log", location @ ..] => self.watch(location, false)?,
+            ["unwatch", location] => {
+                let location: Location = location.parse()?;
+                let watched = self.watchpoints.len();
+                self.watchpoints
+                    .retain(|watchpoint| watchpoint.location != location);
+                if self.watchpoints.len() == watched {
+                    return Err(format!("{} isn't watched", location));
+                }
+                format!("unwatched {}", location)
+            }
+            ["watchpoints"] => self
+                .watchpoints
+                .iter()
+                .map(|watchpoint| {
+                    let action = if watchpoint.stop { "watch" } else { "log" };
+                    format!("{} {}", action, watchpoint.location)
+                })
+                .collect::<Vec<String>>()
+                .join("\n"),
+            ["step"] | ["s"] => self.step(1),
+            ["step", count] | ["s", count] => self.step(parse_number(count)?.into()),
+            ["next"] | ["n"] => self.next(),
+            ["finish"] | ["f"] => self.finish()?,
+            ["continue"] | ["c"] => self.run_until(|_| false),
+            ["print", what] | ["p", what] => self.print(what)?,
+            ["memory", address] | ["x", address] => self.memory(parse_number(address)?, 16)?,
+            ["memory", address, len] | ["x", address, len] => {
+                self.memory(parse_number(address)?, parse_number(len)?.into())?
+            }
+            ["press", key] => {
+                self.chip8.key_press(parse_key(key)?);
+                self.location()
+            }
+            ["release", key] => {
+                self.chip8.key_release(parse_key(key)?);
+                self.location()
+            }
+            _ => return Err(format!("unknown command {:?}; try `help`", command.trim())),
+        };
+        Ok(Some(response))
+    }
+
+    /// Describes where execution is: the pc and the disassembled instruction there, or why it stopped
+    pub fn location(&self) -> String {
+        let state = self.chip8.state();
+        let instruction = mnemonic::disassemble(&state.memory, state.pc)
+            .unwrap_or_else(|| "(unknown opcode)".to_string());
+        let mut location = format!(
+            "{:#06X}: {:04X}  {}",
+            state.pc,
+            self.chip8.get_op(),
+            instruction
+        );
+        if let Some(error) = self.chip8.error() {
+            location = format!("halted: {}\n{}", error, location);
+        } else if self.chip8.has_exited() {
+            location = format!("exited\n{}", location);
+        } else if let Some(register) = state.register_needing_key {
+            location = format!("waiting for a key press into v{:x}\n{}", register, location);
+        }
+        location
+    }
+
+    /// Executes instructions, stopping early if execution halts or a watchpoint is hit
+    fn step(&mut self, count: usize) -> String {
+        let mut log = Vec::new();
+        for _ in 0..count {
+            match self.advance(&mut log) {
+                Ok(false) => continue,
+                Ok(true) => return self.report(log, Some("watchpoint")),
+                Err(()) => break,
+            }
+        }
+        self.report(log, None)
+    }
+
+    /// Steps over a subroutine call, or steps once if the instruction at the pc isn't a call
+    fn next(&mut self) -> String {
+        if mnemonic::decode(self.chip8.get_op()).map(|mnemonic| mnemonic.name) != Some("call") {
+            return self.step(1);
+        }
+        let state = self.chip8.state();
+        let (return_pc, sp) = (state.pc.wrapping_add(0x2), state.sp);
+        self.run_until(|chip8| chip8.state().pc == return_pc && chip8.state().sp == sp)
+    }
+
+    /// Runs until the current subroutine returns
+    fn finish(&mut self) -> Result<String, String> {
+        let sp = self.chip8.state().sp;
+        if sp == 0 {
+            return Err("not in a subroutine".to_string());
+        }
+        Ok(self.run_until(|chip8| chip8.state().sp < sp))
+    }
+
+    /// Runs until a condition is met, a breakpoint or watchpoint is hit, or execution halts or exits
+    fn run_until(&mut self, done: impl Fn(&Chip8) -> bool) -> String {
+        let mut log = Vec::new();
+        for _ in 0..MAX_CYCLES {
+            match self.advance(&mut log) {
+                Err(()) => return self.report(log, None),
+                Ok(true) => return self.report(log, Some("watchpoint")),
+                Ok(false) if self.chip8.has_exited() || done(&self.chip8) => {
+                    return self.report(log, None)
+                }
+                Ok(false) if self.breakpoints.contains(&self.chip8.state().pc) => {
+                    return self.report(log, Some("breakpoint"))
+                }
+                Ok(false) => continue,
+            }
+        }
+        let reason = format!("stopped after {} cycles", MAX_CYCLES);
+        self.report(log, Some(&reason))
+    }
+
+    /// Executes a single instruction, logging its accesses to watched locations
+    /// Returns whether it accessed a location watched with `watch`, or Err if execution halted
+    ///
+    /// # Arguments
+    /// * `log` where accesses to watched locations are described
+    fn advance(&mut self, log: &mut Vec<String>) -> Result<bool, ()> {
+        let state = self.chip8.state();
+        let (pc, op) = (state.pc, self.chip8.get_op());
+        // Described before it executes, in case it overwrites itself
+        let instruction = match self.watchpoints.is_empty() {
+            true => String::new(),
+            false => mnemonic::disassemble(&state.memory, pc).unwrap_or_default(),
+        };
+        self.chip8.step().map_err(|_| ())?;
+        let accesses = self.chip8.accesses();
+        let mut stop = false;
+        for (kind, locations) in [("read", &accesses.reads), ("write", &accesses.writes)] {
+            for location in locations.iter() {
+                if let Some(watchpoint) = self
+                    .watchpoints
+                    .iter()
+                    .find(|watchpoint| watchpoint.location.overlaps(location))
+                {
+                    log.push(format!(
+                        "{:#06X}: {:04X}  {} {} ({})",
+                        pc, op, kind, location, instruction
+                    ));
+                    stop |= watchpoint.stop;
+                }
+            }
+        }
+        Ok(stop)
+    }
+
+    /// Describes the accesses logged while executing, why execution stopped, and where it is
+    fn report(&self, log: Vec<String>, reason: Option<&str>) -> String {
+        log.into_iter()
+            .chain(reason.map(str::to_string))
+            .chain(std::iter::once(self.location()))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Adds a watchpoint
+    ///
+    /// # Arguments
+    /// * `words` the location followed, for memory, by an optional length
+    /// * `stop` whether accessing the location should stop execution
+    fn watch(&mut self, words: &[&str], stop: bool) -> Result<String, String> {
+        let location = match words {
+            [location] => location.parse()?,
+            [address, len] => match address.parse()? {
+                Location::Memory { address, .. } => Location::Memory {
+                    address,
+                    len: parse_number(len)?.into(),
+                },
+                _ => return Err("only memory watchpoints have a length".to_string()),
+            },
+            _ => return Err("expected a location and, for memory, a length".to_string()),
+        };
+        self.watchpoints.push(Watchpoint { location, stop });
+        let action = if stop { "watching" } else { "logging" };
+        Ok(format!("{} {}", action, location))
+    }
+
+    /// Describes part of the state
+    fn print(&self, what: &str) -> Result<String, String> {
+        let state = self.chip8.state();
+        Ok(match what {
+            "v" => state
+                .v
+                .iter()
+                .enumerate()
+                .map(|(register, value)| format!("V{:X}={:02X}", register, value))
+                .collect::<Vec<String>>()
+                .join(" "),
+            "i" => format!("I={:04X}", state.i),
+            "pc" => format!("PC={:04X}", state.pc),
+            "sp" => format!("SP={:02X}", state.sp),
+            "stack" => format!("stack: {:04X?}", &state.stack[1..=state.sp as usize]),
+            "timers" => format!("DT={:02X} ST={:02X}", state.delay_timer, state.sound_timer),
+            "regs" => self.chip8.registers(),
+            _ => {
+                return Err(format!(
+                    "can't print {:?}; expected v, i, pc, sp, stack, timers, or regs",
+                    what
+                ))
+            }
+        })
+    }
+
+    /// Dumps a range of memory as rows of 16 bytes
+    fn memory(&self, address: u16, len: usize) -> Result<String, String> {
+        let memory = &self.chip8.state().memory;
+        let start = address as usize;
+        let end = start + len;
+        if end > memory.len() {
+            return Err(format!("{:#06X}..{:#06X} is out of range", start, end));
+        }
+        Ok((start..end)
+            .step_by(16)
+            .map(|row| {
+                let bytes: Vec<String> = memory[row..end.min(row + 16)]
+                    .iter()
+                    .map(|byte| format!("{:02X}", byte))
+                    .collect();
+                format!("{:#06X}: {}", row, bytes.join(" "))
+            })
+            .collect::<Vec<String>>()
+            .join("\n"))
+    }
+}
+
+/// Parses a decimal number or a hex one prefixed with `0x`
+fn parse_number(s: &str) -> Result<u16, String> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+    .map_err(|_| format!("{:?} is not a number", s))
+}
+
+/// Parses a key from 0 to f
+fn parse_key(s: &str) -> Result<u8, String> {
+    u8::from_str_radix(s, 16)
+        .ok()
+        .filter(|&key| key < 16)
+        .ok_or_else(|| format!("{:?} is not a key; expected 0 to f", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 0x200: call 0x206, 0x202: add v1, 1, 0x204: jump 0x202
+    /// 0x206: load v0, 0x2a, 0x208: rts
+    fn debugger() -> Debugger {
+        let mut chip8 = Chip8::new();
+        chip8
+            .write_memory(
+                0x200,
+                &[0x22, 0x06, 0x71, 0x01, 0x12, 0x02, 0x60, 0x2A, 0x00, 0xEE],
+            )
+            .unwrap();
+        Debugger::new(chip8)
+    }
+
+    fn execute(debugger: &mut Debugger, command: &str) -> String {
+        debugger.execute(command).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_debugger_steps_and_shows_instruction() {
+        let mut debugger = debugger();
+        assert_eq!(debugger.location(), "0x0200: 2206  call 0x206");
+        assert_eq!(
+            execute(&mut debugger, "step"),
+            "0x0206: 602A  load v0, 0x2a"
+        );
+        assert_eq!(execute(&mut debugger, "p stack"), "stack: [0200]");
+        assert_eq!(
+            execute(&mut debugger, "finish"),
+            "0x0202: 7101  add v1, 0x01"
+        );
+    }
+
+    #[test]
+    fn test_debugger_steps_over_calls() {
+        let mut debugger = debugger();
+        assert_eq!(execute(&mut debugger, "next"), "0x0202: 7101  add v1, 0x01");
+        assert_eq!(execute(&mut debugger, "print v").get(0..5), Some("V0=2A"));
+    }
+
+    #[test]
+    fn test_debugger_continues_to_breakpoints() {
+        let mut debugger = debugger();
+        execute(&mut debugger, "break 0x204");
+        assert_eq!(
+            execute(&mut debugger, "c"),
+            "breakpoint\n0x0204: 1202  jump 0x202"
+        );
+        execute(&mut debugger, "c");
+        assert_eq!(debugger.chip8.state().v[0x1], 2);
+    }
+
+    #[test]
+    fn test_debugger_stops_at_watchpoints() {
+        let mut debugger = debugger();
+        execute(&mut debugger, "watch v1");
+        assert_eq!(
+            execute(&mut debugger, "continue"),
+            "0x0202: 7101  read v1 (add v1, 0x01)\n\
+             0x0202: 7101  write v1 (add v1, 0x01)\n\
+             watchpoint\n\
+             0x0204: 1202  jump 0x202"
+        );
+    }
+
+    #[test]
+    fn test_debugger_logs_without_stopping() {
+        let mut debugger = debugger();
+        execute(&mut debugger, "log v0");
+        execute(&mut debugger, "break 0x204");
+        assert_eq!(
+            execute(&mut debugger, "continue"),
+            "0x0206: 602A  write v0 (load v0, 0x2a)\nbreakpoint\n0x0204: 1202  jump 0x202"
+        );
+        assert!(debugger.execute("unwatch v1").is_err());
+        assert!(debugger.execute("watch v1 4").is_err());
+    }
+
+    #[test]
+    fn test_debugger_prints_memory() {
+        let mut debugger = debugger();
+        assert_eq!(execute(&mut debugger, "x 0x200 4"), "0x0200: 22 06 71 01");
+        assert!(debugger.execute("x 0xFFFF 2").is_err());
+    }
+
+    #[test]
+    fn test_debugger_rejects_unknown_commands() {
+        let mut debugger = debugger();
+        assert!(debugger.execute("frobnicate").is_err());
+        assert!(debugger.execute("finish").is_err());
+        assert_eq!(debugger.execute("quit"), Ok(None));
+    }
+}
@@ -0,0 +1,430 @@
+use std::io::Error;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::chip8::Chip8;
+use crate::savestate::Slots;
+use crate::tone::Tone;
+use core::constants::FRAME_RATE;
+use core::error::ExecutionError;
+use display::{Gif, GifRecorder, Palette, Persistence, Renderer, Screenshot, Theme, Window};
+
+/// # Frontend
+/// How the window, sound, and hotkeys behave, for whichever frontend a ROM is played in.
+///
+/// - `palette` the colors the display is drawn in
+/// - `window` the size the window opens with, how frames fit into it, and where its size is remembered
+/// - `persistence` how long pixels stay visible after they're turned off, if at all
+/// - `tone` the tone played while the sound timer is active
+/// - `slots` where the save state hotkeys save and load
+/// - `screenshot` the scale and format of screenshots, which are drawn in the display's palette
+/// - `gif` the scale, trimming, and size limit of GIF recordings, which are drawn in the display's palette
+/// - `screenshots` the directory screenshots and GIF recordings are saved in
+pub struct Frontend {
+    pub palette: Palette,
+    pub window: Window,
+    pub persistence: Option<Persistence>,
+    pub tone: Tone,
+    pub slots: Slots,
+    pub screenshot: Screenshot,
+    pub gif: Gif,
+    pub screenshots: PathBuf,
+}
+
+/// # Action
+/// Something the player asked for, whichever keys or window events a frontend reads it from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Stop playing
+    Quit,
+    /// Press a keypad key
+    Press(u8),
+    /// Release a keypad key
+    Release(u8),
+    /// Start or stop ignoring the frame rate
+    FastForward(bool),
+    /// Start or stop cycling the game's state backwards
+    Rewind(bool),
+    /// Pause or resume
+    Pause,
+    /// Mute or unmute the tone
+    Mute,
+    /// Save a screenshot in the display's palette
+    Screenshot,
+    /// Switch to the next theme
+    Theme,
+    /// Start recording a GIF, or stop and save it
+    Gif,
+    /// Switch between a window and filling the screen
+    Fullscreen,
+    /// Show the last frame again, e.g. when the window is resized or needs repainting
+    Redraw,
+    /// Save the state to a slot
+    Save(usize),
+    /// Load the state from a slot
+    Load(usize),
+}
+
+/// # Controls
+/// Where a frontend's input comes from and where its messages go, so every frontend can be driven
+/// by the same loop.
+pub trait Controls {
+    /// The actions taken since the last frame, in the order they were taken
+    fn poll(&mut self) -> Result<Vec<Action>, Error>;
+
+    /// Tells the player what an action did, or why execution halted
+    ///
+    /// # Arguments
+    /// * `renderer` what frames are drawn with, e.g. to show the message in its title
+    /// * `message` what happened, or what went wrong
+    fn report(
+        &mut self,
+        renderer: &mut dyn Renderer,
+        message: Result<String, String>,
+    ) -> Result<(), Error>;
+
+    /// Starts or stops the tone; controls without sound ignore it
+    ///
+    /// # Arguments
+    /// * `sounding` whether the tone should be playing
+    fn set_sounding(&mut self, _sounding: bool) {}
+}
+
+/// Plays a loaded ROM at 60 frames per second until it exits or the player quits.
+///
+/// Each frame the renderer presents the frame if it changed, the actions since the last frame are
+/// taken, and the machine runs a frame forwards, or backwards while rewinding.
+/// Returns the GIF that was still being recorded when play stopped, if there was one.
+///
+/// # Arguments
+/// * `rom` the path of the ROM, which captures are named after
+/// * `chip8` the Chip-8 the ROM is loaded into
+/// * `renderer` what frames are drawn with
+/// * `controls` where actions come from and messages go
+/// * `frontend` the palette, save state slots, and capture settings
+pub fn play(
+    rom: &Path,
+    chip8: &mut Chip8,
+    renderer: &mut dyn Renderer,
+    controls: &mut dyn Controls,
+    frontend: &Frontend,
+) -> Result<Option<GifRecorder>, Error> {
+    let mut palette = frontend.palette;
+    // A playing movie ignores the keypad unless a recording can take over from it
+    let keypad = |chip8: &Chip8| !chip8.is_playing() || chip8.is_recording();
+
+    // Set initial timing
+    let frame_time: Duration = Duration::from_secs(1) / FRAME_RATE;
+    let mut next_frame: Instant = Instant::now();
+
+    // Whether or not the frame rate should be respected
+    let mut fast_forward: bool = false;
+    // Whether the game's state should be cycled forwards or backwards
+    let mut rewind: bool = false;
+    // Whether the game is paused
+    let mut paused: bool = false;
+    // Whether the tone is muted
+    let mut muted: bool = false;
+    // The GIF being recorded, if one is
+    let mut recording: Option<GifRecorder> = None;
+    // The error that halted execution, if it has been reported
+    let mut halted: Option<ExecutionError> = None;
+
+    'event: loop {
+        // If the frame buffer has changed since the last frame, or pixels are still fading, present it
+        let frame = match chip8.get_frame() {
+            Some(frame) => Some(frame),
+            None if renderer.is_fading() => Some(*chip8.frame()),
+            None => None,
+        };
+        if let Some(frame) = frame {
+            renderer.render(&frame, chip8.resolution());
+            renderer.present()?;
+        }
+
+        if chip8.has_exited() {
+            break 'event;
+        }
+
+        // Report why execution halted once, then keep going so it can be rewound
+        match (chip8.error(), &halted) {
+            (Some(error), None) => {
+                renderer.set_title(&format!("Emu-8 - halted: {}", error))?;
+                controls.report(renderer, Err(chip8.diagnostic().unwrap_or_default()))?;
+                halted = Some(error);
+            }
+            (None, Some(_)) => {
+                renderer.set_title("Emu-8")?;
+                halted = None;
+            }
+            _ => (),
+        }
+
+        // Handle input
+        for action in controls.poll()? {
+            let message = match action {
+                Action::Quit => break 'event,
+                Action::Press(_) | Action::Release(_) if !keypad(chip8) => continue,
+                Action::Press(kc) => {
+                    chip8.key_press(kc);
+                    continue;
+                }
+                Action::Release(kc) => {
+                    chip8.key_release(kc);
+                    continue;
+                }
+                Action::FastForward(on) => {
+                    fast_forward = on;
+                    continue;
+                }
+                Action::Rewind(on) => {
+                    rewind = on;
+                    continue;
+                }
+                Action::Pause => {
+                    paused = !paused;
+                    continue;
+                }
+                Action::Mute => {
+                    muted = !muted;
+                    continue;
+                }
+                Action::Fullscreen => {
+                    renderer.toggle_fullscreen();
+                    continue;
+                }
+                Action::Redraw => {
+                    renderer.present()?;
+                    continue;
+                }
+                Action::Screenshot => {
+                    let screenshot = Screenshot {
+                        palette,
+                        ..frontend.screenshot
+                    };
+                    let path =
+                        capture_path(&frontend.screenshots, rom, screenshot.format.extension());
+                    match screenshot.save(chip8.frame(), chip8.resolution(), &path) {
+                        Ok(()) => Ok(format!("saved screenshot to {}", path.display())),
+                        Err(e) => Err(format!("unable to save screenshot: {}", e)),
+                    }
+                }
+                Action::Theme => {
+                    let theme = Theme::after(&palette);
+                    palette = theme.palette();
+                    renderer.set_palette(palette);
+                    renderer.present()?;
+                    if let Some(recorder) = recording.as_mut() {
+                        recorder.set_palette(palette);
+                    }
+                    Ok(format!("switched to the {} theme", theme))
+                }
+                Action::Gif => match recording.take() {
+                    Some(recorder) => save_gif(&recorder, &frontend.screenshots, rom),
+                    None => {
+                        recording = Some(GifRecorder::new(Gif {
+                            palette,
+                            ..frontend.gif
+                        }));
+                        Ok(String::from("recording GIF"))
+                    }
+                },
+                Action::Save(slot) => match frontend.slots.save(chip8, slot) {
+                    Ok(()) => Ok(format!("saved state to slot {}", slot)),
+                    Err(e) => Err(format!("unable to save to slot {}: {}", slot, e)),
+                },
+                Action::Load(_) if chip8.is_playing() || chip8.is_recording() => {
+                    Err(String::from("save states can't be loaded during a movie"))
+                }
+                Action::Load(slot) => match frontend.slots.load(chip8, slot) {
+                    Ok(()) => Ok(format!("loaded state from slot {}", slot)),
+                    Err(e) => Err(format!("unable to load slot {}: {}", slot, e)),
+                },
+            };
+            controls.report(renderer, message)?;
+        }
+
+        // Update state by a frame; errors that halt execution are reported at the start of the next one
+        if rewind {
+            chip8.reverse_frame();
+        } else if !paused {
+            let _ = chip8.run_frame();
+        }
+
+        // Record what's on screen every frame it's running, forwards or backwards
+        if let Some(recorder) = recording.as_mut().filter(|_| !paused) {
+            recorder.capture(chip8.frame(), chip8.resolution());
+        }
+
+        // Only play the tone while the game is running forwards
+        controls.set_sounding(chip8.is_sounding() && !rewind && !paused && !muted);
+
+        // Wait for the next frame; if we've fallen behind then don't try to catch up
+        let now = Instant::now();
+        if fast_forward || next_frame < now {
+            next_frame = now;
+        } else {
+            std::thread::sleep(next_frame - now);
+        }
+        next_frame += frame_time;
+    }
+
+    Ok(recording)
+}
+
+/// Saves a GIF recording next to the screenshots, describing where it was saved or why it couldn't be
+///
+/// # Arguments
+/// * `recorder` the recording
+/// * `directory` where screenshots are saved
+/// * `rom` the path of the ROM being run
+pub(crate) fn save_gif(
+    recorder: &GifRecorder,
+    directory: &Path,
+    rom: &Path,
+) -> Result<String, String> {
+    let path = capture_path(directory, rom, "gif");
+    match recorder.save(&path) {
+        Ok(()) => Ok(format!(
+            "saved {} frames of GIF to {}",
+            recorder.frames(),
+            path.display()
+        )),
+        Err(e) => Err(format!("unable to save GIF: {}", e)),
+    }
+}
+
+/// The first unused path of the form `<directory>/<ROM name>-<n>.<extension>`, counting from 1
+///
+/// # Arguments
+/// * `directory` where screenshots are saved
+/// * `rom` the path of the ROM being run
+/// * `extension` the extension of the file's format
+fn capture_path(directory: &Path, rom: &Path, extension: &str) -> PathBuf {
+    let name = rom.file_stem().unwrap_or_default().to_string_lossy();
+    (1..)
+        .map(|n| directory.join(format!("{}-{}.{}", name, n, extension)))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use display::{ImageRenderer, NullRenderer};
+
+    /// Takes a list of actions each frame, then quits, keeping what it's told
+    struct Script {
+        frames: std::vec::IntoIter<Vec<Action>>,
+        reports: Vec<Result<String, String>>,
+    }
+
+    impl Script {
+        fn new(frames: Vec<Vec<Action>>) -> Self {
+            Script {
+                frames: frames.into_iter(),
+                reports: Vec::new(),
+            }
+        }
+    }
+
+    impl Controls for Script {
+        fn poll(&mut self) -> Result<Vec<Action>, Error> {
+            Ok(self.frames.next().unwrap_or_else(|| vec![Action::Quit]))
+        }
+
+        fn report(
+            &mut self,
+            _renderer: &mut dyn Renderer,
+            message: Result<String, String>,
+        ) -> Result<(), Error> {
+            self.reports.push(message);
+            Ok(())
+        }
+    }
+
+    fn frontend() -> Frontend {
+        Frontend {
+            palette: Palette::default(),
+            window: Window::default(),
+            persistence: None,
+            tone: Tone::default(),
+            slots: Slots::new(std::env::temp_dir().join("emu8_test_play_states")),
+            screenshot: Screenshot::default(),
+            gif: Gif::default(),
+            screenshots: std::env::temp_dir(),
+        }
+    }
+
+    /// 00E0: clear the screen, A000: point I at the sprite for 0, D015: draw it, 1206: loop forever
+    fn chip8() -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8
+            .load_rom(&mut &[0x00, 0xE0, 0xA0, 0x00, 0xD0, 0x15, 0x12, 0x06][..])
+            .unwrap();
+        chip8
+    }
+
+    #[test]
+    fn test_play_presents_frames_in_the_chosen_theme() {
+        let mut renderer = ImageRenderer::new(Palette::default(), None);
+        let mut script = Script::new(vec![
+            vec![Action::FastForward(true)],
+            vec![],
+            vec![Action::Theme],
+        ]);
+        let rom = Path::new("zero.ch8");
+        play(rom, &mut chip8(), &mut renderer, &mut script, &frontend()).unwrap();
+        let theme = Theme::after(&Palette::default());
+        let palette = theme.palette();
+        assert_eq!(renderer.size(), (64, 32));
+        // The top of the 0 is lit, and its middle isn't
+        assert_eq!(renderer.pixel(0, 0), Some(palette.color(1)));
+        assert_eq!(renderer.pixel(1, 1), Some(palette.color(0)));
+        assert_eq!(
+            script.reports,
+            [Ok(format!("switched to the {} theme", theme))]
+        );
+    }
+
+    #[test]
+    fn test_play_returns_the_gif_being_recorded() {
+        let mut script = Script::new(vec![
+            vec![Action::FastForward(true), Action::Gif],
+            vec![],
+            vec![Action::Pause],
+            vec![],
+        ]);
+        let recording = play(
+            Path::new("zero.ch8"),
+            &mut chip8(),
+            &mut NullRenderer,
+            &mut script,
+            &frontend(),
+        )
+        .unwrap();
+        // Paused frames aren't recorded
+        assert_eq!(recording.map(|recorder| recorder.frames()), Some(2));
+        assert_eq!(script.reports, [Ok(String::from("recording GIF"))]);
+    }
+
+    #[test]
+    fn test_play_reports_halts_in_the_title() {
+        let mut renderer = ImageRenderer::new(Palette::default(), None);
+        let mut script = Script::new(vec![vec![Action::FastForward(true)], vec![]]);
+        // Empty memory halts on the unknown opcode 0000
+        let mut chip8 = Chip8::new();
+        play(
+            Path::new("empty.ch8"),
+            &mut chip8,
+            &mut renderer,
+            &mut script,
+            &frontend(),
+        )
+        .unwrap();
+        assert!(renderer
+            .title()
+            .starts_with("Emu-8 - halted: unknown opcode"));
+        assert_eq!(script.reports, [Err(chip8.diagnostic().unwrap())]);
+    }
+}
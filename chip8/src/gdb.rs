@@ -0,0 +1,562 @@
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+
+use crate::chip8::{Chip8, Options};
+use crate::session::Session;
+use core::error;
+
+/// How many cycles `continue` executes between checks for an interrupt from the client
+const INTERRUPT_CHECK_CYCLES: usize = 1000;
+
+/// The largest packet the client may send, which is advertised in reply to `qSupported`
+const PACKET_SIZE: usize = 0x4000;
+
+/// The byte a client sends to interrupt execution, e.g. when Ctrl-C is pressed in gdb
+const INTERRUPT: u8 = 0x03;
+
+/// The register numbers after the V registers
+const REGISTER_I: usize = 0x10;
+const REGISTER_PC: usize = 0x11;
+const REGISTER_SP: usize = 0x12;
+
+/// Signals reported in stop replies
+const SIGINT: u8 = 0x02;
+const SIGILL: u8 = 0x04;
+const SIGTRAP: u8 = 0x05;
+const SIGSEGV: u8 = 0x0B;
+
+/// Waits for a GDB remote serial protocol client to connect on a local port, then lets it control a ROM
+///
+/// # Arguments
+/// * `rom` the path of the ROM to debug
+/// * `options` how the Chip-8 should be configured
+/// * `session` what to log, play back, and record while the ROM runs
+/// * `port` the local port to listen on
+pub fn gdb(rom: PathBuf, options: Options, mut session: Session, port: u16) -> Result<(), Error> {
+    let mut chip8 = Chip8::with_options(Options {
+        rewind_frames: 0,
+        ..options
+    });
+    chip8.load_rom(&mut BufReader::new(File::open(rom)?))?;
+    session.start(&mut chip8)?;
+
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    eprintln!("waiting for a gdb client on {}", listener.local_addr()?);
+    let (stream, address) = listener.accept()?;
+    eprintln!("gdb client connected from {}", address);
+    let mut server = Server::new(chip8);
+    server.serve(stream)?;
+    session.finish(&mut server.chip8)
+}
+
+/// # Server
+/// Serves the GDB remote serial protocol, mapping its registers, memory, breakpoints, and execution
+/// onto a Chip8.
+///
+/// Registers are numbered `v0`-`vf` (0-15), `i` (16), `pc` (17), and `sp` (18). Their layout is
+/// described to the client by `target.xml`, which names no architecture, so clients read registers
+/// in their host's byte order; they're sent little-endian as that's what the usual hosts use.
+///
+/// - `chip8` the machine being debugged
+/// - `breakpoints` the addresses execution stops at
+/// - `no_ack` whether the client has turned off acknowledging packets
+pub struct Server {
+    chip8: Chip8,
+    breakpoints: BTreeSet<u16>,
+    no_ack: bool,
+}
+
+/// What the server should do after replying to a packet
+enum Reply {
+    Packet(String),
+    Detach(String),
+    Kill,
+}
+
+impl Server {
+    pub fn new(chip8: Chip8) -> Self {
+        Server {
+            chip8,
+            breakpoints: BTreeSet::new(),
+            no_ack: false,
+        }
+    }
+
+    /// Answers packets from a connected client until it detaches, kills the ROM, or disconnects
+    ///
+    /// # Arguments
+    /// * `stream` the connection to the client
+    pub fn serve(&mut self, stream: TcpStream) -> Result<(), Error> {
+        let mut writer = stream.try_clone()?;
+        let mut reader = BufReader::new(stream);
+        while let Some(packet) = self.read_packet(&mut reader, &mut writer)? {
+            match self.handle(&packet, &mut reader)? {
+                Reply::Packet(reply) => write_packet(&mut writer, &reply)?,
+                Reply::Detach(reply) => return write_packet(&mut writer, &reply),
+                Reply::Kill => return Ok(()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the next packet, acknowledging it unless acknowledgements are off
+    /// Returns None once the client disconnects
+    ///
+    /// # Arguments
+    /// * `reader` where packets are read from
+    /// * `writer` where acknowledgements are written to
+    fn read_packet(
+        &self,
+        reader: &mut BufReader<TcpStream>,
+        writer: &mut TcpStream,
+    ) -> Result<Option<String>, Error> {
+        loop {
+            let mut start = Vec::new();
+            if reader.read_until(b'$', &mut start)? == 0 || start.last() != Some(&b'$') {
+                return Ok(None);
+            }
+            let mut packet = Vec::new();
+            reader.read_until(b'#', &mut packet)?;
+            let mut checksum = [0; 2];
+            reader.read_exact(&mut checksum)?;
+            if packet.pop() != Some(b'#') {
+                return Ok(None);
+            }
+            let valid = std::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|checksum| u8::from_str_radix(checksum, 16).ok())
+                == Some(checksum_of(&packet));
+            if !self.no_ack {
+                writer.write_all(if valid { b"+" } else { b"-" })?;
+            }
+            if valid {
+                return Ok(Some(String::from_utf8_lossy(&packet).into_owned()));
+            }
+        }
+    }
+
+    /// Executes a packet's command
+    ///
+    /// # Arguments
+    /// * `packet` the contents of the packet
+    /// * `reader` the connection, which is checked for interrupts while continuing
+    fn handle(&mut self, packet: &str, reader: &mut BufReader<TcpStream>) -> Result<Reply, Error> {
+        let command = match packet.char_indices().nth(1) {
+            Some((end, _)) => packet.split_at(end),
+            None => (packet, ""),
+        };
+        let reply = match command {
+            ("?", _) => stop_reply(SIGTRAP),
+            ("g", _) => (0..=REGISTER_SP).map(|r| self.register(r)).collect(),
+            ("G", values) => self.write_registers(values),
+            ("p", register) => match usize::from_str_radix(register, 16) {
+                Ok(register) if register <= REGISTER_SP => self.register(register),
+                _ => error_reply(),
+            },
+            ("P", assignment) => match assignment.split_once('=') {
+                Some((register, value)) => match usize::from_str_radix(register, 16) {
+                    Ok(register) if register <= REGISTER_SP => self.write_register(register, value),
+                    _ => error_reply(),
+                },
+                None => error_reply(),
+            },
+            ("m", range) => self.read_memory(range).unwrap_or_else(error_reply),
+            ("M", write) => self.write_memory(write).unwrap_or_else(error_reply),
+            ("s", address) => {
+                self.resume_at(address);
+                self.step()
+            }
+            ("c", address) => {
+                self.resume_at(address);
+                self.resume(reader)?
+            }
+            ("Z", breakpoint) | ("z", breakpoint) => match parse_breakpoint(breakpoint) {
+                Some(address) if packet.starts_with('Z') => {
+                    self.breakpoints.insert(address);
+                    "OK".to_string()
+                }
+                Some(address) => {
+                    self.breakpoints.remove(&address);
+                    "OK".to_string()
+                }
+                None => String::new(),
+            },
+            ("H", _) => "OK".to_string(),
+            ("D", _) => return Ok(Reply::Detach("OK".to_string())),
+            ("k", _) => return Ok(Reply::Kill),
+            _ => self.query(packet),
+        };
+        Ok(Reply::Packet(reply))
+    }
+
+    /// Answers general queries, replying empty to anything unsupported
+    ///
+    /// # Arguments
+    /// * `packet` the contents of the packet
+    fn query(&mut self, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            return format!(
+                "PacketSize={:x};qXfer:features:read+;QStartNoAckMode+",
+                PACKET_SIZE
+            );
+        }
+        if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            return read_target_xml(range).unwrap_or_else(error_reply);
+        }
+        match packet {
+            "QStartNoAckMode" => {
+                self.no_ack = true;
+                "OK".to_string()
+            }
+            "qAttached" => "1".to_string(),
+            "qC" => "QC1".to_string(),
+            "qfThreadInfo" => "m1".to_string(),
+            "qsThreadInfo" => "l".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    /// Formats a register's value as hex
+    ///
+    /// # Arguments
+    /// * `register` the register's number
+    fn register(&self, register: usize) -> String {
+        let state = self.chip8.state();
+        match register {
+            REGISTER_I => encode_hex(&state.i.to_le_bytes()),
+            REGISTER_PC => encode_hex(&state.pc.to_le_bytes()),
+            REGISTER_SP => format!("{:02x}", state.sp),
+            v => format!("{:02x}", state.v[v]),
+        }
+    }
+
+    /// Sets a register from a little-endian hex value
+    /// The stack pointer can't be changed, but writing its current value succeeds so `G` packets work
+    ///
+    /// # Arguments
+    /// * `register` the register's number
+    /// * `value` the value in hex
+    fn write_register(&mut self, register: usize, value: &str) -> String {
+        let value = match decode_hex(value).as_deref() {
+            Some([low]) => u16::from(*low),
+            Some([low, high]) => u16::from_le_bytes([*low, *high]),
+            _ => return error_reply(),
+        };
+        match register {
+            REGISTER_I => self.chip8.set_i(value),
+            REGISTER_PC => self.chip8.set_pc(value),
+            REGISTER_SP if value == u16::from(self.chip8.state().sp) => (),
+            REGISTER_SP => return error_reply(),
+            v => self.chip8.set_v(v, value as u8),
+        }
+        "OK".to_string()
+    }
+
+    /// Sets every register from their hex values laid out as in a `g` reply
+    ///
+    /// # Arguments
+    /// * `values` the registers' values
+    fn write_registers(&mut self, values: &str) -> String {
+        let mut offset = 0;
+        for register in 0..=REGISTER_SP {
+            let width = self.register(register).len();
+            let reply = match values.get(offset..offset + width) {
+                Some(value) => self.write_register(register, value),
+                None => error_reply(),
+            };
+            if reply != "OK" {
+                return reply;
+            }
+            offset += width;
+        }
+        "OK".to_string()
+    }
+
+    /// Reads memory requested as `addr,length`
+    ///
+    /// # Arguments
+    /// * `range` the address and length in hex
+    fn read_memory(&self, range: &str) -> Option<String> {
+        let (address, len) = parse_range(range)?;
+        let memory = &self.chip8.state().memory;
+        let bytes = memory.get(address..address.checked_add(len)?)?;
+        Some(encode_hex(bytes))
+    }
+
+    /// Writes memory requested as `addr,length:XX...`
+    ///
+    /// # Arguments
+    /// * `write` the address, length, and bytes in hex
+    fn write_memory(&mut self, write: &str) -> Option<String> {
+        let (range, data) = write.split_once(':')?;
+        let (address, len) = parse_range(range)?;
+        let bytes = decode_hex(data)?;
+        if bytes.len() != len {
+            return None;
+        }
+        self.chip8.write_memory(address as u16, &bytes).ok()?;
+        Some("OK".to_string())
+    }
+
+    /// Moves the pc before resuming if the client gave an address to resume at
+    ///
+    /// # Arguments
+    /// * `address` the address in hex, or empty to resume where execution stopped
+    fn resume_at(&mut self, address: &str) {
+        if let Ok(address) = u16::from_str_radix(address, 16) {
+            self.chip8.set_pc(address);
+        }
+    }
+
+    /// Executes a single instruction
+    fn step(&mut self) -> String {
+        let _ = self.chip8.step();
+        self.stop_reason().unwrap_or_else(|| stop_reply(SIGTRAP))
+    }
+
+    /// Executes until a breakpoint is hit, execution halts or exits, or the client interrupts
+    ///
+    /// # Arguments
+    /// * `reader` the connection, which is checked for interrupts
+    fn resume(&mut self, reader: &mut BufReader<TcpStream>) -> Result<String, Error> {
+        loop {
+            for _ in 0..INTERRUPT_CHECK_CYCLES {
+                let _ = self.chip8.step();
+                if let Some(reason) = self.stop_reason() {
+                    return Ok(reason);
+                }
+                if self.breakpoints.contains(&self.chip8.state().pc) {
+                    return Ok(stop_reply(SIGTRAP));
+                }
+            }
+            if interrupted(reader)? {
+                return Ok(stop_reply(SIGINT));
+            }
+        }
+    }
+
+    /// The stop reply for a ROM that has halted or exited, if it has
+    fn stop_reason(&self) -> Option<String> {
+        match self.chip8.error() {
+            Some(error) => Some(stop_reply(match error.kind {
+                error::ErrorKind::UnknownOpcode => SIGILL,
+                _ => SIGSEGV,
+            })),
+            None if self.chip8.has_exited() => Some("W00".to_string()),
+            None => None,
+        }
+    }
+}
+
+/// Returns whether the client has sent an interrupt, without waiting for one
+///
+/// # Arguments
+/// * `reader` the connection to the client
+fn interrupted(reader: &mut BufReader<TcpStream>) -> Result<bool, Error> {
+    if reader.buffer().is_empty() {
+        reader.get_ref().set_nonblocking(true)?;
+        let filled = reader.fill_buf().map(|_| ());
+        reader.get_ref().set_nonblocking(false)?;
+        match filled {
+            Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(false),
+            result => result?,
+        }
+    }
+    match reader.buffer().first() {
+        Some(&INTERRUPT) => {
+            reader.consume(1);
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Writes a packet framed as `$data#checksum`
+///
+/// # Arguments
+/// * `writer` the connection to the client
+/// * `data` the contents of the packet
+fn write_packet(writer: &mut TcpStream, data: &str) -> Result<(), Error> {
+    write!(writer, "${}#{:02x}", data, checksum_of(data.as_bytes()))?;
+    writer.flush()
+}
+
+/// The sum of a packet's bytes modulo 256
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+/// A stop reply reporting a signal
+fn stop_reply(signal: u8) -> String {
+    format!("S{:02x}", signal)
+}
+
+/// A reply reporting that a command failed
+fn error_reply() -> String {
+    "E01".to_string()
+}
+
+/// Parses `addr,length` in hex
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    let (address, len) = range.split_once(',')?;
+    Some((
+        usize::from_str_radix(address, 16).ok()?,
+        usize::from_str_radix(len, 16).ok()?,
+    ))
+}
+
+/// Parses the address of a breakpoint written as `type,addr,kind`
+fn parse_breakpoint(breakpoint: &str) -> Option<u16> {
+    let mut fields = breakpoint.split(',');
+    match (fields.next(), fields.next()) {
+        (Some("0"), Some(address)) | (Some("1"), Some(address)) => {
+            u16::from_str_radix(address, 16).ok()
+        }
+        _ => None,
+    }
+}
+
+/// Encodes bytes as pairs of hex digits
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Decodes pairs of hex digits into bytes
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Reads part of the target description requested as `offset,length`
+fn read_target_xml(range: &str) -> Option<String> {
+    let (offset, len) = parse_range(range)?;
+    let xml = target_xml();
+    let part = xml.get(offset.min(xml.len())..(offset + len).min(xml.len()))?;
+    let more = if offset + len < xml.len() { 'm' } else { 'l' };
+    Some(format!("{}{}", more, part))
+}
+
+/// Describes the registers to the client
+fn target_xml() -> String {
+    let v: String = (0..16)
+        .map(|v| format!(r#"<reg name="v{:x}" bitsize="8" type="uint8"/>"#, v))
+        .collect();
+    format!(
+        concat!(
+            r#"<?xml version="1.0"?><!DOCTYPE target SYSTEM "gdb-target.dtd">"#,
+            r#"<target version="1.0"><feature name="org.emu8.chip8">{}"#,
+            r#"<reg name="i" bitsize="16" type="data_ptr"/>"#,
+            r#"<reg name="pc" bitsize="16" type="code_ptr"/>"#,
+            r#"<reg name="sp" bitsize="8" type="uint8"/>"#,
+            r#"</feature></target>"#
+        ),
+        v
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    /// A scripted client that sends packets and returns the replies
+    struct Client {
+        stream: TcpStream,
+    }
+
+    impl Client {
+        fn send(&mut self, data: &str) -> String {
+            write_packet(&mut self.stream, data).unwrap();
+            let mut ack = [0];
+            self.stream.read_exact(&mut ack).unwrap();
+            assert_eq!(ack, *b"+");
+            self.reply()
+        }
+
+        fn reply(&mut self) -> String {
+            let mut reply = Vec::new();
+            let mut byte = [0];
+            while byte != *b"#" {
+                self.stream.read_exact(&mut byte).unwrap();
+                reply.push(byte[0]);
+            }
+            let mut checksum = [0; 2];
+            self.stream.read_exact(&mut checksum).unwrap();
+            self.stream.write_all(b"+").unwrap();
+            let reply = String::from_utf8(reply).unwrap();
+            reply[1..reply.len() - 1].to_string()
+        }
+    }
+
+    /// Serves a ROM on a free port, returning a client connected to it
+    fn connect(rom: &'static [u8]) -> (Client, thread::JoinHandle<()>) {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let mut chip8 = Chip8::new();
+            chip8.load_rom(&mut &rom[..]).unwrap();
+            let (stream, _) = listener.accept().unwrap();
+            Server::new(chip8).serve(stream).unwrap();
+        });
+        let stream = TcpStream::connect(address).unwrap();
+        (Client { stream }, server)
+    }
+
+    // 0x200 call 0x206, 0x202 add v1 1, 0x204 jump 0x202, 0x206 load v0 0x2A, 0x208 rts
+    const ROM: &[u8] = &[0x22, 0x06, 0x71, 0x01, 0x12, 0x02, 0x60, 0x2A, 0x00, 0xEE];
+
+    #[test]
+    fn test_gdb_reads_and_writes_registers_and_memory() {
+        let (mut client, server) = connect(ROM);
+        assert!(client
+            .send("qSupported:xmlRegisters=i386")
+            .contains("PacketSize"));
+        assert_eq!(client.send("?"), "S05");
+        assert_eq!(client.send("g"), format!("{}0000000200", "00".repeat(16)));
+        assert_eq!(client.send("P1=2a"), "OK");
+        assert_eq!(client.send("p1"), "2a");
+        assert_eq!(client.send("p12"), "00");
+        assert_eq!(client.send("P12=01"), "E01");
+        assert_eq!(client.send("m200,4"), "22067101");
+        assert_eq!(client.send("M300,2:dead"), "OK");
+        assert_eq!(client.send("m300,2"), "dead");
+        assert_eq!(client.send("mffff,2"), "E01");
+        assert!(client
+            .send("qXfer:features:read:target.xml:0,1000")
+            .starts_with("l<?xml"));
+        assert_eq!(client.send("D"), "OK");
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_gdb_steps_continues_and_interrupts() {
+        let (mut client, server) = connect(ROM);
+        assert_eq!(client.send("s"), "S05");
+        assert_eq!(client.send("p11"), "0602");
+        assert_eq!(client.send("Z0,204,2"), "OK");
+        assert_eq!(client.send("c"), "S05");
+        assert_eq!(client.send("p11"), "0402");
+        assert_eq!(client.send("P11=0002"), "OK");
+        assert_eq!(client.send("p11"), "0002");
+        assert_eq!(client.send("P11=0402"), "OK");
+        assert_eq!(client.send("p0"), "2a");
+        assert_eq!(client.send("z0,204,2"), "OK");
+
+        // Without a breakpoint the ROM loops forever until it's interrupted
+        write_packet(&mut client.stream, "c").unwrap();
+        let mut ack = [0];
+        client.stream.read_exact(&mut ack).unwrap();
+        client.stream.write_all(&[INTERRUPT]).unwrap();
+        assert_eq!(client.reply(), "S02");
+
+        // 0000 isn't an instruction
+        assert_eq!(client.send("c300"), "S04");
+        write_packet(&mut client.stream, "k").unwrap();
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,23 @@
+/// Hashes the contents of a ROM with 64-bit FNV-1a.
+///
+/// Unlike `std`'s hashers the result is stable across builds and platforms,
+/// so it can identify a ROM across sessions and seed its random number generator.
+///
+/// # Arguments
+/// * `rom` the bytes of a ROM
+pub fn rom_hash(rom: &[u8]) -> u64 {
+    rom.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rom_hash_matches_reference_values() {
+        assert_eq!(rom_hash(b""), 0xCBF2_9CE4_8422_2325);
+        assert_eq!(rom_hash(b"a"), 0xAF63_DC4C_8601_EC8C);
+    }
+}
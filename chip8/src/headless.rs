@@ -0,0 +1,246 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Error, Write};
+use std::path::PathBuf;
+
+use crate::chip8::{Chip8, Options};
+use crate::session::Session;
+use core::error::ExecutionError;
+use core::state::{FrameBuffer, Resolution};
+use display::{Gif, GifRecorder, Screenshot};
+
+/// # Limit
+/// How long a headless run should last.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Limit {
+    /// Execute this many CPU cycles
+    Cycles(usize),
+    /// Execute this many 60Hz frames
+    Frames(usize),
+}
+
+/// # Exit Reason
+/// Why a headless run stopped.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExitReason {
+    /// The limit was reached
+    Completed,
+    /// The ROM executed the SUPER-CHIP exit instruction
+    Exited,
+    /// An instruction couldn't be executed
+    Halted(ExecutionError),
+}
+
+impl fmt::Display for ExitReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExitReason::Completed => write!(f, "completed"),
+            ExitReason::Exited => write!(f, "exited"),
+            ExitReason::Halted(error) => write!(f, "halted: {}", error),
+        }
+    }
+}
+
+/// # Outputs
+/// Where the results of a headless run are saved.
+///
+/// - `report` where the report is saved, or `None` to print it
+/// - `screenshot` where the final frame is saved as an image and how it's drawn, if anywhere
+/// - `gif` where every frame is saved as an animated GIF and how it's encoded, if anywhere
+#[derive(Default)]
+pub struct Outputs {
+    pub report: Option<PathBuf>,
+    pub screenshot: Option<(PathBuf, Screenshot)>,
+    pub gif: Option<(PathBuf, Gif)>,
+}
+
+/// Runs a ROM without any video or audio and reports the final state of the machine.
+///
+/// # Arguments
+/// * `rom` the path of the ROM to run
+/// * `options` how the Chip-8 should be configured
+/// * `session` what to log, play back, and record while the ROM runs
+/// * `limit` how long to run the ROM for
+/// * `outputs` where the report, final frame, and animation should be saved
+pub fn headless(
+    rom: PathBuf,
+    options: Options,
+    mut session: Session,
+    limit: Limit,
+    outputs: Outputs,
+) -> Result<ExitReason, Error> {
+    // Nothing rewinds a headless run, so don't spend time saving its history
+    let mut chip8 = Chip8::with_options(Options {
+        rewind_frames: 0,
+        ..options
+    });
+    chip8.load_rom(&mut BufReader::new(File::open(rom)?))?;
+    session.start(&mut chip8)?;
+
+    let mut recorder = outputs.gif.as_ref().map(|(_, gif)| GifRecorder::new(*gif));
+    let reason = run_until_each_frame(&mut chip8, limit, |chip8| {
+        if let Some(recorder) = recorder.as_mut() {
+            recorder.capture(chip8.frame(), chip8.resolution());
+        }
+    });
+    session.finish(&mut chip8)?;
+    if let (Some((path, _)), Some(recorder)) = (&outputs.gif, &recorder) {
+        recorder.save(path)?;
+    }
+    if let Some((path, screenshot)) = outputs.screenshot {
+        screenshot.save(chip8.frame(), chip8.resolution(), &path)?;
+    }
+    let report = report(&chip8, reason);
+    match outputs.report {
+        Some(path) => File::create(path)?.write_all(report.as_bytes())?,
+        None => print!("{}", report),
+    }
+    Ok(reason)
+}
+
+/// Steps a Chip-8 until it reaches the limit, exits, or halts.
+///
+/// # Arguments
+/// * `chip8` the Chip-8 to step
+/// * `limit` how long to run it for
+pub fn run_until(chip8: &mut Chip8, limit: Limit) -> ExitReason {
+    run_until_each_frame(chip8, limit, |_| ())
+}
+
+/// Steps a Chip-8 until it reaches the limit, exits, or halts, passing it to a function at the end
+/// of every 60Hz frame.
+///
+/// # Arguments
+/// * `chip8` the Chip-8 to step
+/// * `limit` how long to run it for
+/// * `frame` what to do with the Chip-8 at the end of each frame
+pub fn run_until_each_frame(
+    chip8: &mut Chip8,
+    limit: Limit,
+    mut frame: impl FnMut(&Chip8),
+) -> ExitReason {
+    let (count, frames) = match limit {
+        Limit::Cycles(cycles) => (cycles, false),
+        Limit::Frames(frames) => (frames, true),
+    };
+    for _ in 0..count {
+        if chip8.has_exited() {
+            break;
+        }
+        let result = if frames {
+            chip8.run_frame()
+        } else {
+            chip8.step()
+        };
+        if let Err(error) = result {
+            return ExitReason::Halted(error);
+        }
+        if chip8.is_frame_boundary() {
+            frame(chip8);
+        }
+    }
+    if chip8.has_exited() {
+        ExitReason::Exited
+    } else {
+        ExitReason::Completed
+    }
+}
+
+/// Describes why a run stopped, the final registers, and the final frame.
+///
+/// # Arguments
+/// * `chip8` the Chip-8 that was run
+/// * `reason` why it stopped
+pub fn report(chip8: &Chip8, reason: ExitReason) -> String {
+    format!(
+        "exit: {}\n{}\n{}",
+        reason,
+        chip8.registers(),
+        frame_to_text(chip8.frame(), chip8.resolution())
+    )
+}
+
+/// Formats the portion of a FrameBuffer in use as text, one line per row.
+/// Pixels are drawn by the planes they're set in: `.` for none, `#` the first, `+` the second, `@` both.
+///
+/// # Arguments
+/// * `frame` a Chip-8 FrameBuffer
+/// * `resolution` the portion of the FrameBuffer in use
+pub fn frame_to_text(frame: &FrameBuffer, resolution: Resolution) -> String {
+    const PIXELS: [char; 4] = ['.', '#', '+', '@'];
+    frame
+        .iter()
+        .take(resolution.height())
+        .flat_map(|row| {
+            row.iter()
+                .take(resolution.width())
+                .map(|&pixel| PIXELS[(pixel & 0x3) as usize])
+                .chain(std::iter::once('\n'))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_to_text() {
+        let mut frame: FrameBuffer = [[0; 128]; 64];
+        frame[0][0..4].copy_from_slice(&[0, 1, 2, 3]);
+        let text = frame_to_text(&frame, Resolution::Low);
+        let rows: Vec<&str> = text.lines().collect();
+        assert_eq!(rows.len(), 32);
+        assert!(rows.iter().all(|row| row.len() == 64));
+        assert!(rows[0].starts_with(".#+@."));
+    }
+
+    #[test]
+    fn test_run_until_cycle_limit() {
+        let mut chip8 = Chip8::new();
+        // 7001: add 1 to V0, 1200: jump back to 0x200
+        chip8
+            .write_memory(0x200, &[0x70, 0x01, 0x12, 0x00])
+            .unwrap();
+        assert_eq!(
+            run_until(&mut chip8, Limit::Cycles(6)),
+            ExitReason::Completed
+        );
+        assert_eq!(chip8.state().v[0x0], 3);
+    }
+
+    #[test]
+    fn test_run_until_each_frame() {
+        let mut chip8 = Chip8::with_options(Options {
+            instructions_per_frame: 4,
+            ..Options::default()
+        });
+        // 7001: add 1 to V0, 1200: jump back to 0x200
+        chip8
+            .write_memory(0x200, &[0x70, 0x01, 0x12, 0x00])
+            .unwrap();
+        let mut frames = Vec::new();
+        run_until_each_frame(&mut chip8, Limit::Cycles(10), |chip8| {
+            frames.push(chip8.state().v[0x0])
+        });
+        assert_eq!(frames, [2, 4]);
+
+        frames.clear();
+        run_until_each_frame(&mut chip8, Limit::Frames(2), |chip8| {
+            frames.push(chip8.state().v[0x0])
+        });
+        assert_eq!(frames, [6, 8]);
+    }
+
+    #[test]
+    fn test_run_until_exit_or_halt() {
+        let mut chip8 = Chip8::new();
+        chip8.write_memory(0x200, &[0x00, 0xFD]).unwrap();
+        assert_eq!(run_until(&mut chip8, Limit::Frames(10)), ExitReason::Exited);
+
+        let mut chip8 = Chip8::new();
+        let reason = run_until(&mut chip8, Limit::Frames(10));
+        assert!(matches!(reason, ExitReason::Halted(error) if error.pc == 0x200));
+        assert!(report(&chip8, reason).starts_with("exit: halted: unknown opcode"));
+    }
+}
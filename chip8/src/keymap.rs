@@ -31,3 +31,20 @@ pub fn keymap(key: Keycode) -> Option<u8> {
         _ => None,
     }
 }
+
+/// Maps the function keys onto save state slots, `F1` to slot 1 through `F10` to slot 10
+pub fn slot(key: Keycode) -> Option<usize> {
+    match key {
+        Keycode::F1 => Some(1),
+        Keycode::F2 => Some(2),
+        Keycode::F3 => Some(3),
+        Keycode::F4 => Some(4),
+        Keycode::F5 => Some(5),
+        Keycode::F6 => Some(6),
+        Keycode::F7 => Some(7),
+        Keycode::F8 => Some(8),
+        Keycode::F9 => Some(9),
+        Keycode::F10 => Some(10),
+        _ => None,
+    }
+}
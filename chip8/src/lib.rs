@@ -1,5 +1,52 @@
+//! An emulator for the Chip-8 and its SUPER-CHIP and XO-CHIP extensions.
+//!
+//! [`Chip8`] can be embedded in other tools to step through ROMs, inspect their state, and grab frames:
+//! ```
+//! use chip8::{Chip8, Options};
+//!
+//! let mut chip8 = Chip8::with_options(Options::default());
+//! // 00E0: clear the screen, A000: point I at the sprite for 0, D015: draw it
+//! chip8
+//!     .load_rom(&mut &[0x00, 0xE0, 0xA0, 0x00, 0xD0, 0x15][..])
+//!     .unwrap();
+//! chip8.run_cycles(3).unwrap();
+//! assert_eq!(chip8.frame()[0][..4], [1, 1, 1, 1]);
+//! ```
+//!
+//! [`headless::headless`] runs a ROM without any video or audio, with the default `sdl` feature
+//! `run` opens a window and plays a ROM interactively, and with the `terminal` feature `terminal`
+//! plays it in the terminal it's run from. Both drive [`play`], which other frontends can drive
+//! too with any [`display::Renderer`] and their own [`Controls`].
+pub use crate::chip8::{Chip8, Options};
+pub use core::error::{ErrorKind, ExecutionError};
+pub use core::quirks::{Profile, Quirks};
+pub use core::state::{FrameBuffer, Resolution, State};
+pub use frontend::{play, Action, Controls, Frontend};
+#[cfg(feature = "sdl")]
 pub use run::run;
+pub use session::Session;
+#[cfg(feature = "terminal")]
+pub use terminal::terminal;
+pub use tone::{Tone, Waveform};
+pub use trace::Trace;
 
+#[cfg(feature = "sdl")]
+mod audio;
 mod chip8;
+pub mod debug;
+pub mod frontend;
+pub mod gdb;
+mod hash;
+pub mod headless;
+#[cfg(feature = "sdl")]
 mod keymap;
+pub mod movie;
+mod rewind;
+#[cfg(feature = "sdl")]
 pub mod run;
+pub mod savestate;
+mod session;
+#[cfg(feature = "terminal")]
+pub mod terminal;
+pub mod tone;
+pub mod trace;
@@ -0,0 +1,347 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use core::quirks::{Quirks, QUIRKS_SIZE};
+
+/// Identifies a file as an emu8 movie
+const MAGIC: &[u8; 8] = b"EMU8MOVI";
+
+/// The version of the movie format, which changes whenever the layout of its bytes does
+pub const VERSION: u16 = 1;
+
+/// The number of bytes before the events: magic, version, ROM hash, seed, instructions per frame,
+/// quirks, and the number of events
+const HEADER_SIZE: usize = 8 + 2 + 8 + 8 + 4 + QUIRKS_SIZE + 4;
+
+/// The number of bytes each event is stored in: its cycle, key, and whether it was pressed
+const EVENT_SIZE: usize = 8 + 1 + 1;
+
+/// # KeyEvent
+/// A key being pressed or released.
+///
+/// - `cycle` the number of cycles executed before the key changed
+/// - `key` which key changed
+/// - `pressed` whether it was pressed or released
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub cycle: u64,
+    pub key: u8,
+    pub pressed: bool,
+}
+
+/// # MovieError
+/// Why a movie couldn't be saved, loaded, or played.
+#[derive(Debug)]
+pub enum MovieError {
+    /// The file couldn't be read or written
+    Io(io::Error),
+    /// The file isn't a movie
+    NotAMovie,
+    /// The file was saved in a format this version of emu8 can't play
+    IncompatibleVersion { version: u16 },
+    /// The movie was recorded while playing a different ROM
+    DifferentRom { rom_hash: u64 },
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MovieError::Io(e) => write!(f, "{}", e),
+            MovieError::NotAMovie => write!(f, "not an emu8 movie"),
+            MovieError::IncompatibleVersion { version } => write!(
+                f,
+                "movie is version {} but only version {} can be played",
+                version, VERSION
+            ),
+            MovieError::DifferentRom { rom_hash } => write!(
+                f,
+                "movie was recorded with a different ROM (hash {:016x})",
+                rom_hash
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MovieError {}
+
+impl From<io::Error> for MovieError {
+    fn from(e: io::Error) -> Self {
+        MovieError::Io(e)
+    }
+}
+
+/// # Movie
+/// Every key press and release from the moment a ROM was loaded, along with everything else that
+/// determines how it runs, so that playing it back reproduces the session exactly.
+///
+/// - `rom_hash` identifies the ROM the movie was recorded with
+/// - `seed` the seed of the random number generator
+/// - `instructions_per_frame` how many cycles pass between ticks of the timers
+/// - `quirks` which interpretation of ambiguous opcodes was followed
+/// - `events` the key presses and releases, ordered by cycle
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Movie {
+    pub rom_hash: u64,
+    pub seed: u64,
+    pub instructions_per_frame: usize,
+    pub quirks: Quirks,
+    pub events: Vec<KeyEvent>,
+}
+
+impl Movie {
+    /// Serializes the movie
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + EVENT_SIZE * self.events.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_be_bytes());
+        bytes.extend_from_slice(&self.rom_hash.to_be_bytes());
+        bytes.extend_from_slice(&self.seed.to_be_bytes());
+        bytes.extend_from_slice(&(self.instructions_per_frame as u32).to_be_bytes());
+        bytes.extend_from_slice(&self.quirks.to_bytes());
+        bytes.extend_from_slice(&(self.events.len() as u32).to_be_bytes());
+        for event in &self.events {
+            bytes.extend_from_slice(&event.cycle.to_be_bytes());
+            bytes.extend_from_slice(&[event.key, event.pressed as u8]);
+        }
+        bytes
+    }
+
+    /// Deserializes a movie from the bytes produced by `to_bytes`
+    ///
+    /// # Arguments
+    /// * `bytes` a serialized movie
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MovieError> {
+        if bytes.len() < HEADER_SIZE || &bytes[..8] != MAGIC {
+            return Err(MovieError::NotAMovie);
+        }
+        let version = u16::from_be_bytes([bytes[8], bytes[9]]);
+        if version != VERSION {
+            return Err(MovieError::IncompatibleVersion { version });
+        }
+        let u32_at = |offset: usize| {
+            let mut word = [0; 4];
+            word.copy_from_slice(&bytes[offset..offset + 4]);
+            u32::from_be_bytes(word)
+        };
+        let u64_at = |offset: usize| {
+            let mut word = [0; 8];
+            word.copy_from_slice(&bytes[offset..offset + 8]);
+            u64::from_be_bytes(word)
+        };
+        let quirks =
+            Quirks::from_bytes(&bytes[30..30 + QUIRKS_SIZE]).ok_or(MovieError::NotAMovie)?;
+        let count = u32_at(HEADER_SIZE - 4) as usize;
+        if bytes.len() != HEADER_SIZE + EVENT_SIZE * count {
+            return Err(MovieError::NotAMovie);
+        }
+        let events = bytes[HEADER_SIZE..]
+            .chunks(EVENT_SIZE)
+            .map(|event| {
+                let mut cycle = [0; 8];
+                cycle.copy_from_slice(&event[..8]);
+                match event[8..] {
+                    [key, pressed] if key < 16 && pressed < 2 => Ok(KeyEvent {
+                        cycle: u64::from_be_bytes(cycle),
+                        key,
+                        pressed: pressed == 1,
+                    }),
+                    _ => Err(MovieError::NotAMovie),
+                }
+            })
+            .collect::<Result<Vec<KeyEvent>, MovieError>>()?;
+        Ok(Movie {
+            rom_hash: u64_at(10),
+            seed: u64_at(18),
+            instructions_per_frame: u32_at(26) as usize,
+            quirks,
+            events,
+        })
+    }
+
+    /// Reads a movie from a file
+    ///
+    /// # Arguments
+    /// * `path` the file to read
+    pub fn load(path: &Path) -> Result<Self, MovieError> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    /// Writes the movie to a file, replacing it if it exists
+    ///
+    /// # Arguments
+    /// * `path` the file to write
+    pub fn save(&self, path: &Path) -> Result<(), MovieError> {
+        Ok(fs::write(path, self.to_bytes())?)
+    }
+
+    /// Which keys are pressed once every event before a cycle has happened
+    ///
+    /// # Arguments
+    /// * `cycle` the cycle to stop before
+    pub fn keys_at(&self, cycle: u64) -> [u8; 16] {
+        let mut keys = [0; 16];
+        for event in self.events.iter().take_while(|event| event.cycle < cycle) {
+            keys[event.key as usize] = event.pressed as u8;
+        }
+        keys
+    }
+
+    /// Drops every event from a cycle onwards, so recording can carry on from an earlier point
+    ///
+    /// # Arguments
+    /// * `cycle` the first cycle to drop events from
+    pub fn truncate(&mut self, cycle: u64) {
+        let kept = self.events.partition_point(|event| event.cycle < cycle);
+        self.events.truncate(kept);
+    }
+}
+
+/// # Playback
+/// A movie being played and the index of the next event to happen.
+pub(crate) struct Playback {
+    pub movie: Movie,
+    next: usize,
+}
+
+impl Playback {
+    pub fn new(movie: Movie) -> Self {
+        Playback { movie, next: 0 }
+    }
+
+    /// Returns the next event if it happens by a cycle, moving past it
+    ///
+    /// # Arguments
+    /// * `cycle` the number of cycles executed so far
+    pub fn next_event(&mut self, cycle: u64) -> Option<KeyEvent> {
+        let event = *self.movie.events.get(self.next)?;
+        if event.cycle > cycle {
+            return None;
+        }
+        self.next += 1;
+        Some(event)
+    }
+
+    /// Moves to the first event at or after a cycle, e.g. after rewinding
+    ///
+    /// # Arguments
+    /// * `cycle` the number of cycles executed so far
+    pub fn seek(&mut self, cycle: u64) {
+        self.next = self
+            .movie
+            .events
+            .partition_point(|event| event.cycle < cycle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8;
+
+    // F00A: wait for a key and store it in V0, C1FF: set V1 to a random byte,
+    // 8104: add V0 to V1, E09E: skip the jump back to 0x200 while the key in V0 is still pressed,
+    // 1200: jump back to 0x200, 1206: jump back to 0x206
+    const ROM: &[u8] = &[
+        0xF0, 0x0A, 0xC1, 0xFF, 0x81, 0x04, 0xE0, 0x9E, 0x12, 0x00, 0x12, 0x06,
+    ];
+
+    fn chip8() -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.load_rom(&mut &ROM[..]).unwrap();
+        chip8
+    }
+
+    /// Runs frames, pressing and releasing keys along the way
+    fn play(chip8: &mut Chip8, script: &[(u8, bool)]) {
+        for &(key, pressed) in script {
+            chip8.run_frame().unwrap();
+            match pressed {
+                true => chip8.key_press(key),
+                false => chip8.key_release(key),
+            }
+        }
+        chip8.run_frame().unwrap();
+    }
+
+    #[test]
+    fn test_playback_reproduces_the_recording() {
+        let mut recorded = chip8();
+        recorded.start_recording();
+        play(
+            &mut recorded,
+            &[
+                (0x5, true),
+                (0x5, false),
+                (0xA, true),
+                (0x3, true),
+                (0xA, false),
+            ],
+        );
+        let movie = Movie::from_bytes(&recorded.take_recording().unwrap().to_bytes()).unwrap();
+        assert_eq!(movie.events.len(), 5);
+
+        let mut played = chip8();
+        played.play(movie).unwrap();
+        played.run_cycles(recorded.state().cycles as usize).unwrap();
+        assert_eq!(played.state().to_bytes(), recorded.state().to_bytes());
+        assert_eq!(played.pressed_keys(), recorded.pressed_keys());
+    }
+
+    #[test]
+    fn test_recording_branches_from_rewind() {
+        let mut recorded = chip8();
+        recorded.start_recording();
+        play(&mut recorded, &[(0x5, true), (0x5, false), (0x7, true)]);
+        recorded.reverse_frame();
+        recorded.reverse_frame();
+        play(&mut recorded, &[(0x2, true)]);
+        let movie = recorded.take_recording().unwrap();
+        // Keys held when recording carries on are pressed at the rewind point
+        let events: Vec<(u64, u8, bool)> = movie
+            .events
+            .iter()
+            .map(|event| (event.cycle, event.key, event.pressed))
+            .collect();
+        assert_eq!(
+            events,
+            [
+                (8, 0x5, true),
+                (16, 0x5, false),
+                (16, 0x7, true),
+                (24, 0x2, true)
+            ]
+        );
+
+        let mut played = chip8();
+        played.play(movie).unwrap();
+        played.run_cycles(recorded.state().cycles as usize).unwrap();
+        assert_eq!(played.state().to_bytes(), recorded.state().to_bytes());
+    }
+
+    #[test]
+    fn test_movies_reject_other_roms_and_versions() {
+        let mut recording = chip8();
+        recording.start_recording();
+        let mut bytes = recording.take_recording().unwrap().to_bytes();
+
+        let mut other = Chip8::new();
+        other.load_rom(&mut &[0x12, 0x00][..]).unwrap();
+        let movie = Movie::from_bytes(&bytes).unwrap();
+        assert!(matches!(
+            other.play(movie),
+            Err(MovieError::DifferentRom { .. })
+        ));
+
+        bytes[9] = 0x2;
+        assert_eq!(
+            Movie::from_bytes(&bytes).unwrap_err().to_string(),
+            "movie is version 2 but only version 1 can be played"
+        );
+        assert!(matches!(
+            Movie::from_bytes(b"not a movie"),
+            Err(MovieError::NotAMovie)
+        ));
+    }
+}
@@ -0,0 +1,277 @@
+use std::collections::VecDeque;
+
+use core::state::State;
+
+/// # Rewind
+/// A bounded history of States, one per frame, that can be rewound through.
+///
+/// States are stored as bytes, and to fit minutes of history in a few megabytes only the oldest is
+/// stored whole as a keyframe; every later one is stored as a delta from the one before it.
+/// A delta lists the runs of bytes that changed, XORed with their previous values.
+/// XOR deltas can be applied in either direction, so rewinding walks back from the newest State
+/// and dropping the oldest State walks the keyframe forward.
+///
+/// Tracks:
+/// - `keyframe` the bytes of the oldest State
+/// - `deltas` from each State to the next, oldest first
+/// - `newest` the bytes of the newest State, so the next delta can be made from it
+/// - `frames` & `memory` how many States and how many bytes the history is limited to
+pub struct Rewind {
+    keyframe: Vec<u8>,
+    deltas: VecDeque<Vec<u8>>,
+    newest: Vec<u8>,
+    delta_bytes: usize,
+    frames: usize,
+    memory: usize,
+}
+
+impl Rewind {
+    /// Creates an empty history
+    ///
+    /// # Arguments
+    /// * `frames` the most States to keep
+    /// * `memory` the most bytes to use, though the newest State is always kept
+    pub fn new(frames: usize, memory: usize) -> Self {
+        Rewind {
+            keyframe: Vec::new(),
+            deltas: VecDeque::new(),
+            newest: Vec::new(),
+            delta_bytes: 0,
+            frames,
+            memory,
+        }
+    }
+
+    /// Returns how many States are held
+    pub fn len(&self) -> usize {
+        if self.newest.is_empty() {
+            0
+        } else {
+            self.deltas.len() + 1
+        }
+    }
+
+    /// Returns how many bytes the history is using
+    pub fn memory_used(&self) -> usize {
+        self.keyframe.len() + self.newest.len() + self.delta_bytes
+    }
+
+    /// Changes how much history is kept, dropping the oldest States if there's now too much
+    ///
+    /// # Arguments
+    /// * `frames` the most States to keep
+    /// * `memory` the most bytes to use
+    pub fn set_limits(&mut self, frames: usize, memory: usize) {
+        self.frames = frames;
+        self.memory = memory;
+        self.trim();
+    }
+
+    /// Adds a State as the newest one, dropping the oldest States if there's too much history
+    ///
+    /// # Arguments
+    /// * `state` the State to add
+    pub fn push(&mut self, state: &State) {
+        if self.frames == 0 {
+            return;
+        }
+        let bytes = state.to_bytes();
+        if self.newest.is_empty() {
+            self.keyframe = bytes.clone();
+        } else {
+            let delta = delta(&self.newest, &bytes);
+            self.delta_bytes += delta.len();
+            self.deltas.push_back(delta);
+        }
+        self.newest = bytes;
+        self.trim();
+    }
+
+    /// Removes and returns the newest State
+    pub fn pop(&mut self) -> Option<State> {
+        if self.newest.is_empty() {
+            return None;
+        }
+        let state = State::from_bytes(&self.newest);
+        match self.deltas.pop_back() {
+            Some(delta) => {
+                self.delta_bytes -= delta.len();
+                apply(&mut self.newest, &delta);
+            }
+            None => {
+                self.keyframe.clear();
+                self.newest.clear();
+            }
+        }
+        state
+    }
+
+    /// Drops the oldest States until the history is within its limits
+    fn trim(&mut self) {
+        if self.frames == 0 {
+            *self = Rewind::new(0, self.memory);
+            return;
+        }
+        while self.len() > self.frames || (self.memory_used() > self.memory && self.len() > 1) {
+            if let Some(delta) = self.deltas.pop_front() {
+                self.delta_bytes -= delta.len();
+                apply(&mut self.keyframe, &delta);
+            }
+        }
+    }
+}
+
+/// Encodes the changes between two equally long byte strings.
+/// Each run of changed bytes is encoded as the number of unchanged bytes before it and its length
+/// (both as LEB128 varints) followed by the XOR of its old and new values.
+///
+/// # Arguments
+/// * `old` the bytes before
+/// * `new` the bytes after
+fn delta(old: &[u8], new: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    let mut position = 0;
+    let mut end = 0;
+    while position < old.len() {
+        if old[position] == new[position] {
+            position += 1;
+            continue;
+        }
+        let start = position;
+        while position < old.len() && old[position] != new[position] {
+            position += 1;
+        }
+        write_varint(&mut encoded, start - end);
+        write_varint(&mut encoded, position - start);
+        encoded.extend(
+            old[start..position]
+                .iter()
+                .zip(&new[start..position])
+                .map(|(a, b)| a ^ b),
+        );
+        end = position;
+    }
+    encoded
+}
+
+/// Applies a delta produced by `delta`, turning its old bytes into its new ones or vice versa
+///
+/// # Arguments
+/// * `bytes` the bytes to change in place
+/// * `delta` the encoded changes
+fn apply(bytes: &mut [u8], delta: &[u8]) {
+    let mut delta = delta;
+    let mut position = 0;
+    while !delta.is_empty() {
+        position += read_varint(&mut delta);
+        let len = read_varint(&mut delta);
+        let (changes, rest) = delta.split_at(len);
+        bytes[position..position + len]
+            .iter_mut()
+            .zip(changes)
+            .for_each(|(byte, change)| *byte ^= change);
+        position += len;
+        delta = rest;
+    }
+}
+
+fn write_varint(encoded: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        encoded.push((value as u8 & 0x7F) | 0x80);
+        value >>= 7;
+    }
+    encoded.push(value as u8);
+}
+
+fn read_varint(encoded: &mut &[u8]) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    while let Some((&byte, rest)) = encoded.split_first() {
+        *encoded = rest;
+        value |= usize::from(byte & 0x7F) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state_with_pc(pc: u16) -> State {
+        State { pc, ..State::new() }
+    }
+
+    #[test]
+    fn test_delta_round_trips() {
+        let old = vec![0, 1, 2, 3, 4, 5, 6, 7];
+        let new = vec![0, 9, 9, 3, 4, 5, 6, 8];
+        let encoded = delta(&old, &new);
+        assert_eq!(encoded, [1, 2, 1 ^ 9, 2 ^ 9, 4, 1, 7 ^ 8]);
+
+        let mut bytes = old.clone();
+        apply(&mut bytes, &encoded);
+        assert_eq!(bytes, new);
+        apply(&mut bytes, &encoded);
+        assert_eq!(bytes, old);
+    }
+
+    #[test]
+    fn test_varint_round_trips() {
+        let mut encoded = Vec::new();
+        write_varint(&mut encoded, 300);
+        assert_eq!(encoded, [0xAC, 0x02]);
+        assert_eq!(read_varint(&mut &encoded[..]), 300);
+    }
+
+    #[test]
+    fn test_rewind_pops_newest_first() {
+        let mut rewind = Rewind::new(10, usize::MAX);
+        (0..3).for_each(|pc| rewind.push(&state_with_pc(pc)));
+        assert_eq!(rewind.len(), 3);
+        assert_eq!(rewind.pop().unwrap().pc, 2);
+        assert_eq!(rewind.pop().unwrap().pc, 1);
+        assert_eq!(rewind.pop().unwrap().pc, 0);
+        assert!(rewind.pop().is_none());
+    }
+
+    #[test]
+    fn test_rewind_drops_oldest_frames() {
+        let mut rewind = Rewind::new(3, usize::MAX);
+        (0..5).for_each(|pc| rewind.push(&state_with_pc(pc)));
+        assert_eq!(rewind.len(), 3);
+        assert_eq!(rewind.pop().unwrap().pc, 4);
+        assert_eq!(rewind.pop().unwrap().pc, 3);
+        assert_eq!(rewind.pop().unwrap().pc, 2);
+        assert!(rewind.pop().is_none());
+    }
+
+    #[test]
+    fn test_rewind_stays_within_memory_budget() {
+        let mut rewind = Rewind::new(usize::MAX, 0);
+        (0..5).for_each(|pc| rewind.push(&state_with_pc(pc)));
+        assert_eq!(rewind.len(), 1);
+        assert_eq!(rewind.pop().unwrap().pc, 4);
+    }
+
+    #[test]
+    fn test_rewind_deltas_are_compact() {
+        let mut rewind = Rewind::new(100, usize::MAX);
+        (0..100).for_each(|pc| rewind.push(&state_with_pc(pc)));
+        // Two whole States plus a few bytes for each of the 99 deltas
+        assert!(rewind.memory_used() < 2 * core::state::STATE_SIZE + 99 * 8);
+    }
+
+    #[test]
+    fn test_rewind_limits_can_shrink() {
+        let mut rewind = Rewind::new(10, usize::MAX);
+        (0..10).for_each(|pc| rewind.push(&state_with_pc(pc)));
+        rewind.set_limits(2, usize::MAX);
+        assert_eq!(rewind.len(), 2);
+        rewind.set_limits(0, usize::MAX);
+        assert_eq!(rewind.len(), 0);
+    }
+}
@@ -1,26 +1,44 @@
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, Error};
 use std::path::PathBuf;
-use std::time::{Duration, Instant};
 
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::EventPump;
 
-use crate::chip8::Chip8;
-use crate::keymap::keymap;
-use core::constants::CLOCK_SPEED;
-use display::Display;
+use crate::audio::Audio;
+use crate::chip8::{Chip8, Options};
+use crate::frontend::{play, save_gif, Action, Controls, Frontend};
+use crate::keymap::{keymap, slot};
+use crate::session::Session;
+use display::{Display, Renderer, Window};
 
-pub fn run(rom: PathBuf) {
-    let mut chip8: Chip8 = Chip8::new();
+pub fn run(rom: PathBuf, options: Options, mut session: Session, frontend: Frontend) {
+    let mut chip8: Chip8 = Chip8::with_options(options);
 
     // Get SDL2 context
     let sdl: sdl2::Sdl = sdl2::init().unwrap();
-    let mut display: Display = Display::new(&sdl);
-    let mut events = sdl.event_pump().unwrap();
+    let mut display: Display = Display::new(
+        &sdl,
+        frontend.palette,
+        &frontend.window,
+        frontend.persistence,
+    );
+    let mut events = Events {
+        pump: sdl.event_pump().unwrap(),
+        // Carry on without sound if there's no audio device
+        audio: Audio::new(&sdl, frontend.tone)
+            .map_err(|e| {
+                eprintln!(
+                    "unable to open audio device, continuing without sound: {}",
+                    e
+                )
+            })
+            .ok(),
+    };
 
     // Load ROM
-    let file = File::open(rom).expect("unable to open file");
+    let file = File::open(&rom).expect("unable to open file");
     let mut reader = BufReader::new(file);
     match chip8.load_rom(&mut reader) {
         Ok(()) => println!("successfully loaded ROM"),
@@ -30,59 +48,103 @@ pub fn run(rom: PathBuf) {
         ),
     };
 
-    // Set initial timing
-    let cycle_time: Duration = Duration::new(0, CLOCK_SPEED as u32);
-    let mut last_cycle: Instant = Instant::now();
-
-    // Whether or not the default clock speed should be respected
-    let mut fast_forward: bool = false;
-    // Whether the game's state should be cycled forwards or backwards
-    let mut rewind: bool = false;
+    if let Err(e) = session.start(&mut chip8) {
+        eprintln!("unable to start session: {}", e);
+        return;
+    }
 
-    'event: loop {
-        // If the draw flag is set, unset it and render the current frame
-        if let Some(frame) = chip8.get_frame() {
-            display.render(&frame);
+    match play(&rom, &mut chip8, &mut display, &mut events, &frontend) {
+        Ok(Some(recorder)) => report(save_gif(&recorder, &frontend.screenshots, &rom)),
+        Ok(None) => (),
+        Err(e) => eprintln!("unable to draw the display: {}", e),
+    }
+    if let Some(path) = frontend.window.remember.as_deref() {
+        if let Err(e) = Window::save_size(path, display.windowed_size()) {
+            eprintln!("unable to remember the window size: {}", e);
         }
+    }
+    if let Err(e) = session.finish(&mut chip8) {
+        eprintln!("unable to finish session: {}", e);
+    }
+}
 
-        // Handle input
-        for event in events.poll_iter() {
-            match event {
-                Event::Quit { .. } => break 'event,
-                Event::KeyDown {
-                    keycode: Some(key), ..
-                } => match (key, keymap(key)) {
-                    (_, Some(kc)) => chip8.key_press(kc),
-                    (Keycode::Space, _) => fast_forward = true,
-                    (Keycode::Escape, _) => rewind = true,
-                    _ => continue,
-                },
-                Event::KeyUp {
-                    keycode: Some(key), ..
-                } => match (key, keymap(key)) {
-                    (_, Some(kc)) => chip8.key_release(kc),
-                    (Keycode::Space, _) => fast_forward = false,
-                    (Keycode::Escape, _) => rewind = false,
-                    _ => continue,
+/// # Events
+/// The window's keyboard and window events, and the tone played alongside it.
+///
+/// - `pump` where SDL events are read from
+/// - `audio` the tone, unless there's no audio device
+struct Events {
+    pump: EventPump,
+    audio: Option<Audio>,
+}
+
+impl Controls for Events {
+    fn poll(&mut self) -> Result<Vec<Action>, Error> {
+        let actions = self.pump.poll_iter().filter_map(|event| match event {
+            Event::Quit { .. } => Some(Action::Quit),
+            // Redraw the letterboxed frame whenever the window changes size or needs repainting
+            Event::Window {
+                win_event: WindowEvent::SizeChanged(..) | WindowEvent::Exposed,
+                ..
+            } => Some(Action::Redraw),
+            Event::KeyDown {
+                keycode: Some(key),
+                keymod,
+                repeat,
+                ..
+            } => match (key, keymap(key)) {
+                (_, Some(kc)) => Some(Action::Press(kc)),
+                (Keycode::Space, _) => Some(Action::FastForward(true)),
+                (Keycode::Escape, _) => Some(Action::Rewind(true)),
+                // Holding a hotkey down triggers it once rather than on every autorepeat
+                _ if repeat => None,
+                (Keycode::P, _) => Some(Action::Pause),
+                (Keycode::M, _) => Some(Action::Mute),
+                (Keycode::F12, _) => Some(Action::Screenshot),
+                (Keycode::F11, _) => Some(Action::Fullscreen),
+                (Keycode::T, _) => Some(Action::Theme),
+                (Keycode::G, _) => Some(Action::Gif),
+                // Shift+F1-F10 saves to a slot and F1-F10 loads from it
+                _ => match slot(key) {
+                    Some(slot) if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) => {
+                        Some(Action::Save(slot))
+                    }
+                    slot => slot.map(Action::Load),
                 },
-                _ => continue,
-            };
-        }
+            },
+            Event::KeyUp {
+                keycode: Some(key), ..
+            } => match (key, keymap(key)) {
+                (_, Some(kc)) => Some(Action::Release(kc)),
+                (Keycode::Space, _) => Some(Action::FastForward(false)),
+                (Keycode::Escape, _) => Some(Action::Rewind(false)),
+                _ => None,
+            },
+            _ => None,
+        });
+        Ok(actions.collect())
+    }
 
-        // Update state
-        if rewind {
-            chip8.reverse_cpu();
-        } else {
-            chip8.advance_cpu();
-            chip8.advance_timers();
-        }
+    fn report(
+        &mut self,
+        _renderer: &mut dyn Renderer,
+        message: Result<String, String>,
+    ) -> Result<(), Error> {
+        report(message);
+        Ok(())
+    }
 
-        // Handle timing
-        let current_time = Instant::now();
-        let elapsed_cycle_time = current_time - last_cycle;
-        if !fast_forward && cycle_time > elapsed_cycle_time {
-            std::thread::sleep(cycle_time - elapsed_cycle_time);
+    fn set_sounding(&mut self, sounding: bool) {
+        if let Some(audio) = self.audio.as_mut() {
+            audio.set_playing(sounding);
         }
-        last_cycle = current_time;
+    }
+}
+
+/// Prints what happened, or what went wrong to stderr
+fn report(message: Result<String, String>) {
+    match message {
+        Ok(message) => println!("{}", message),
+        Err(message) => eprintln!("{}", message),
     }
 }
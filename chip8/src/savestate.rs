@@ -0,0 +1,250 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::chip8::Chip8;
+use core::state::{State, STATE_SIZE};
+
+/// Identifies a file as an emu8 save state
+const MAGIC: &[u8; 8] = b"EMU8SAVE";
+
+/// The version of the save state format, which changes whenever the layout of its bytes does
+pub const VERSION: u16 = 1;
+
+/// How many save state slots each ROM has
+pub const SLOTS: usize = 10;
+
+/// The number of bytes before the serialized State: magic, version, ROM hash, and pressed keys
+const HEADER_SIZE: usize = 8 + 2 + 8 + 16;
+
+/// # SaveStateError
+/// Why a save state couldn't be saved or loaded.
+#[derive(Debug)]
+pub enum SaveStateError {
+    /// The file couldn't be read or written
+    Io(io::Error),
+    /// The file isn't a save state
+    NotASaveState,
+    /// The file was saved in a format this version of emu8 can't load
+    IncompatibleVersion { version: u16 },
+    /// The file was saved while playing a different ROM
+    DifferentRom { rom_hash: u64 },
+    /// The slot isn't one of the `SLOTS` slots
+    InvalidSlot { slot: usize },
+}
+
+impl fmt::Display for SaveStateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveStateError::Io(e) => write!(f, "{}", e),
+            SaveStateError::NotASaveState => write!(f, "not an emu8 save state"),
+            SaveStateError::IncompatibleVersion { version } => write!(
+                f,
+                "save state is version {} but only version {} can be loaded",
+                version, VERSION
+            ),
+            SaveStateError::DifferentRom { rom_hash } => write!(
+                f,
+                "save state is for a different ROM (hash {:016x})",
+                rom_hash
+            ),
+            SaveStateError::InvalidSlot { slot } => {
+                write!(f, "slot {} doesn't exist; slots are 1 to {}", slot, SLOTS)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SaveStateError {}
+
+impl From<io::Error> for SaveStateError {
+    fn from(e: io::Error) -> Self {
+        SaveStateError::Io(e)
+    }
+}
+
+/// Serializes the whole machine: its state, which includes the random number generator, and
+/// which keys are pressed, tagged with the format version and the loaded ROM's hash
+///
+/// # Arguments
+/// * `chip8` the machine to save
+pub fn to_bytes(chip8: &Chip8) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_SIZE + STATE_SIZE);
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&VERSION.to_be_bytes());
+    bytes.extend_from_slice(&chip8.rom_hash().to_be_bytes());
+    bytes.extend_from_slice(&chip8.pressed_keys());
+    bytes.extend_from_slice(&chip8.state().to_bytes());
+    bytes
+}
+
+/// Restores a machine from the bytes produced by `to_bytes`
+/// Rejects save states from other ROMs or format versions without changing the machine
+///
+/// # Arguments
+/// * `chip8` the machine to restore, which must have the same ROM loaded
+/// * `bytes` a serialized save state
+pub fn from_bytes(chip8: &mut Chip8, bytes: &[u8]) -> Result<(), SaveStateError> {
+    if bytes.len() < HEADER_SIZE || &bytes[..8] != MAGIC {
+        return Err(SaveStateError::NotASaveState);
+    }
+    let version = u16::from_be_bytes([bytes[8], bytes[9]]);
+    if version != VERSION {
+        return Err(SaveStateError::IncompatibleVersion { version });
+    }
+    let mut rom_hash = [0; 8];
+    rom_hash.copy_from_slice(&bytes[10..18]);
+    let rom_hash = u64::from_be_bytes(rom_hash);
+    if rom_hash != chip8.rom_hash() {
+        return Err(SaveStateError::DifferentRom { rom_hash });
+    }
+    let mut pressed_keys = [0; 16];
+    pressed_keys.copy_from_slice(&bytes[18..HEADER_SIZE]);
+    let state = State::from_bytes(&bytes[HEADER_SIZE..]).ok_or(SaveStateError::NotASaveState)?;
+    chip8.restore(state, pressed_keys);
+    Ok(())
+}
+
+/// # Slots
+/// Numbered save states for each ROM, stored as `<directory>/<ROM hash>/<slot>.state`.
+///
+/// - `directory` where every ROM's save states are kept
+pub struct Slots {
+    directory: PathBuf,
+}
+
+impl Slots {
+    /// # Arguments
+    /// * `directory` where every ROM's save states are kept
+    pub fn new(directory: PathBuf) -> Self {
+        Slots { directory }
+    }
+
+    /// The slots in emu8's data directory, e.g. `~/.local/share/emu8/states`
+    pub fn in_data_directory() -> Self {
+        let data = std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share"))
+            })
+            .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+            .unwrap_or_default();
+        Self::new(data.join("emu8").join("states"))
+    }
+
+    /// The file a slot is stored in
+    ///
+    /// # Arguments
+    /// * `rom_hash` the hash of the ROM the slot belongs to
+    /// * `slot` the slot, from 1 to `SLOTS`
+    pub fn path(&self, rom_hash: u64, slot: usize) -> Result<PathBuf, SaveStateError> {
+        if !(1..=SLOTS).contains(&slot) {
+            return Err(SaveStateError::InvalidSlot { slot });
+        }
+        Ok(self
+            .directory
+            .join(format!("{:016x}", rom_hash))
+            .join(format!("{}.state", slot)))
+    }
+
+    /// Saves the machine to a slot for its ROM, replacing what was there
+    ///
+    /// # Arguments
+    /// * `chip8` the machine to save
+    /// * `slot` the slot, from 1 to `SLOTS`
+    pub fn save(&self, chip8: &Chip8, slot: usize) -> Result<(), SaveStateError> {
+        let path = self.path(chip8.rom_hash(), slot)?;
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        fs::write(path, to_bytes(chip8))?;
+        Ok(())
+    }
+
+    /// Restores the machine from a slot for its ROM
+    ///
+    /// # Arguments
+    /// * `chip8` the machine to restore
+    /// * `slot` the slot, from 1 to `SLOTS`
+    pub fn load(&self, chip8: &mut Chip8, slot: usize) -> Result<(), SaveStateError> {
+        let bytes = fs::read(self.path(chip8.rom_hash(), slot)?)?;
+        from_bytes(chip8, &bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chip8(rom: &[u8]) -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.load_rom(&mut &rom[..]).unwrap();
+        chip8
+    }
+
+    // C0FF: set V0 to a random byte, 1200: jump back to 0x200
+    const ROM: &[u8] = &[0xC0, 0xFF, 0x12, 0x00];
+
+    #[test]
+    fn test_save_states_restore_the_machine() {
+        let mut original = chip8(ROM);
+        original.key_press(0x5);
+        original.run_cycles(5).unwrap();
+        let bytes = to_bytes(&original);
+
+        let mut restored = chip8(ROM);
+        from_bytes(&mut restored, &bytes).unwrap();
+        assert_eq!(restored.registers(), original.registers());
+        assert_eq!(restored.state().cycles, original.state().cycles);
+        assert_eq!(restored.pressed_keys(), original.pressed_keys());
+
+        // The random number generator carries on from where it was saved
+        original.run_cycles(2).unwrap();
+        restored.run_cycles(2).unwrap();
+        assert_eq!(restored.state().v[0x0], original.state().v[0x0]);
+    }
+
+    #[test]
+    fn test_save_states_reject_other_roms_and_versions() {
+        let mut bytes = to_bytes(&chip8(ROM));
+        let mut other = chip8(&[0x12, 0x00]);
+        assert!(matches!(
+            from_bytes(&mut other, &bytes),
+            Err(SaveStateError::DifferentRom { .. })
+        ));
+
+        bytes[9] = 0xFF;
+        let error = from_bytes(&mut chip8(ROM), &bytes).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "save state is version 255 but only version 1 can be loaded"
+        );
+        assert!(matches!(
+            from_bytes(&mut chip8(ROM), b"not a save state"),
+            Err(SaveStateError::NotASaveState)
+        ));
+    }
+
+    #[test]
+    fn test_slots_are_kept_per_rom() {
+        let directory = tempfile::tempdir().unwrap();
+        let slots = Slots::new(directory.path().to_path_buf());
+        let mut saved = chip8(ROM);
+        saved.run_cycles(3).unwrap();
+        slots.save(&saved, 10).unwrap();
+        assert!(slots.path(saved.rom_hash(), 10).unwrap().exists());
+
+        let mut loaded = chip8(ROM);
+        slots.load(&mut loaded, 10).unwrap();
+        assert_eq!(loaded.state().cycles, 3);
+        assert!(matches!(
+            slots.load(&mut loaded, 1),
+            Err(SaveStateError::Io(_))
+        ));
+        assert!(matches!(
+            slots.save(&saved, 11),
+            Err(SaveStateError::InvalidSlot { slot: 11 })
+        ));
+    }
+}
@@ -0,0 +1,52 @@
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+
+use crate::chip8::Chip8;
+use crate::movie::Movie;
+use crate::trace::Trace;
+
+/// # Session
+/// What should be logged, played back, and recorded while a ROM runs.
+///
+/// - `trace` where executed instructions are logged, if anywhere
+/// - `play` the movie to play back, if any
+/// - `record` where a movie of the session is saved when it ends, if anywhere
+#[derive(Default)]
+pub struct Session {
+    pub trace: Option<Trace>,
+    pub play: Option<Movie>,
+    pub record: Option<PathBuf>,
+}
+
+impl Session {
+    /// Attaches the trace and movies to a Chip-8 whose ROM has just been loaded
+    ///
+    /// # Arguments
+    /// * `chip8` the machine the session runs on
+    pub fn start(&mut self, chip8: &mut Chip8) -> Result<(), Error> {
+        chip8.set_trace(self.trace.take());
+        if let Some(movie) = self.play.take() {
+            chip8
+                .play(movie)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        }
+        if self.record.is_some() {
+            chip8.start_recording();
+        }
+        Ok(())
+    }
+
+    /// Finishes writing the trace and saves the recorded movie
+    ///
+    /// # Arguments
+    /// * `chip8` the machine the session ran on
+    pub fn finish(&mut self, chip8: &mut Chip8) -> Result<(), Error> {
+        if let Some(trace) = chip8.take_trace() {
+            trace.finish()?;
+        }
+        if let (Some(path), Some(movie)) = (&self.record, chip8.take_recording()) {
+            movie.save(path).map_err(Error::other)?;
+        }
+        Ok(())
+    }
+}
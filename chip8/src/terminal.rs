@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Error};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+use crate::chip8::{Chip8, Options};
+use crate::frontend::{play, save_gif, Action, Controls, Frontend};
+use crate::session::Session;
+use display::{Glyphs, Renderer, Terminal};
+
+/// How many frames a key stays held after it's pressed or repeats, when the terminal can't report
+/// releases. Long enough to last until the keyboard starts repeating it.
+const HOLD_FRAMES: u32 = 30;
+
+/// # Keymap
+/// The keypad is mapped to the same keys as in the window, going by the characters they type, with
+/// the character for each key in order from 0 to F.
+/// ```text
+/// |1|2|3|C|      |1|2|3|4|
+/// |4|5|6|D|  ->  |Q|W|E|R|
+/// |7|8|9|E|  ->  |A|S|D|F|
+/// |A|0|B|F|      |Z|X|C|V|
+/// ```
+const KEYMAP: &str = "x123qweasdzc4rfv";
+
+/// The keypad key a key types, if it's mapped to one
+fn keymap(key: KeyCode) -> Option<u8> {
+    match key {
+        KeyCode::Char(c) => KEYMAP.find(c.to_ascii_lowercase()).map(|kc| kc as u8),
+        _ => None,
+    }
+}
+
+/// The action a key starts when it's pressed and stops when it's released, if it's held for one
+fn hold(key: KeyCode, down: bool) -> Option<Action> {
+    match (key, keymap(key)) {
+        (_, Some(kc)) if down => Some(Action::Press(kc)),
+        (_, Some(kc)) => Some(Action::Release(kc)),
+        (KeyCode::Char(' '), _) => Some(Action::FastForward(down)),
+        (KeyCode::Esc, _) => Some(Action::Rewind(down)),
+        _ => None,
+    }
+}
+
+/// The action a key takes when it's pressed, if it's a hotkey
+fn hotkey(key: KeyCode, modifiers: KeyModifiers) -> Option<Action> {
+    match key {
+        KeyCode::Char('c' | 'q') if modifiers.contains(KeyModifiers::CONTROL) => Some(Action::Quit),
+        KeyCode::Char('p') => Some(Action::Pause),
+        KeyCode::Char('t') => Some(Action::Theme),
+        KeyCode::Char('g') => Some(Action::Gif),
+        KeyCode::F(12) => Some(Action::Screenshot),
+        // Shift+F1-F10 saves to a slot and F1-F10 loads from it
+        KeyCode::F(slot @ 1..=10) if modifiers.contains(KeyModifiers::SHIFT) => {
+            Some(Action::Save(slot as usize))
+        }
+        KeyCode::F(slot @ 1..=10) => Some(Action::Load(slot as usize)),
+        _ => None,
+    }
+}
+
+/// Plays a ROM in the terminal it's run from, drawing frames with text instead of opening a window.
+///
+/// Terminals without key release reporting can't tell how long a key is held, so a key counts as
+/// held until it stops repeating. There's no sound.
+///
+/// # Arguments
+/// * `rom` the path of the ROM to play
+/// * `options` how the machine behaves
+/// * `session` the trace to write and movies to play or record
+/// * `frontend` the palette, save state slots, and capture settings; the window and tone are unused
+/// * `glyphs` the characters frames are drawn with
+pub fn terminal(
+    rom: PathBuf,
+    options: Options,
+    mut session: Session,
+    frontend: Frontend,
+    glyphs: Glyphs,
+) -> Result<(), Error> {
+    let mut chip8: Chip8 = Chip8::with_options(options);
+    let file = File::open(&rom)?;
+    // Errors loading the ROM are reported once the terminal's been taken over
+    let loaded = chip8.load_rom(&mut BufReader::new(file));
+    session
+        .start(&mut chip8)
+        .map_err(|e| Error::other(format!("unable to start session: {}", e)))?;
+
+    let mut terminal = Terminal::new(frontend.palette, glyphs, frontend.persistence)?;
+    if let Err(e) = loaded {
+        terminal.set_title(&format!(
+            "Emu-8 - encountered error {:?} while attempting to load ROM",
+            e
+        ))?;
+    }
+    let mut keyboard = Keyboard {
+        releases: terminal.reports_releases(),
+        held: HashMap::new(),
+    };
+    let played = play(&rom, &mut chip8, &mut terminal, &mut keyboard, &frontend);
+
+    // Restore the terminal before reporting anything else
+    drop(terminal);
+    if let Ok(Some(recorder)) = &played {
+        match save_gif(recorder, &frontend.screenshots, &rom) {
+            Ok(message) => println!("{}", message),
+            Err(message) => eprintln!("{}", message),
+        }
+    }
+    session
+        .finish(&mut chip8)
+        .map_err(|e| Error::other(format!("unable to finish session: {}", e)))?;
+    played.map(|_| ())
+}
+
+/// # Keyboard
+/// The keys typed into the terminal.
+///
+/// - `releases` whether the terminal reports when keys are released
+/// - `held` the keys being held, and how many more frames they're held for if they don't repeat
+struct Keyboard {
+    releases: bool,
+    held: HashMap<KeyCode, u32>,
+}
+
+impl Controls for Keyboard {
+    fn poll(&mut self) -> Result<Vec<Action>, Error> {
+        let mut actions = Vec::new();
+        while event::poll(Duration::ZERO)? {
+            match event::read()? {
+                Event::Resize(..) => actions.push(Action::Redraw),
+                Event::Key(KeyEvent {
+                    kind: KeyEventKind::Release,
+                    code,
+                    ..
+                }) if self.held.remove(&code).is_some() => actions.extend(hold(code, false)),
+                Event::Key(KeyEvent {
+                    kind: KeyEventKind::Release,
+                    ..
+                }) => (),
+                Event::Key(KeyEvent {
+                    code, modifiers, ..
+                }) => {
+                    let frames = match self.releases {
+                        true => u32::MAX,
+                        false => HOLD_FRAMES,
+                    };
+                    if self.held.insert(code, frames).is_some() {
+                        continue;
+                    }
+                    // Hotkeys come first, so Ctrl+C quits rather than pressing C
+                    match hotkey(code, modifiers) {
+                        Some(action) => actions.push(action),
+                        None => actions.extend(hold(code, true)),
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        // Keys that have stopped repeating count as released
+        for frames in self.held.values_mut() {
+            *frames = frames.saturating_sub(1);
+        }
+        let released: Vec<KeyCode> = self
+            .held
+            .iter()
+            .filter(|(_, &frames)| frames == 0)
+            .map(|(&code, _)| code)
+            .collect();
+        for code in released {
+            self.held.remove(&code);
+            actions.extend(hold(code, false));
+        }
+        Ok(actions)
+    }
+
+    /// Shows the first line of the message in the title
+    fn report(
+        &mut self,
+        renderer: &mut dyn Renderer,
+        message: Result<String, String>,
+    ) -> Result<(), Error> {
+        let message = message.unwrap_or_else(|e| e);
+        let line = message.lines().next().unwrap_or_default();
+        renderer.set_title(&format!("Emu-8 - {}", line))
+    }
+}
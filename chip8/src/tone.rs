@@ -0,0 +1,229 @@
+use std::f32::consts::PI;
+use std::str::FromStr;
+
+/// How many samples of the tone are played per second
+pub const SAMPLE_RATE: i32 = 44_100;
+
+/// # Waveform
+/// The shape of the tone played while the sound timer is active.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Waveform {
+    Square,
+    Sine,
+    Triangle,
+}
+
+impl Waveform {
+    /// The value of the wave at a point in its cycle, from -1.0 to 1.0
+    /// Sine and triangle waves start a cycle at their midpoint so that they can start and stop
+    /// without clicking; a square wave only has its peaks, so it jumps to one as it does every
+    /// half cycle
+    ///
+    /// # Arguments
+    /// * `phase` how far through a cycle the wave is, from 0.0 to 1.0
+    pub fn sample(self, phase: f32) -> f32 {
+        match self {
+            Waveform::Square if phase < 0.5 => 1.0,
+            Waveform::Square => -1.0,
+            Waveform::Sine => (2.0 * PI * phase).sin(),
+            Waveform::Triangle if phase < 0.25 => 4.0 * phase,
+            Waveform::Triangle if phase < 0.75 => 2.0 - 4.0 * phase,
+            Waveform::Triangle => 4.0 * phase - 4.0,
+        }
+    }
+}
+
+impl FromStr for Waveform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "square" => Ok(Waveform::Square),
+            "sine" => Ok(Waveform::Sine),
+            "triangle" => Ok(Waveform::Triangle),
+            _ => Err(format!(
+                "unknown waveform {:?}; expected square, sine, or triangle",
+                s
+            )),
+        }
+    }
+}
+
+/// # Tone
+/// The sound played while the sound timer is active.
+///
+/// - `frequency` the pitch of the tone in Hz
+/// - `volume` how loud the tone is, from 0.0 (silent) to 1.0
+/// - `waveform` the shape of the tone
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Tone {
+    pub frequency: f32,
+    pub volume: f32,
+    pub waveform: Waveform,
+}
+
+impl Default for Tone {
+    fn default() -> Self {
+        Tone {
+            frequency: 440.0,
+            volume: 0.25,
+            waveform: Waveform::Square,
+        }
+    }
+}
+
+/// # Oscillator
+/// Generates the samples of a Tone while it's playing.
+///
+/// The tone always starts at the beginning of a cycle and only stops once the cycle it's in finishes,
+/// so turning it on and off at sound timer boundaries doesn't produce clicks.
+pub struct Oscillator {
+    tone: Tone,
+    step: f32,
+    phase: f32,
+    playing: bool,
+    sounding: bool,
+}
+
+impl Oscillator {
+    /// Creates an oscillator that's initially silent
+    ///
+    /// # Arguments
+    /// * `tone` the tone to generate
+    /// * `sample_rate` how many samples are played per second
+    pub fn new(tone: Tone, sample_rate: i32) -> Self {
+        Oscillator {
+            tone,
+            step: tone.frequency / sample_rate as f32,
+            phase: 0.0,
+            playing: false,
+            sounding: false,
+        }
+    }
+
+    /// Starts or stops the tone
+    ///
+    /// # Arguments
+    /// * `playing` whether the tone should be audible
+    pub fn set_playing(&mut self, playing: bool) {
+        self.playing = playing;
+    }
+
+    /// Fills a buffer with the next samples
+    ///
+    /// # Arguments
+    /// * `samples` the buffer to fill
+    pub fn fill(&mut self, samples: &mut [f32]) {
+        for sample in samples.iter_mut() {
+            if !self.sounding {
+                if !self.playing {
+                    *sample = 0.0;
+                    continue;
+                }
+                self.sounding = true;
+                self.phase = 0.0;
+            }
+            *sample = self.tone.volume * self.tone.waveform.sample(self.phase);
+            self.phase += self.step;
+            if self.phase >= 1.0 {
+                self.phase -= 1.0;
+                self.sounding = self.playing;
+            }
+        }
+    }
+}
+
+/// Parses the pitch of a tone in Hz, which must be positive and low enough to be played at the
+/// sample rate
+///
+/// # Arguments
+/// * `frequency` the frequency to parse
+pub fn parse_frequency(frequency: &str) -> Result<f32, String> {
+    let nyquist = SAMPLE_RATE as f32 / 2.0;
+    match frequency.parse::<f32>() {
+        Ok(hz) if hz > 0.0 && hz < nyquist => Ok(hz),
+        Ok(_) => Err(format!(
+            "{} is out of range; expected more than 0 and less than {} Hz",
+            frequency, nyquist
+        )),
+        Err(_) => Err(format!("{:?} is not a frequency", frequency)),
+    }
+}
+
+/// Parses the volume of a tone, from silent at 0 to full at 1
+///
+/// # Arguments
+/// * `volume` the volume to parse
+pub fn parse_volume(volume: &str) -> Result<f32, String> {
+    match volume.parse::<f32>() {
+        Ok(level) if (0.0..=1.0).contains(&level) => Ok(level),
+        Ok(_) => Err(format!("{} is out of range; expected 0 to 1", volume)),
+        Err(_) => Err(format!("{:?} is not a volume", volume)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_waveform_parses() {
+        assert_eq!("sine".parse::<Waveform>(), Ok(Waveform::Sine));
+        assert!("sawtooth".parse::<Waveform>().is_err());
+    }
+
+    #[test]
+    fn test_frequency_parses() {
+        assert_eq!(parse_frequency("440"), Ok(440.0));
+        assert!(parse_frequency("0").is_err());
+        assert!(parse_frequency("-440").is_err());
+        assert!(parse_frequency("NaN").is_err());
+        assert!(parse_frequency("22050").is_err());
+    }
+
+    #[test]
+    fn test_volume_parses() {
+        assert_eq!(parse_volume("0"), Ok(0.0));
+        assert_eq!(parse_volume("0.25"), Ok(0.25));
+        assert_eq!(parse_volume("1"), Ok(1.0));
+        assert!(parse_volume("1.5").is_err());
+        assert!(parse_volume("-0.1").is_err());
+        assert!(parse_volume("NaN").is_err());
+        assert!(parse_volume("inf").is_err());
+    }
+
+    #[test]
+    fn test_waveforms_start_and_end_at_midpoint() {
+        // Square waves are left out as they have no midpoint to start at
+        assert_eq!(Waveform::Sine.sample(0.0), 0.0);
+        assert_eq!(Waveform::Triangle.sample(0.0), 0.0);
+        assert_eq!(Waveform::Triangle.sample(0.25), 1.0);
+        assert_eq!(Waveform::Triangle.sample(0.75), -1.0);
+    }
+
+    #[test]
+    fn test_oscillator_is_silent_until_played() {
+        let mut oscillator = Oscillator::new(Tone::default(), 4400);
+        let mut samples = [1.0; 4];
+        oscillator.fill(&mut samples);
+        assert_eq!(samples, [0.0; 4]);
+
+        oscillator.set_playing(true);
+        oscillator.fill(&mut samples);
+        assert_eq!(samples, [0.25; 4]);
+    }
+
+    #[test]
+    fn test_oscillator_finishes_cycle_before_stopping() {
+        // 10 samples per cycle
+        let mut oscillator = Oscillator::new(Tone::default(), 4400);
+        oscillator.set_playing(true);
+        oscillator.fill(&mut [0.0; 3]);
+        oscillator.set_playing(false);
+
+        let mut samples = [0.0; 10];
+        oscillator.fill(&mut samples);
+        assert_eq!(samples[2..7], [-0.25; 5]);
+        assert_eq!(samples[7..], [0.0; 3]);
+    }
+}
@@ -0,0 +1,176 @@
+use std::fs::File;
+use std::io::{BufWriter, Error, Write};
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use core::mnemonic;
+use core::state::State;
+
+/// The width the mnemonic column is padded to, which keeps the registers aligned
+const MNEMONIC_WIDTH: usize = 20;
+
+/// # Trace
+/// A log of every instruction executed, written one line per instruction with the state before it
+/// executes, e.g.
+/// `0000000042 0204 7101 add v1, 0x01         V0=00 ... VF=00 I=0000 SP=00 DT=00 ST=00`
+///
+/// The format is stable so traces of the same ROM can be diffed to find where they diverge.
+///
+/// - `output` where lines are written
+/// - `range` only instructions at addresses in this range are traced
+/// - `error` the first error writing to `output`, after which nothing more is written
+pub struct Trace {
+    output: Box<dyn Write>,
+    range: RangeInclusive<u16>,
+    error: Option<Error>,
+}
+
+impl Trace {
+    /// Creates a Trace writing to any output
+    ///
+    /// # Arguments
+    /// * `output` where lines are written
+    /// * `range` only instructions at addresses in this range are traced
+    pub fn new(output: Box<dyn Write>, range: RangeInclusive<u16>) -> Self {
+        Trace {
+            output,
+            range,
+            error: None,
+        }
+    }
+
+    /// Creates a Trace writing to a file, replacing it if it exists
+    ///
+    /// # Arguments
+    /// * `path` the file to write to
+    /// * `range` only instructions at addresses in this range are traced
+    pub fn create(path: &Path, range: RangeInclusive<u16>) -> Result<Self, Error> {
+        let file = BufWriter::new(File::create(path)?);
+        Ok(Self::new(Box::new(file), range))
+    }
+
+    /// Writes a line for an instruction that's about to execute, if its address is in range
+    ///
+    /// # Arguments
+    /// * `state` the state before the instruction executes
+    /// * `op` the instruction's opcode
+    pub fn record(&mut self, state: &State, op: u16) {
+        if self.error.is_some() || !self.range.contains(&state.pc) {
+            return;
+        }
+        if let Err(e) = writeln!(self.output, "{}", line(state, op)) {
+            self.error = Some(e);
+        }
+    }
+
+    /// Flushes the trace, returning the first error that occurred while writing it
+    pub fn finish(mut self) -> Result<(), Error> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.output.flush(),
+        }
+    }
+}
+
+/// Formats a line of the trace
+///
+/// # Arguments
+/// * `state` the state before the instruction executes
+/// * `op` the instruction's opcode
+pub fn line(state: &State, op: u16) -> String {
+    let instruction = mnemonic::disassemble(&state.memory, state.pc).unwrap_or_default();
+    let registers: Vec<String> = state
+        .v
+        .iter()
+        .enumerate()
+        .map(|(register, value)| format!("V{:X}={:02X}", register, value))
+        .collect();
+    format!(
+        "{:010} {:04X} {:04X} {:width$} {} I={:04X} SP={:02X} DT={:02X} ST={:02X}",
+        state.cycles,
+        state.pc,
+        op,
+        instruction,
+        registers.join(" "),
+        state.i,
+        state.sp,
+        state.delay_timer,
+        state.sound_timer,
+        width = MNEMONIC_WIDTH
+    )
+}
+
+/// Parses an inclusive range of addresses written as `START-END`, e.g. `0x200-0x2FF`
+///
+/// # Arguments
+/// * `range` the range to parse
+pub fn parse_range(range: &str) -> Result<RangeInclusive<u16>, String> {
+    let address = |address: &str| {
+        let address = address.trim();
+        let digits = address
+            .strip_prefix("0x")
+            .or_else(|| address.strip_prefix("0X"))
+            .unwrap_or(address);
+        u16::from_str_radix(digits, 16).map_err(|_| format!("{:?} is not a hex address", address))
+    };
+    match range.split_once('-') {
+        Some((start, end)) => Ok(address(start)?..=address(end)?),
+        None => Err(format!("{:?} is not a range; expected START-END", range)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::Chip8;
+
+    /// Collects what's written to it so a test can inspect the trace
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_traces_instructions_in_range() {
+        let output = Shared::default();
+        let mut chip8 = Chip8::new();
+        // 6A2A: set VA to 0x2A, 7A01: add 1 to VA, 1202: jump back to 0x202
+        chip8
+            .load_rom(&mut &[0x6A, 0x2A, 0x7A, 0x01, 0x12, 0x02][..])
+            .unwrap();
+        chip8.set_trace(Some(Trace::new(Box::new(output.clone()), 0x200..=0x202)));
+        chip8.run_cycles(4).unwrap();
+        chip8.take_trace().unwrap().finish().unwrap();
+
+        let trace = String::from_utf8(output.0.borrow().clone()).unwrap();
+        let lines: Vec<&str> = trace.lines().collect();
+        let registers = "V0=00 V1=00 V2=00 V3=00 V4=00 V5=00 V6=00 V7=00 V8=00 V9=00";
+        assert_eq!(
+            lines,
+            [
+                format!("0000000000 0200 6A2A load va, 0x2a        {} VA=00 VB=00 VC=00 VD=00 VE=00 VF=00 I=0000 SP=00 DT=00 ST=00", registers),
+                format!("0000000001 0202 7A01 add va, 0x01         {} VA=2A VB=00 VC=00 VD=00 VE=00 VF=00 I=0000 SP=00 DT=00 ST=00", registers),
+                format!("0000000003 0202 7A01 add va, 0x01         {} VA=2B VB=00 VC=00 VD=00 VE=00 VF=00 I=0000 SP=00 DT=00 ST=00", registers),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parses_ranges() {
+        assert_eq!(parse_range("0x200-0x2FF"), Ok(0x200..=0x2FF));
+        assert_eq!(parse_range("300-3ff"), Ok(0x300..=0x3FF));
+        assert!(parse_range("0x200").is_err());
+        assert!(parse_range("0x200-nope").is_err());
+    }
+}
@@ -0,0 +1,220 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// # Location
+/// A register or range of memory that instructions read and write.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Location {
+    V(u8),
+    I,
+    Sp,
+    DelayTimer,
+    SoundTimer,
+    Memory { address: u16, len: usize },
+}
+
+impl Location {
+    /// Returns whether two locations share any register or byte of memory
+    ///
+    /// # Arguments
+    /// * `other` the location to compare against
+    pub fn overlaps(&self, other: &Location) -> bool {
+        match (self, other) {
+            (
+                Location::Memory { address, len },
+                Location::Memory {
+                    address: other_address,
+                    len: other_len,
+                },
+            ) => {
+                let (start, other_start) = (*address as usize, *other_address as usize);
+                start < other_start + other_len && other_start < start + len
+            }
+            _ => self == other,
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Location::V(register) => write!(f, "v{:x}", register),
+            Location::I => write!(f, "i"),
+            Location::Sp => write!(f, "sp"),
+            Location::DelayTimer => write!(f, "dt"),
+            Location::SoundTimer => write!(f, "st"),
+            Location::Memory { address, len: 1 } => write!(f, "mem[{:#06X}]", address),
+            Location::Memory { address, len } => {
+                write!(f, "mem[{:#06X}..{:#06X}]", address, *address as usize + len)
+            }
+        }
+    }
+}
+
+/// Parses a register (`v0`..`vf`, `i`, `sp`, `dt`, `st`) or a single byte of memory (`0x300`)
+impl FromStr for Location {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "{:?} is not a location; expected v0-vf, i, sp, dt, st, or a hex address",
+                s
+            )
+        };
+        let lower = s.to_ascii_lowercase();
+        match lower.as_str() {
+            "i" => Ok(Location::I),
+            "sp" => Ok(Location::Sp),
+            "dt" => Ok(Location::DelayTimer),
+            "st" => Ok(Location::SoundTimer),
+            _ => {
+                if let Some(register) = lower.strip_prefix('v') {
+                    match u8::from_str_radix(register, 16) {
+                        Ok(register) if register < 16 => Ok(Location::V(register)),
+                        _ => Err(invalid()),
+                    }
+                } else if let Some(address) = lower.strip_prefix("0x") {
+                    u16::from_str_radix(address, 16)
+                        .map(|address| Location::Memory { address, len: 1 })
+                        .map_err(|_| invalid())
+                } else {
+                    Err(invalid())
+                }
+            }
+        }
+    }
+}
+
+/// # Accesses
+/// The locations an instruction reads and writes when it's executed, as reported by
+/// `instruction::execute`.
+///
+/// Instruction fetches and the stack's contents aren't included, and neither is the FrameBuffer.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Accesses {
+    pub reads: Vec<Location>,
+    pub writes: Vec<Location>,
+}
+
+impl Accesses {
+    /// Creates the accesses of an instruction
+    ///
+    /// # Arguments
+    /// * `reads` the locations the instruction read, in the order it read them
+    /// * `writes` the locations the instruction wrote, in the order it wrote them
+    pub fn new(reads: Vec<Location>, writes: Vec<Location>) -> Self {
+        Accesses { reads, writes }
+    }
+}
+
+#[cfg(test)]
+mod test_access {
+    use super::*;
+    use crate::instruction::execute;
+    use crate::quirks::Profile;
+    use crate::state::State;
+    use Location::*;
+
+    fn accesses(op: u16, state: &State) -> Accesses {
+        execute(op, state, [0; 16]).unwrap().1
+    }
+
+    #[test]
+    fn test_accesses_arithmetic() {
+        let state = State::new();
+        assert_eq!(
+            accesses(0x8124, &state),
+            Accesses {
+                reads: vec![V(1), V(2)],
+                writes: vec![V(1), V(0xF)],
+            }
+        );
+    }
+
+    #[test]
+    fn test_accesses_follow_quirks() {
+        let mut state = State::with_quirks(Profile::CosmacVip.into());
+        state.i = 0x300;
+        assert_eq!(
+            accesses(0xF255, &state),
+            Accesses {
+                reads: vec![I, V(0), V(1), V(2)],
+                writes: vec![
+                    Memory {
+                        address: 0x300,
+                        len: 3
+                    },
+                    I
+                ],
+            }
+        );
+        assert_eq!(accesses(0x8126, &state).reads, vec![V(2)]);
+    }
+
+    #[test]
+    fn test_accesses_descending_register_range() {
+        let mut state = State::new();
+        state.i = 0x300;
+        assert_eq!(
+            accesses(0x5312, &state),
+            Accesses {
+                reads: vec![I, V(3), V(2), V(1)],
+                writes: vec![Memory {
+                    address: 0x300,
+                    len: 3
+                }],
+            }
+        );
+    }
+
+    #[test]
+    fn test_accesses_draw_reads_sprite() {
+        let mut state = State::new();
+        state.i = 0x400;
+        state.plane = 0x3;
+        assert!(accesses(0xD015, &state).reads.contains(&Memory {
+            address: 0x400,
+            len: 10
+        }));
+    }
+
+    #[test]
+    fn test_location_overlaps() {
+        let watched = Memory {
+            address: 0x300,
+            len: 4,
+        };
+        assert!(watched.overlaps(&Memory {
+            address: 0x2FF,
+            len: 2
+        }));
+        assert!(!watched.overlaps(&Memory {
+            address: 0x304,
+            len: 1
+        }));
+        assert!(!V(1).overlaps(&V(2)));
+    }
+
+    #[test]
+    fn test_location_parses_and_displays() {
+        assert_eq!("VA".parse::<Location>(), Ok(V(0xA)));
+        assert_eq!("dt".parse::<Location>(), Ok(DelayTimer));
+        assert_eq!(
+            "0x300".parse::<Location>(),
+            Ok(Memory {
+                address: 0x300,
+                len: 1
+            })
+        );
+        assert!("v10".parse::<Location>().is_err());
+        assert_eq!(
+            Memory {
+                address: 0x300,
+                len: 3
+            }
+            .to_string(),
+            "mem[0x0300..0x0303]"
+        );
+    }
+}
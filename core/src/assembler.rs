@@ -0,0 +1,538 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::constants::{MEMORY_SIZE, ROM_ADDRESS};
+use crate::mnemonic::{self, Mnemonic, Operands};
+
+/// How deeply `include`s may be nested, which stops files that include themselves
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// How deeply constants may be defined in terms of other constants
+const MAX_CONSTANT_DEPTH: usize = 64;
+
+/// # AssemblyError
+/// Why a line of assembly couldn't be assembled.
+///
+/// - `file` the file containing the line
+/// - `line` the line number, starting at 1
+/// - `message` what's wrong with the line
+#[derive(Debug, PartialEq, Eq)]
+pub struct AssemblyError {
+    pub file: PathBuf,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AssemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file.display(), self.line, self.message)
+    }
+}
+
+/// Where a statement was written
+#[derive(Clone)]
+struct Source {
+    file: Rc<PathBuf>,
+    line: usize,
+}
+
+impl Source {
+    fn error(&self, message: String) -> AssemblyError {
+        AssemblyError {
+            file: self.file.to_path_buf(),
+            line: self.line,
+            message,
+        }
+    }
+}
+
+/// # Statement
+/// A line of assembly that produces bytes.
+enum Statement {
+    /// `draw v1, v2, 5`
+    Instruction(&'static Mnemonic, Vec<String>),
+    /// `db 0xF0, 0x90` or `dw 0x1234` with the width of each value in bytes
+    Data(usize, Vec<String>),
+}
+
+impl Statement {
+    /// The number of bytes the statement assembles to
+    fn size(&self) -> usize {
+        match self {
+            Statement::Instruction(mnemonic, _) => mnemonic.size() as usize,
+            Statement::Data(width, values) => width * values.len(),
+        }
+    }
+}
+
+/// A name that can be used in place of a number
+enum Symbol {
+    /// `loop:` the address of the statement following the label
+    Label(u16),
+    /// `SPEED = 4` an expression that's evaluated once every name has been defined
+    Constant(String),
+    /// A constant's value once its expression has been evaluated
+    Value(i64),
+}
+
+/// Assembles source into a ROM that's loaded at `ROM_ADDRESS`
+///
+/// Each line holds any number of `label:`s followed by one of:
+/// - an instruction named after the operation that executes it, e.g. `draw v1, v2, 5`
+/// - a constant, e.g. `SPEED = 4`
+/// - data, e.g. `db 0xF0, 0x90` for bytes or `dw sprite` for 16-bit words
+/// - `include "sprites.s"` to assemble another file in place, relative to this one
+///
+/// Numbers are decimal, hex (`0x1F`), or binary (`0b1010`), and anywhere one is expected an
+/// expression adding and subtracting numbers, labels, and constants can be used instead.
+/// Everything after a `;` is a comment.
+///
+/// # Arguments
+/// * `source` the assembly
+/// * `path` the file the assembly was read from, which errors refer to and includes are relative to
+pub fn assemble(source: &str, path: &Path) -> Result<Vec<u8>, AssemblyError> {
+    let mut assembler = Assembler::default();
+    assembler.load(source, path, 0)?;
+    assembler.resolve_constants()?;
+    assembler.emit()
+}
+
+/// # Assembler
+/// Statements and symbols collected in a first pass over the source, which are then emitted once
+/// every label's address is known.
+///
+/// - `statements` everything that produces bytes, in order
+/// - `symbols` labels and constants by name
+/// - `constants` the names of constants and where they were defined
+/// - `size` the number of bytes the statements so far assemble to
+#[derive(Default)]
+struct Assembler {
+    statements: Vec<(Source, Statement)>,
+    symbols: HashMap<String, Symbol>,
+    constants: Vec<(String, Source)>,
+    size: usize,
+}
+
+impl Assembler {
+    /// Collects the statements and symbols in a file, and in any files it includes
+    ///
+    /// # Arguments
+    /// * `source` the contents of the file
+    /// * `path` the file's path
+    /// * `depth` how many includes deep the file is
+    fn load(&mut self, source: &str, path: &Path, depth: usize) -> Result<(), AssemblyError> {
+        let file = Rc::new(path.to_path_buf());
+        for (number, text) in source.lines().enumerate() {
+            let at = Source {
+                file: file.clone(),
+                line: number + 1,
+            };
+            if let Some(include) = self.parse_line(text, &at).map_err(|e| at.error(e))? {
+                if depth >= MAX_INCLUDE_DEPTH {
+                    return Err(at.error("includes are nested too deeply".to_string()));
+                }
+                let contents = std::fs::read_to_string(&include).map_err(|e| {
+                    at.error(format!("unable to include {}: {}", include.display(), e))
+                })?;
+                self.load(&contents, &include, depth + 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Collects the labels, constant, or statement on a line
+    /// Returns the path of the file to include if the line is an `include`
+    ///
+    /// # Arguments
+    /// * `text` the line
+    /// * `at` where the line is
+    fn parse_line(&mut self, text: &str, at: &Source) -> Result<Option<PathBuf>, String> {
+        let mut text = text.split(';').next().unwrap_or_default().trim();
+        while let Some((name, rest)) = text.split_once(':') {
+            let address = ROM_ADDRESS as usize + self.size;
+            self.define(name.trim(), Symbol::Label(address as u16))?;
+            text = rest.trim();
+        }
+        if text.is_empty() {
+            return Ok(None);
+        }
+        if let Some((name, expression)) = text.split_once('=') {
+            let name = name.trim();
+            self.define(name, Symbol::Constant(expression.trim().to_string()))?;
+            self.constants.push((name.to_string(), at.clone()));
+            return Ok(None);
+        }
+
+        let (word, operands) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let operands: Vec<String> = match operands.trim() {
+            "" => Vec::new(),
+            operands => operands.split(',').map(|o| o.trim().to_string()).collect(),
+        };
+        let statement = match word.to_ascii_lowercase().as_str() {
+            "include" => {
+                let name = match operands.as_slice() {
+                    [name] => name.strip_prefix('"').and_then(|n| n.strip_suffix('"')),
+                    _ => None,
+                };
+                let name = name.ok_or("expected a quoted file name to include")?;
+                let directory = at.file.parent().unwrap_or_else(|| Path::new(""));
+                return Ok(Some(directory.join(name)));
+            }
+            "db" | "dw" if operands.is_empty() => return Err(format!("{} needs a value", word)),
+            "db" => Statement::Data(1, operands),
+            "dw" => Statement::Data(2, operands),
+            name => {
+                let mnemonic = mnemonic::lookup(name)
+                    .ok_or_else(|| format!("{:?} is not an instruction", word))?;
+                let expected = operand_count(mnemonic.operands);
+                if operands.len() != expected {
+                    return Err(format!(
+                        "{} takes {} operand(s) but was given {}",
+                        mnemonic.name,
+                        expected,
+                        operands.len()
+                    ));
+                }
+                Statement::Instruction(mnemonic, operands)
+            }
+        };
+        self.size += statement.size();
+        if ROM_ADDRESS as usize + self.size > MEMORY_SIZE {
+            return Err("the program is too large to fit in memory".to_string());
+        }
+        self.statements.push((at.clone(), statement));
+        Ok(None)
+    }
+
+    /// Adds a label or constant
+    ///
+    /// # Arguments
+    /// * `name` the symbol's name
+    /// * `symbol` what the name stands for
+    fn define(&mut self, name: &str, symbol: Symbol) -> Result<(), String> {
+        let mut chars = name.chars();
+        let identifier = chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !identifier || register(name).is_ok() {
+            return Err(format!("{:?} can't be used as a name", name));
+        }
+        if self.symbols.contains_key(name) {
+            return Err(format!("{} is already defined", name));
+        }
+        self.symbols.insert(name.to_string(), symbol);
+        Ok(())
+    }
+
+    /// Evaluates every constant once, so errors are reported where constants are defined rather
+    /// than where they're used
+    fn resolve_constants(&mut self) -> Result<(), AssemblyError> {
+        let constants = std::mem::take(&mut self.constants);
+        for (name, at) in &constants {
+            self.resolve(name, &mut Vec::new())
+                .map_err(|e| at.error(e))?;
+        }
+        Ok(())
+    }
+
+    /// Evaluates a constant after the constants it's defined in terms of, replacing it with its value
+    ///
+    /// # Arguments
+    /// * `name` the constant to evaluate
+    /// * `visiting` the constants being evaluated that led to this one
+    fn resolve(&mut self, name: &str, visiting: &mut Vec<String>) -> Result<(), String> {
+        let expression = match self.symbols.get(name) {
+            Some(Symbol::Constant(expression)) => expression.clone(),
+            _ => return Ok(()),
+        };
+        if visiting.iter().any(|visited| visited == name) {
+            return Err(format!("{} is defined in terms of itself", name));
+        }
+        if visiting.len() >= MAX_CONSTANT_DEPTH {
+            return Err(format!(
+                "{} is defined too deeply in terms of other constants",
+                name
+            ));
+        }
+        visiting.push(name.to_string());
+        for (_, term) in terms(&expression) {
+            self.resolve(term, visiting)?;
+        }
+        visiting.pop();
+        let value = self.evaluate(&expression)?;
+        self.symbols.insert(name.to_string(), Symbol::Value(value));
+        Ok(())
+    }
+
+    /// Assembles the collected statements
+    fn emit(&self) -> Result<Vec<u8>, AssemblyError> {
+        let mut rom = Vec::with_capacity(self.size);
+        for (at, statement) in &self.statements {
+            self.encode(statement, &mut rom).map_err(|e| at.error(e))?;
+        }
+        Ok(rom)
+    }
+
+    /// Assembles a statement
+    ///
+    /// # Arguments
+    /// * `statement` the statement to assemble
+    /// * `rom` where its bytes are written
+    fn encode(&self, statement: &Statement, rom: &mut Vec<u8>) -> Result<(), String> {
+        match statement {
+            Statement::Data(1, values) => {
+                for value in values {
+                    rom.push(self.value(value, -0x80, 0xFF)? as u8);
+                }
+            }
+            Statement::Data(_, values) => {
+                for value in values {
+                    rom.extend((self.value(value, -0x8000, 0xFFFF)? as u16).to_be_bytes());
+                }
+            }
+            Statement::Instruction(mnemonic, operands) => {
+                let byte = |operand: &String| -> Result<u16, String> {
+                    Ok(self.value(operand, -0x80, 0xFF)? as u16 & 0xFF)
+                };
+                let nibble = |operand: &String| -> Result<u16, String> {
+                    Ok(self.value(operand, 0x0, 0xF)? as u16)
+                };
+                let x = |operand: &String| register(operand).map(|x| x << 8);
+                let y = |operand: &String| register(operand).map(|y| y << 4);
+                let fields = match (mnemonic.operands, operands.as_slice()) {
+                    (Operands::N, [n]) => nibble(n)?,
+                    (Operands::X, [x]) => nibble(x)? << 8,
+                    (Operands::Addr, [addr]) => self.value(addr, 0x0, 0xFFF)? as u16,
+                    (Operands::Vx, [vx]) => x(vx)?,
+                    (Operands::VxKk, [vx, kk]) => x(vx)? | byte(kk)?,
+                    (Operands::VxVy, [vx, vy]) => x(vx)? | y(vy)?,
+                    (Operands::VxVyN, [vx, vy, n]) => x(vx)? | y(vy)? | nibble(n)?,
+                    _ => 0x0,
+                };
+                rom.extend((mnemonic.pattern | fields).to_be_bytes());
+                if let (Operands::Long, [addr]) = (mnemonic.operands, operands.as_slice()) {
+                    rom.extend((self.value(addr, 0x0, 0xFFFF)? as u16).to_be_bytes());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Evaluates an expression that must be within a range
+    ///
+    /// # Arguments
+    /// * `expression` the expression to evaluate
+    /// * `min` & `max` the smallest and largest values allowed
+    fn value(&self, expression: &str, min: i64, max: i64) -> Result<i64, String> {
+        let value = self.evaluate(expression)?;
+        if value < min || value > max {
+            return Err(format!(
+                "{} ({}) is out of range; expected {} to {:#X}",
+                expression, value, min, max
+            ));
+        }
+        Ok(value)
+    }
+
+    /// Evaluates numbers, labels, and constants added and subtracted together
+    ///
+    /// # Arguments
+    /// * `expression` the expression to evaluate
+    fn evaluate(&self, expression: &str) -> Result<i64, String> {
+        let mut total: i64 = 0;
+        for (sign, term) in terms(expression) {
+            total = self
+                .term(term)?
+                .checked_mul(sign)
+                .and_then(|term| term.checked_add(total))
+                .ok_or_else(|| format!("{} is out of range", expression.trim()))?;
+        }
+        Ok(total)
+    }
+
+    /// Evaluates a number, label, or constant
+    ///
+    /// # Arguments
+    /// * `term` the term to evaluate
+    fn term(&self, term: &str) -> Result<i64, String> {
+        let invalid = || format!("{:?} is not a number or a defined name", term);
+        if term.starts_with(|c: char| c.is_ascii_digit()) {
+            let lower = term.to_ascii_lowercase();
+            let parsed = if let Some(hex) = lower.strip_prefix("0x") {
+                i64::from_str_radix(hex, 16)
+            } else if let Some(binary) = lower.strip_prefix("0b") {
+                i64::from_str_radix(binary, 2)
+            } else {
+                lower.parse()
+            };
+            return parsed.map_err(|_| invalid());
+        }
+        match self.symbols.get(term) {
+            Some(Symbol::Label(address)) => Ok(i64::from(*address)),
+            Some(Symbol::Value(value)) => Ok(*value),
+            // Constants are all evaluated before anything that uses them
+            Some(Symbol::Constant(_)) => Err(format!("{} is defined in terms of itself", term)),
+            None => Err(invalid()),
+        }
+    }
+}
+
+/// Splits an expression into the terms that are added and subtracted together
+///
+/// # Arguments
+/// * `expression` e.g. `BASE + 2 - OFFSET`
+fn terms(expression: &str) -> Vec<(i64, &str)> {
+    let mut terms = Vec::new();
+    let mut rest = expression.trim();
+    loop {
+        let (sign, after) = match rest.chars().next() {
+            Some('-') => (-1, rest[1..].trim_start()),
+            Some('+') => (1, rest[1..].trim_start()),
+            _ => (1, rest),
+        };
+        let end = after.find(&['+', '-'][..]).unwrap_or(after.len());
+        terms.push((sign, after[..end].trim()));
+        rest = &after[end..];
+        if rest.is_empty() {
+            return terms;
+        }
+    }
+}
+
+/// The number of operands an instruction is written with
+///
+/// # Arguments
+/// * `operands` which of the opcode's fields are operands
+fn operand_count(operands: Operands) -> usize {
+    match operands {
+        Operands::None => 0,
+        Operands::N | Operands::X | Operands::Addr | Operands::Long | Operands::Vx => 1,
+        Operands::VxKk | Operands::VxVy => 2,
+        Operands::VxVyN => 3,
+    }
+}
+
+/// Parses a register (`v0`..`vf`), returning its index
+///
+/// # Arguments
+/// * `operand` the register's name
+fn register(operand: &str) -> Result<u16, String> {
+    let index = operand
+        .strip_prefix(&['v', 'V'][..])
+        .filter(|index| index.len() == 1)
+        .and_then(|index| u16::from_str_radix(index, 16).ok());
+    index.ok_or_else(|| format!("{:?} is not a register; expected v0-vf", operand))
+}
+
+#[cfg(test)]
+mod test_assembler {
+    use super::*;
+    use crate::disassembler::disassemble;
+
+    fn assemble_str(source: &str) -> Result<Vec<u8>, AssemblyError> {
+        assemble(source, Path::new("test.s"))
+    }
+
+    fn error(source: &str) -> String {
+        assemble_str(source).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_assembles_every_opcode() {
+        for op in 0..=u16::MAX {
+            if let Some(mnemonic) = mnemonic::decode(op) {
+                let text = mnemonic.format(op, 0x1234);
+                let mut expected = op.to_be_bytes().to_vec();
+                if mnemonic.operands == Operands::Long {
+                    expected.extend([0x12, 0x34]);
+                }
+                assert_eq!(assemble_str(&text), Ok(expected), "{}", text);
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trips_through_the_disassembler() {
+        let rom = [
+            0x22, 0x08, 0x71, 0x01, 0x3A, 0x20, 0x12, 0x02, 0xA2, 0x0E, 0xF0, 0x00, 0x02, 0x0E,
+            0x00, 0xEE, 0xF0, 0x90, 0xF0, 0xFF,
+        ];
+        assert_eq!(assemble_str(&disassemble(&rom)), Ok(rom.to_vec()));
+    }
+
+    #[test]
+    fn test_resolves_labels_constants_and_data() {
+        let source = "
+            X = 4          ; constants can be used before they're defined
+            start: load v1, X + 1
+                   loadi sprite
+                   jump start
+            sprite: db 0b11110000, 0x90, -1
+            table:  dw sprite, END - 1
+            END = table + 4
+        ";
+        assert_eq!(
+            assemble_str(source),
+            Ok(vec![
+                0x61, 0x05, 0xA2, 0x06, 0x12, 0x00, 0xF0, 0x90, 0xFF, 0x02, 0x06, 0x02, 0x0C,
+            ])
+        );
+    }
+
+    #[test]
+    fn test_includes_files_relative_to_the_including_file() {
+        let directory = tempfile::tempdir().unwrap();
+        let directory = directory.path();
+        std::fs::write(directory.join("sprites.s"), "sprite: db 0xF0\n").unwrap();
+        let main = directory.join("main.s");
+        assert_eq!(
+            assemble("loadi sprite\ninclude \"sprites.s\"\n", &main),
+            Ok(vec![0xA2, 0x02, 0xF0])
+        );
+        std::fs::write(directory.join("sprites.s"), "db 0xF0\nload v1\n").unwrap();
+        let message = assemble("include \"sprites.s\"", &main)
+            .unwrap_err()
+            .to_string();
+        assert!(message.ends_with("sprites.s:2: load takes 2 operand(s) but was given 1"));
+    }
+
+    #[test]
+    fn test_reports_errors_with_line_numbers() {
+        assert_eq!(error("clr\nnop"), "test.s:2: \"nop\" is not an instruction");
+        assert_eq!(
+            error("\n\nload v1, 0x100"),
+            "test.s:3: 0x100 (256) is out of range; expected -128 to 0xFF"
+        );
+        assert_eq!(
+            error("jump nowhere"),
+            "test.s:1: \"nowhere\" is not a number or a defined name"
+        );
+        assert_eq!(
+            error("mv v1, vg"),
+            "test.s:1: \"vg\" is not a register; expected v0-vf"
+        );
+        assert_eq!(error("a: clr\na: clr"), "test.s:2: a is already defined");
+        assert_eq!(
+            error("x = 9223372036854775807 + 1"),
+            "test.s:1: 9223372036854775807 + 1 is out of range"
+        );
+        assert_eq!(
+            error("A = B\nB = A"),
+            "test.s:1: A is defined in terms of itself"
+        );
+    }
+
+    #[test]
+    fn test_constants_are_evaluated_once() {
+        // Evaluating each use of a constant again would take 2^60 steps
+        let mut source = String::from("A0 = 0\n");
+        for i in 1..=60 {
+            source.push_str(&format!("A{} = A{} + A{}\n", i, i - 1, i - 1));
+        }
+        source.push_str("db A60 + 1");
+        assert_eq!(assemble_str(&source), Ok(vec![0x01]));
+    }
+}
@@ -1,15 +1,34 @@
-/// The Chip-8 runs at 500Hz which is equal to two million nanoseconds per cycle
-pub const CLOCK_SPEED: usize = 2_000_000;
+/// Timers are decremented and the display is redrawn 60 times per second
+pub const FRAME_RATE: u32 = 60;
 
-/// How often timers should be decremented relative to CPU cycles (500Hz / 60Hz ~= 8)
-pub const CPU_CYCLES_PER_TIMER_CYCLE: u8 = 8;
+/// How many instructions are executed each frame by default (8 * 60Hz ~= the original 500Hz)
+pub const INSTRUCTIONS_PER_FRAME: usize = 8;
 
 /// The Chip-8 has a 64x32 pixel display
 pub const DISPLAY_WIDTH: usize = 64;
 pub const DISPLAY_HEIGHT: usize = 32;
 
-/// The maximum number of states to be saved in the rewind buffer
-pub const MAX_SAVED_STATES: usize = 1_000;
+/// The SUPER-CHIP adds a 128x64 pixel high resolution mode
+pub const HIRES_DISPLAY_WIDTH: usize = 128;
+pub const HIRES_DISPLAY_HEIGHT: usize = 64;
+
+/// XO-CHIP extends addressable memory to 64K
+pub const MEMORY_SIZE: usize = 0x10000;
+
+/// ROMs are loaded into memory starting at 0x200
+pub const ROM_ADDRESS: u16 = 0x200;
+
+/// XO-CHIP draws to two bitplanes; each pixel of the FrameBuffer is a bitmask of the planes it's set in
+pub const PLANES: usize = 2;
+
+/// The SUPER-CHIP can store up to 16 registers in its RPL user flags
+pub const RPL_FLAGS: usize = 16;
+
+/// How many frames of history the rewind buffer holds by default (5 minutes at 60Hz)
+pub const REWIND_FRAMES: usize = 5 * 60 * 60;
+
+/// How many bytes the rewind buffer may use by default
+pub const REWIND_MEMORY: usize = 64 * 1024 * 1024;
 
 /// Chip-8 has sprites for hexadecimal characters in memory starting at 0x000
 pub static SPRITE_SHEET: [u8; 80] = [
@@ -30,3 +49,25 @@ pub static SPRITE_SHEET: [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
+
+/// The SUPER-CHIP has 8x10 sprites for hexadecimal characters in memory starting at 0x050
+/// The original only defined 0-9, A-F are included as later interpreters do
+pub const BIG_SPRITE_SHEET_ADDRESS: u16 = 0x050;
+pub static BIG_SPRITE_SHEET: [u8; 160] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
@@ -0,0 +1,245 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::constants::ROM_ADDRESS;
+use crate::mnemonic::{self, Mnemonic, Operands};
+use crate::opcode::Opcode;
+
+/// The most bytes written on a single `db` line
+const BYTES_PER_LINE: usize = 8;
+
+/// The column that address comments are aligned to
+const COMMENT_COLUMN: usize = 24;
+
+/// # Target
+/// Why an address is the target of a branch, which determines how its label is named.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Target {
+    /// `loc_206` reached by `jump` or `jumpi`
+    Jump,
+    /// `sub_206` reached by `call`
+    Call,
+}
+
+/// # Line
+/// A single instruction or a run of data bytes in the disassembly.
+enum Line<'a> {
+    Instruction {
+        mnemonic: &'static Mnemonic,
+        op: u16,
+        next: u16,
+    },
+    Data(&'a [u8]),
+}
+
+/// Disassembles a ROM into assembly that `assembler::assemble` turns back into the same bytes
+/// Code is told apart from data by tracing every path execution can take from the first
+/// instruction through jumps, calls, and skips; bytes no path reaches are written as `db` data.
+/// Branch and call targets are given labels (`loc_206`, `sub_206`).
+///
+/// # Arguments
+/// * `rom` the ROM, which is loaded at `ROM_ADDRESS`
+pub fn disassemble(rom: &[u8]) -> String {
+    let (starts, targets) = trace(rom);
+
+    let mut lines = Vec::new();
+    let mut address = ROM_ADDRESS as usize;
+    let end = ROM_ADDRESS as usize + rom.len();
+    while address < end {
+        if starts.contains(&address) {
+            let (mnemonic, op, next) = decode_at(rom, address).unwrap();
+            lines.push((address, Line::Instruction { mnemonic, op, next }));
+            address += mnemonic.size() as usize;
+        } else {
+            let run_end = (address + 1..end)
+                .find(|a| {
+                    starts.contains(a) || targets.contains_key(a) || a - address == BYTES_PER_LINE
+                })
+                .unwrap_or(end);
+            let offset = address - ROM_ADDRESS as usize;
+            lines.push((
+                address,
+                Line::Data(&rom[offset..offset + run_end - address]),
+            ));
+            address = run_end;
+        }
+    }
+
+    // Targets in the middle of an instruction, or outside the ROM, are left as addresses
+    let labels: BTreeMap<usize, String> = lines
+        .iter()
+        .filter_map(|(address, _)| {
+            targets
+                .get(address)
+                .map(|&target| (*address, label(*address, target)))
+        })
+        .collect();
+
+    let mut assembly = String::new();
+    for (address, line) in lines {
+        if let Some(label) = labels.get(&address) {
+            assembly.push_str(&format!("{}:\n", label));
+        }
+        let (text, comment) = match line {
+            Line::Instruction { mnemonic, op, next } => {
+                let text = match (mnemonic.operands, labels.get(&(op.addr() as usize))) {
+                    (Operands::Addr, Some(label)) if mnemonic.name != "loadi" => {
+                        format!("{} {}", mnemonic.name, label)
+                    }
+                    _ => mnemonic.format(op, next),
+                };
+                let comment = match mnemonic.operands {
+                    Operands::Long => format!("{:#05x}: {:04X} {:04X}", address, op, next),
+                    _ => format!("{:#05x}: {:04X}", address, op),
+                };
+                (text, comment)
+            }
+            Line::Data(bytes) => {
+                let bytes: Vec<String> =
+                    bytes.iter().map(|byte| format!("{:#04x}", byte)).collect();
+                (
+                    format!("db {}", bytes.join(", ")),
+                    format!("{:#05x}", address),
+                )
+            }
+        };
+        assembly.push_str(&format!(
+            "    {:width$} ; {}\n",
+            text,
+            comment,
+            width = COMMENT_COLUMN - 1
+        ));
+    }
+    assembly
+}
+
+/// Finds the addresses of every instruction execution can reach from the start of a ROM, and the
+/// targets of the branches among them
+///
+/// # Arguments
+/// * `rom` the ROM, which is loaded at `ROM_ADDRESS`
+fn trace(rom: &[u8]) -> (BTreeSet<usize>, BTreeMap<usize, Target>) {
+    let mut code = vec![false; rom.len()];
+    let mut starts = BTreeSet::new();
+    let mut targets = BTreeMap::new();
+    let mut pending = vec![ROM_ADDRESS as usize];
+    while let Some(address) = pending.pop() {
+        let (mnemonic, op, _) = match decode_at(rom, address) {
+            Some(decoded) => decoded,
+            None => continue,
+        };
+        let offset = address - ROM_ADDRESS as usize;
+        let bytes = offset..offset + mnemonic.size() as usize;
+        // Instructions that overlap ones already traced are left as data
+        if code[bytes.clone()].iter().any(|&traced| traced) {
+            continue;
+        }
+        code[bytes].iter_mut().for_each(|traced| *traced = true);
+        starts.insert(address);
+
+        let next = address + mnemonic.size() as usize;
+        let target = op.addr() as usize;
+        match mnemonic.name {
+            "jump" | "jumpi" => {
+                targets.entry(target).or_insert(Target::Jump);
+                pending.push(target);
+            }
+            "call" => {
+                targets.insert(target, Target::Call);
+                pending.extend([next, target]);
+            }
+            "rts" | "exit" => (),
+            "ske" | "skne" | "skre" | "skrne" | "skpr" | "skup" => {
+                // Matches `operations::skip`, which skips both words of a `loadil`
+                let skipped = match decode_at(rom, next) {
+                    Some((mnemonic, _, _)) => mnemonic.size() as usize,
+                    None => 0x2,
+                };
+                pending.extend([next + skipped, next]);
+            }
+            _ => pending.push(next),
+        }
+    }
+    (starts, targets)
+}
+
+/// Decodes the instruction at an address, returning its Mnemonic, opcode, and the following word
+/// Returns None if the bytes there don't encode an instruction that fits in the ROM
+///
+/// # Arguments
+/// * `rom` the ROM, which is loaded at `ROM_ADDRESS`
+/// * `address` where the instruction starts
+fn decode_at(rom: &[u8], address: usize) -> Option<(&'static Mnemonic, u16, u16)> {
+    let word = |address: usize| {
+        let offset = address.checked_sub(ROM_ADDRESS as usize)?;
+        match rom.get(offset..offset + 2) {
+            Some(&[high, low]) => Some(u16::from(high) << 8 | u16::from(low)),
+            _ => None,
+        }
+    };
+    let op = word(address)?;
+    let mnemonic = mnemonic::decode(op)?;
+    match mnemonic.operands {
+        Operands::Long => Some((mnemonic, op, word(address + 2)?)),
+        _ => Some((mnemonic, op, 0)),
+    }
+}
+
+/// Names the label of a branch target
+///
+/// # Arguments
+/// * `address` the target's address
+/// * `target` how the address is branched to
+fn label(address: usize, target: Target) -> String {
+    match target {
+        Target::Jump => format!("loc_{:03x}", address),
+        Target::Call => format!("sub_{:03x}", address),
+    }
+}
+
+#[cfg(test)]
+mod test_disassembler {
+    use super::*;
+
+    #[test]
+    fn test_labels_branches_and_leaves_unreachable_bytes_as_data() {
+        let rom = [
+            0x22, 0x06, 0x71, 0x01, 0x12, 0x02, 0x60, 0x2A, 0x00, 0xEE, 0xF0, 0x90, 0xF0,
+        ];
+        assert_eq!(
+            disassemble(&rom),
+            "    call sub_206            ; 0x200: 2206\n\
+             loc_202:\n\
+             \x20   add v1, 0x01            ; 0x202: 7101\n\
+             \x20   jump loc_202            ; 0x204: 1202\n\
+             sub_206:\n\
+             \x20   load v0, 0x2a           ; 0x206: 602A\n\
+             \x20   rts                     ; 0x208: 00EE\n\
+             \x20   db 0xf0, 0x90, 0xf0     ; 0x20a\n"
+        );
+    }
+
+    #[test]
+    fn test_follows_both_sides_of_skips() {
+        // ske skips a loadil, so both the loadil and the instruction after it are code
+        let rom = [
+            0x3A, 0x20, 0xF0, 0x00, 0x02, 0x0A, 0x00, 0xFD, 0xFF, 0xFF, 0x12, 0x34,
+        ];
+        assert_eq!(
+            disassemble(&rom),
+            "    ske va, 0x20            ; 0x200: 3A20\n\
+             \x20   loadil 0x020a           ; 0x202: F000 020A\n\
+             \x20   exit                    ; 0x206: 00FD\n\
+             \x20   db 0xff, 0xff, 0x12, 0x34 ; 0x208\n"
+        );
+    }
+
+    #[test]
+    fn test_splits_data_into_lines() {
+        let rom = [0x00; 20];
+        let lines: Vec<usize> = disassemble(&rom)
+            .lines()
+            .map(|line| line.matches("0x00").count())
+            .collect();
+        assert_eq!(lines, [8, 8, 4]);
+    }
+}
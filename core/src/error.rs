@@ -0,0 +1,65 @@
+use std::fmt;
+
+/// # Execution Error
+/// Describes an instruction that couldn't be executed and where it was encountered.
+///
+/// - `pc` the address of the instruction
+/// - `op` the instruction's opcode
+/// - `kind` what went wrong
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ExecutionError {
+    pub pc: u16,
+    pub op: u16,
+    pub kind: ErrorKind,
+}
+
+/// The ways in which executing an instruction can fail
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The opcode doesn't correspond to any instruction
+    UnknownOpcode,
+    /// A subroutine was called while the stack was already full
+    StackOverflow,
+    /// A subroutine returned while the stack was empty
+    StackUnderflow,
+    /// The instruction accessed `len` bytes of memory starting at `address`, running past its end
+    MemoryOutOfRange { address: u16, len: usize },
+}
+
+impl fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at pc {:#06X} ({:#06X})", self.kind, self.pc, self.op)
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::UnknownOpcode => write!(f, "unknown opcode"),
+            ErrorKind::StackOverflow => write!(f, "stack overflow"),
+            ErrorKind::StackUnderflow => write!(f, "stack underflow"),
+            ErrorKind::MemoryOutOfRange { address, len } => write!(
+                f,
+                "memory access of {} bytes at {:#06X} out of range",
+                len, address
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ExecutionError {}
+
+#[cfg(test)]
+mod test_error {
+    use super::*;
+
+    #[test]
+    fn test_execution_error_display() {
+        let error = ExecutionError {
+            pc: 0x200,
+            op: 0x00EE,
+            kind: ErrorKind::StackUnderflow,
+        };
+        assert_eq!(error.to_string(), "stack underflow at pc 0x0200 (0x00EE)");
+    }
+}
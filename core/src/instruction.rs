@@ -1,19 +1,65 @@
+use crate::access::Accesses;
+use crate::constants::MEMORY_SIZE;
+use crate::error::{ErrorKind, ExecutionError};
 use crate::opcode::Opcode;
 use crate::operations::*;
 use crate::state::State;
 
-/// Selects the correct Instruction for a given Opcode
-pub fn from_op(
+/// An operation that produces the next State from an Opcode, the current State, and the pressed keys,
+/// along with the locations it read and wrote to do so
+pub type Instruction = fn(
     op: &dyn Opcode,
-) -> fn(op: &dyn Opcode, state: &State, pressed_keys: [u8; 16]) -> State {
-    match op.nibbles() {
+    state: &State,
+    pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind>;
+
+/// Executes a single opcode against a state, producing the next state and the locations it accessed
+/// Fails without modifying anything if the opcode is unknown or can't be executed
+///
+/// # Arguments
+/// * `op` the opcode found at `state.pc`
+/// * `state` the state to execute the opcode against
+/// * `pressed_keys` the pressed status of each key
+pub fn execute(
+    op: u16,
+    state: &State,
+    pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ExecutionError> {
+    let error = |kind| ExecutionError {
+        pc: state.pc,
+        op,
+        kind,
+    };
+    // Every instruction advances the pc by at least 2 which would run off the end of memory
+    if state.pc as usize + 0x2 >= MEMORY_SIZE {
+        return Err(error(ErrorKind::MemoryOutOfRange {
+            address: state.pc,
+            len: 0x2,
+        }));
+    }
+    let instruction = from_op(&op).map_err(error)?;
+    instruction(&op, state, pressed_keys).map_err(error)
+}
+
+/// Selects the correct Instruction for a given Opcode
+pub fn from_op(op: &dyn Opcode) -> Result<Instruction, ErrorKind> {
+    let instruction: Instruction = match op.nibbles() {
+        (0x0, 0x0, 0xC, _) => scrd,
+        (0x0, 0x0, 0xD, _) => scru,
         (0x0, 0x0, 0xE, 0x0) => clr,
         (0x0, 0x0, 0xE, 0xE) => rts,
+        (0x0, 0x0, 0xF, 0xB) => scrr,
+        (0x0, 0x0, 0xF, 0xC) => scrl,
+        (0x0, 0x0, 0xF, 0xD) => exit,
+        (0x0, 0x0, 0xF, 0xE) => extd,
+        (0x0, 0x0, 0xF, 0xF) => exte,
         (0x1, ..) => jump,
         (0x2, ..) => call,
         (0x3, ..) => ske,
         (0x4, ..) => skne,
         (0x5, .., 0x0) => skre,
+        (0x5, .., 0x2) => savr,
+        (0x5, .., 0x3) => loadr,
         (0x6, ..) => load,
         (0x7, ..) => add,
         (0x8, .., 0x0) => mv,
@@ -32,51 +78,208 @@ pub fn from_op(
         (0xD, ..) => draw,
         (0xE, .., 0x9, 0xE) => skpr,
         (0xE, .., 0xA, 0x1) => skup,
+        (0xF, 0x0, 0x0, 0x0) => loadil,
+        (0xF, .., 0x0, 0x1) => plane,
         (0xF, .., 0x0, 0x7) => moved,
         (0xF, .., 0x0, 0xA) => keyd,
         (0xF, .., 0x1, 0x5) => loads,
         (0xF, .., 0x1, 0x8) => ld,
         (0xF, .., 0x1, 0xE) => addi,
         (0xF, .., 0x2, 0x9) => ldspr,
+        (0xF, .., 0x3, 0x0) => ldhspr,
         (0xF, .., 0x3, 0x3) => bcd,
         (0xF, .., 0x5, 0x5) => stor,
         (0xF, .., 0x6, 0x5) => read,
-        other => panic!("Opcode {:?} is not implemented", other),
-    }
+        (0xF, .., 0x7, 0x5) => srpl,
+        (0xF, .., 0x8, 0x5) => lrpl,
+        _ => return Err(ErrorKind::UnknownOpcode),
+    };
+    Ok(instruction)
 }
 
 #[cfg(test)]
 mod test_instruction {
     use super::*;
-    use crate::constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
-    use crate::state::State;
+    use crate::constants::{
+        BIG_SPRITE_SHEET_ADDRESS, DISPLAY_HEIGHT, DISPLAY_WIDTH, HIRES_DISPLAY_HEIGHT,
+        HIRES_DISPLAY_WIDTH,
+    };
+    use crate::quirks::{MemoryIncrement, Profile, Quirks};
+    use crate::rng::Rng;
+    use crate::state::{Resolution, State};
+
+    #[test]
+    fn test_unknown_opcode() {
+        let state = State::new();
+        let op = 0x5121;
+        let error = execute(op, &state, [0; 16]).err().unwrap();
+        assert_eq!(
+            error,
+            ExecutionError {
+                pc: 0x200,
+                op,
+                kind: ErrorKind::UnknownOpcode
+            }
+        );
+    }
+
+    #[test]
+    fn test_pc_out_of_range() {
+        let mut state = State::new();
+        state.pc = 0xFFFE;
+        let op = 0x00E0;
+        let error = execute(op, &state, [0; 16]).err().unwrap();
+        assert_eq!(
+            error.kind,
+            ErrorKind::MemoryOutOfRange {
+                address: 0xFFFE,
+                len: 0x2
+            }
+        );
+    }
 
     #[test]
     fn test_00e0_cls() {
         let mut state = State::new();
         state.frame_buffer[0][0] = 1;
         let op = 0x00E0;
-        let state = from_op(&0x00E0)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.frame_buffer[0][0], 0);
     }
 
+    #[test]
+    fn test_00e0_cls_only_clears_selected_planes() {
+        let mut state = State::new();
+        state.plane = 0x2;
+        state.frame_buffer[0][0] = 0x3;
+        let op = 0x00E0;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.frame_buffer[0][0], 0x1);
+    }
+
+    #[test]
+    fn test_00cn_scd() {
+        let mut state = State::new();
+        state.frame_buffer[0][0] = 1;
+        state.frame_buffer[DISPLAY_HEIGHT - 1][0] = 1;
+        let op = 0x00C2;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.frame_buffer[0][0], 0);
+        assert_eq!(state.frame_buffer[2][0], 1);
+        // Pixels scrolled past the bottom of the display are discarded
+        assert_eq!(state.frame_buffer[DISPLAY_HEIGHT + 1][0], 0);
+    }
+
+    #[test]
+    fn test_00dn_scu() {
+        let mut state = State::new();
+        state.frame_buffer[2][0] = 1;
+        let op = 0x00D2;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.frame_buffer[0][0], 1);
+        assert_eq!(state.frame_buffer[2][0], 0);
+    }
+
+    #[test]
+    fn test_00dn_scu_only_scrolls_selected_planes() {
+        let mut state = State::new();
+        state.plane = 0x2;
+        state.frame_buffer[2][0] = 0x3;
+        let op = 0x00D2;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.frame_buffer[0][0], 0x2);
+        assert_eq!(state.frame_buffer[2][0], 0x1);
+    }
+
     #[test]
     fn test_00ee_ret() {
         let mut state = State::new();
         state.sp = 0x1;
         state.stack[state.sp as usize] = 0xABCD;
         let op = 0x00EE;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.sp, 0x0);
         // Add 2 to the program as it's bumped after opcode execution
         assert_eq!(state.pc, 0xABCD + 0x2);
     }
 
+    #[test]
+    fn test_00ee_ret_wraps() {
+        let mut state = State::new();
+        state.sp = 0x1;
+        state.stack[state.sp as usize] = 0xFFFE;
+        let op = 0x00EE;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.pc, 0x0000);
+    }
+
+    #[test]
+    fn test_00ee_ret_underflows() {
+        let state = State::new();
+        let op = 0x00EE;
+        let error = execute(op, &state, [0; 16]).err().unwrap();
+        assert_eq!(error.kind, ErrorKind::StackUnderflow);
+    }
+
+    #[test]
+    fn test_00fb_scr() {
+        let mut state = State::new();
+        state.resolution = Resolution::High;
+        state.frame_buffer[0][0] = 1;
+        state.frame_buffer[0][HIRES_DISPLAY_WIDTH - 1] = 1;
+        let op = 0x00FB;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.frame_buffer[0][0..5], [0, 0, 0, 0, 1]);
+        assert_eq!(state.frame_buffer[0][HIRES_DISPLAY_WIDTH - 1], 0);
+    }
+
+    #[test]
+    fn test_00fc_scl() {
+        let mut state = State::new();
+        state.frame_buffer[0][4] = 1;
+        state.frame_buffer[0][DISPLAY_WIDTH - 1] = 1;
+        let op = 0x00FC;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.frame_buffer[0][0], 1);
+        assert_eq!(
+            state.frame_buffer[0][DISPLAY_WIDTH - 5..DISPLAY_WIDTH],
+            [1, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_00fd_exit() {
+        let state = State::new();
+        let op = 0x00FD;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert!(state.exited);
+        assert_eq!(state.pc, 0x0200);
+    }
+
+    #[test]
+    fn test_00fe_low() {
+        let mut state = State::new();
+        state.resolution = Resolution::High;
+        state.frame_buffer[0][0] = 1;
+        let op = 0x00FE;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.resolution, Resolution::Low);
+        assert_eq!(state.frame_buffer[0][0], 0);
+    }
+
+    #[test]
+    fn test_00ff_high() {
+        let state = State::new();
+        let op = 0x00FF;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.resolution, Resolution::High);
+    }
+
     #[test]
     fn test_1nnn_jp() {
         let state = State::new();
         let op = 0x1ABC;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.pc, 0x0ABC);
     }
 
@@ -85,18 +288,27 @@ mod test_instruction {
         let mut state = State::new();
         state.pc = 0xABCD;
         let op = 0x2123;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.sp, 0x1);
         assert_eq!(state.stack[state.sp as usize], 0xABCD);
         assert_eq!(state.pc, 0x0123);
     }
 
+    #[test]
+    fn test_2nnn_call_overflows() {
+        let mut state = State::new();
+        state.sp = 0xF;
+        let op = 0x2123;
+        let error = execute(op, &state, [0; 16]).err().unwrap();
+        assert_eq!(error.kind, ErrorKind::StackOverflow);
+    }
+
     #[test]
     fn test_3xkk_se_skips() {
         let mut state = State::new();
         state.v[0x1] = 0x11;
         let op = 0x3111;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.pc, 0x0204);
     }
 
@@ -104,15 +316,24 @@ mod test_instruction {
     fn test_3xkk_se_doesntskip() {
         let state = State::new();
         let op = 0x3111;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.pc, 0x0202);
     }
 
+    #[test]
+    fn test_3xkk_se_skips_long_load() {
+        let mut state = State::new();
+        state.memory[0x202..0x206].copy_from_slice(&[0xF0, 0x00, 0x12, 0x34]);
+        let op = 0x3100;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.pc, 0x0206);
+    }
+
     #[test]
     fn test_4xkk_sne_skips() {
         let state = State::new();
         let op = 0x4111;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.pc, 0x0204);
     }
 
@@ -121,7 +342,7 @@ mod test_instruction {
         let mut state = State::new();
         state.v[0x1] = 0x11;
         let op = 0x4111;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.pc, 0x0202);
     }
 
@@ -131,7 +352,7 @@ mod test_instruction {
         state.v[0x1] = 0x11;
         state.v[0x2] = 0x11;
         let op = 0x5120;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.pc, 0x0204);
     }
 
@@ -140,15 +361,72 @@ mod test_instruction {
         let mut state = State::new();
         state.v[0x1] = 0x11;
         let op = 0x5120;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.pc, 0x0202);
     }
 
+    #[test]
+    fn test_5xy2_save_range() {
+        let mut state = State::new();
+        state.i = 0x300;
+        state.v[0x1..0x4].copy_from_slice(&[0x1, 0x2, 0x3]);
+        let op = 0x5132;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.memory[0x300..0x304], [0x1, 0x2, 0x3, 0x0]);
+        assert_eq!(state.i, 0x300);
+    }
+
+    #[test]
+    fn test_5xy2_save_range_descending() {
+        let mut state = State::new();
+        state.i = 0x300;
+        state.v[0x1..0x4].copy_from_slice(&[0x1, 0x2, 0x3]);
+        let op = 0x5312;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.memory[0x300..0x303], [0x3, 0x2, 0x1]);
+    }
+
+    #[test]
+    fn test_5xy2_save_range_out_of_range() {
+        let mut state = State::new();
+        state.i = 0xFFFF;
+        let op = 0x5012;
+        let error = execute(op, &state, [0; 16]).err().unwrap();
+        assert_eq!(
+            error.kind,
+            ErrorKind::MemoryOutOfRange {
+                address: 0xFFFF,
+                len: 0x2
+            }
+        );
+    }
+
+    #[test]
+    fn test_5xy3_load_range() {
+        let mut state = State::new();
+        state.i = 0x300;
+        state.memory[0x300..0x303].copy_from_slice(&[0x1, 0x2, 0x3]);
+        let op = 0x5243;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.v[0x1..0x6], [0x0, 0x1, 0x2, 0x3, 0x0]);
+        assert_eq!(state.i, 0x300);
+    }
+
+    #[test]
+    fn test_5xy3_load_range_descending() {
+        let mut state = State::new();
+        state.i = 0x300;
+        state.memory[0x300..0x303].copy_from_slice(&[0x1, 0x2, 0x3]);
+        let op = 0x5423;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.v[0x2..0x5], [0x3, 0x2, 0x1]);
+    }
+
     #[test]
     fn test_6xkk_ld() {
         let state = State::new();
         let op = 0x6122;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.v[0x1], 0x22);
     }
 
@@ -157,7 +435,7 @@ mod test_instruction {
         let mut state = State::new();
         state.v[0x1] = 0x1;
         let op = 0x7122;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.v[0x1], 0x23);
     }
 
@@ -166,7 +444,7 @@ mod test_instruction {
         let mut state = State::new();
         state.v[0x2] = 0x1;
         let op = 0x8120;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.v[0x1], 0x1);
     }
 
@@ -176,37 +454,82 @@ mod test_instruction {
         state.v[0x1] = 0x6;
         state.v[0x2] = 0x3;
         let op = 0x8121;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.v[0x1], 0x7);
     }
 
+    #[test]
+    fn test_8xy1_or_vf_reset_quirk() {
+        let mut state = State::with_quirks(Quirks {
+            vf_reset: true,
+            ..Quirks::default()
+        });
+        state.v[0xF] = 0x1;
+        let op = 0x8121;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.v[0xF], 0x0);
+    }
+
     #[test]
     fn test_8xy2_and() {
         let mut state = State::new();
         state.v[0x1] = 0x6;
         state.v[0x2] = 0x3;
         let op = 0x8122;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.v[0x1], 0x2);
     }
 
+    #[test]
+    fn test_8xy2_and_vf_reset_quirk() {
+        let mut state = State::with_quirks(Quirks {
+            vf_reset: true,
+            ..Quirks::default()
+        });
+        state.v[0xF] = 0x1;
+        let op = 0x8122;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.v[0xF], 0x0);
+    }
+
     #[test]
     fn test_8xy3_xor() {
         let mut state = State::new();
         state.v[0x1] = 0x6;
         state.v[0x2] = 0x3;
         let op = 0x8123;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.v[0x1], 0x5);
     }
 
+    #[test]
+    fn test_8xy3_xor_vf_reset_quirk() {
+        let mut state = State::with_quirks(Quirks {
+            vf_reset: true,
+            ..Quirks::default()
+        });
+        state.v[0xF] = 0x1;
+        let op = 0x8123;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.v[0xF], 0x0);
+    }
+
+    #[test]
+    fn test_8xy3_xor_leaves_vf_without_quirk() {
+        let mut state = State::new();
+        state.v[0xF] = 0x1;
+        let op = 0x8123;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.v[0xF], 0x1);
+    }
+
     #[test]
     fn test_8xy4_add_nocarry() {
         let mut state = State::new();
         state.v[0x1] = 0xEE;
         state.v[0x2] = 0x11;
         let op = 0x8124;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.v[0x1], 0xFF);
         assert_eq!(state.v[0xF], 0x0);
     }
@@ -217,7 +540,7 @@ mod test_instruction {
         state.v[0x1] = 0xFF;
         state.v[0x2] = 0x11;
         let op = 0x8124;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.v[0x1], 0x10);
         assert_eq!(state.v[0xF], 0x1);
     }
@@ -228,7 +551,7 @@ mod test_instruction {
         state.v[0x1] = 0x33;
         state.v[0x2] = 0x11;
         let op = 0x8125;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.v[0x1], 0x22);
         assert_eq!(state.v[0xF], 0x1);
     }
@@ -239,7 +562,7 @@ mod test_instruction {
         state.v[0x1] = 0x11;
         state.v[0x2] = 0x12;
         let op = 0x8125;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.v[0x1], 0xFF);
         assert_eq!(state.v[0xF], 0x0);
     }
@@ -249,7 +572,7 @@ mod test_instruction {
         let mut state = State::new();
         state.v[0x1] = 0x5;
         let op = 0x8106;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.v[0x1], 0x2);
         assert_eq!(state.v[0xF], 0x1);
     }
@@ -259,18 +582,33 @@ mod test_instruction {
         let mut state = State::new();
         state.v[0x1] = 0x4;
         let op = 0x8106;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.v[0x1], 0x2);
         assert_eq!(state.v[0xF], 0x0);
     }
 
+    #[test]
+    fn test_8xy6_shr_shift_vy_quirk() {
+        let mut state = State::with_quirks(Quirks {
+            shift_vy: true,
+            ..Quirks::default()
+        });
+        state.v[0x1] = 0x4;
+        state.v[0x2] = 0x5;
+        let op = 0x8126;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.v[0x1], 0x2);
+        assert_eq!(state.v[0x2], 0x5);
+        assert_eq!(state.v[0xF], 0x1);
+    }
+
     #[test]
     fn test_8xy7_subn_nocarry() {
         let mut state = State::new();
         state.v[0x1] = 0x11;
         state.v[0x2] = 0x33;
         let op = 0x8127;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.v[0x1], 0x22);
         assert_eq!(state.v[0xF], 0x1);
     }
@@ -281,7 +619,7 @@ mod test_instruction {
         state.v[0x1] = 0x12;
         state.v[0x2] = 0x11;
         let op = 0x8127;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.v[0x1], 0xFF);
         assert_eq!(state.v[0xF], 0x0);
     }
@@ -291,7 +629,7 @@ mod test_instruction {
         let mut state = State::new();
         state.v[0x1] = 0xFF;
         let op = 0x810E;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         // 0xFF * 2 = 0x01FE
         assert_eq!(state.v[0x1], 0xFE);
         assert_eq!(state.v[0xF], 0x1);
@@ -302,17 +640,32 @@ mod test_instruction {
         let mut state = State::new();
         state.v[0x1] = 0x4;
         let op = 0x810E;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.v[0x1], 0x8);
         assert_eq!(state.v[0xF], 0x0);
     }
 
+    #[test]
+    fn test_8xye_shl_shift_vy_quirk() {
+        let mut state = State::with_quirks(Quirks {
+            shift_vy: true,
+            ..Quirks::default()
+        });
+        state.v[0x1] = 0x4;
+        state.v[0x2] = 0x81;
+        let op = 0x812E;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.v[0x1], 0x2);
+        assert_eq!(state.v[0x2], 0x81);
+        assert_eq!(state.v[0xF], 0x1);
+    }
+
     #[test]
     fn test_9xy0_sne_skips() {
         let mut state = State::new();
         state.v[0x1] = 0x11;
         let op = 0x9120;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.pc, 0x0204);
     }
 
@@ -322,7 +675,7 @@ mod test_instruction {
         state.v[0x1] = 0x11;
         state.v[0x2] = 0x11;
         let op = 0x9120;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.pc, 0x0202);
     }
 
@@ -330,7 +683,7 @@ mod test_instruction {
     fn test_annn_ld() {
         let state = State::new();
         let op = 0xAABC;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.i, 0xABC);
     }
 
@@ -339,11 +692,43 @@ mod test_instruction {
         let mut state = State::new();
         state.v[0x0] = 0x2;
         let op = 0xBABC;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.pc, 0xABE);
     }
 
-    // Not testing cxkk as it generates a random number
+    #[test]
+    fn test_bnnn_jp_jump_vx_quirk() {
+        let mut state = State::with_quirks(Quirks {
+            jump_vx: true,
+            ..Quirks::default()
+        });
+        state.v[0x0] = 0x2;
+        state.v[0xA] = 0x4;
+        let op = 0xBABC;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.pc, 0xAC0);
+    }
+
+    #[test]
+    fn test_cxkk_rnd_is_deterministic() {
+        let mut state = State::new();
+        state.rng = Rng::new(0x1234);
+        let op = 0xC1FF;
+        let first = execute(op, &state, [0; 16]).unwrap().0;
+        let again = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(first.v[0x1], again.v[0x1]);
+        assert_eq!(first.v[0x1], Rng::new(0x1234).next_byte().0);
+        // The generator advances so the next number is drawn from further along the sequence
+        assert_ne!(first.rng, state.rng);
+    }
+
+    #[test]
+    fn test_cxkk_rnd_masks() {
+        let state = State::new();
+        let op = 0xC10F;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.v[0x1] & 0xF0, 0x0);
+    }
 
     #[test]
     fn test_dxyn_drw_draws() {
@@ -351,8 +736,8 @@ mod test_instruction {
         state.v[0x0] = 0x1;
         // Draw the 0x0 sprite with a 1x 1y offset
         let op = 0xD005;
-        let state = from_op(&op)(&op, &state, [0; 16]);
-        let mut expected = [[0; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        let mut expected = [[0; HIRES_DISPLAY_WIDTH]; HIRES_DISPLAY_HEIGHT];
         expected[1][1..5].copy_from_slice(&[1, 1, 1, 1]);
         expected[2][1..5].copy_from_slice(&[1, 0, 0, 1]);
         expected[3][1..5].copy_from_slice(&[1, 0, 0, 1]);
@@ -370,7 +755,7 @@ mod test_instruction {
         let mut state = State::new();
         state.frame_buffer[0][0] = 1;
         let op = 0xD001;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.v[0xF], 0x1)
     }
 
@@ -381,10 +766,129 @@ mod test_instruction {
         state.frame_buffer[0][2..6].copy_from_slice(&[0, 1, 0, 1]);
         // 1 1 0 0 -> Draw xor
         let op = 0xD005;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.frame_buffer[0][2..6], [1, 0, 0, 1])
     }
 
+    #[test]
+    fn test_dxyn_drw_out_of_range() {
+        let mut state = State::new();
+        state.i = 0xFFFC;
+        let op = 0xD005;
+        let error = execute(op, &state, [0; 16]).err().unwrap();
+        assert_eq!(
+            error.kind,
+            ErrorKind::MemoryOutOfRange {
+                address: 0xFFFC,
+                len: 0x5
+            }
+        );
+    }
+
+    #[test]
+    fn test_dxyn_drw_wraps() {
+        let mut state = State::new();
+        state.v[0x0] = (DISPLAY_WIDTH - 2) as u8;
+        state.v[0x1] = (DISPLAY_HEIGHT - 1) as u8;
+        // Draw the top row of the 0x0 sprite (1 1 1 1) across the bottom right corner
+        let op = 0xD012;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(
+            state.frame_buffer[DISPLAY_HEIGHT - 1][DISPLAY_WIDTH - 2..DISPLAY_WIDTH],
+            [1, 1]
+        );
+        assert_eq!(state.frame_buffer[DISPLAY_HEIGHT - 1][0..2], [1, 1]);
+        assert_eq!(
+            state.frame_buffer[0][DISPLAY_WIDTH - 2..DISPLAY_WIDTH],
+            [1, 0]
+        );
+    }
+
+    #[test]
+    fn test_dxyn_drw_clip_sprites_quirk() {
+        let mut state = State::with_quirks(Quirks {
+            clip_sprites: true,
+            ..Quirks::default()
+        });
+        state.v[0x0] = (DISPLAY_WIDTH - 2) as u8;
+        state.v[0x1] = (DISPLAY_HEIGHT - 1) as u8;
+        let op = 0xD012;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(
+            state.frame_buffer[DISPLAY_HEIGHT - 1][DISPLAY_WIDTH - 2..DISPLAY_WIDTH],
+            [1, 1]
+        );
+        assert_eq!(state.frame_buffer[DISPLAY_HEIGHT - 1][0..2], [0, 0]);
+        assert_eq!(
+            state.frame_buffer[0][DISPLAY_WIDTH - 2..DISPLAY_WIDTH],
+            [0, 0]
+        );
+    }
+
+    #[test]
+    fn test_dxyn_drw_clip_sprites_quirk_wraps_origin() {
+        let mut state = State::with_quirks(Quirks {
+            clip_sprites: true,
+            ..Quirks::default()
+        });
+        state.v[0x0] = (DISPLAY_WIDTH + 1) as u8;
+        let op = 0xD011;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.frame_buffer[0][1..5], [1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_dxyn_drw_wraps_in_high_resolution() {
+        let mut state = State::new();
+        state.resolution = Resolution::High;
+        state.v[0x0] = (HIRES_DISPLAY_WIDTH - 2) as u8;
+        let op = 0xD011;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.frame_buffer[0][HIRES_DISPLAY_WIDTH - 2..], [1, 1]);
+        assert_eq!(state.frame_buffer[0][0..3], [1, 1, 0]);
+    }
+
+    #[test]
+    fn test_dxy0_drw_draws_16x16() {
+        let mut state = State::new();
+        state.resolution = Resolution::High;
+        state.i = 0x300;
+        state.memory[0x300..0x302].copy_from_slice(&[0x80, 0x01]);
+        state.memory[0x31E..0x320].copy_from_slice(&[0x80, 0x01]);
+        let op = 0xD000;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.frame_buffer[0][0], 1);
+        assert_eq!(state.frame_buffer[0][15], 1);
+        assert_eq!(state.frame_buffer[15][0], 1);
+        assert_eq!(state.frame_buffer[15][15], 1);
+        assert_eq!(state.frame_buffer[16][0], 0);
+        assert_eq!(state.frame_buffer[0][16], 0);
+    }
+
+    #[test]
+    fn test_dxyn_drw_draws_to_selected_plane() {
+        let mut state = State::new();
+        state.plane = 0x2;
+        state.frame_buffer[0][0] = 0x1;
+        let op = 0xD001;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.frame_buffer[0][0..5], [0x3, 0x2, 0x2, 0x2, 0x0]);
+        // Only collisions within the selected planes count
+        assert_eq!(state.v[0xF], 0x0);
+    }
+
+    #[test]
+    fn test_dxyn_drw_draws_each_plane_with_its_own_sprite() {
+        let mut state = State::new();
+        state.plane = 0x3;
+        state.i = 0x300;
+        state.memory[0x300..0x302].copy_from_slice(&[0xC0, 0xA0]);
+        let op = 0xD001;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.frame_buffer[0][0..3], [0x3, 0x1, 0x2]);
+        assert_eq!(state.frame_buffer[1][0..3], [0x0, 0x0, 0x0]);
+    }
+
     #[test]
     fn test_ex9e_skp_skips() {
         let mut state = State::new();
@@ -392,7 +896,7 @@ mod test_instruction {
         pressed_keys[0xE] = 0x1;
         state.v[0x1] = 0xE;
         let op = 0xE19E;
-        let state = from_op(&op)(&op, &state, pressed_keys);
+        let state = execute(op, &state, pressed_keys).unwrap().0;
         assert_eq!(state.pc, 0x0204);
     }
 
@@ -400,15 +904,25 @@ mod test_instruction {
     fn test_ex9e_skp_doesntskip() {
         let state = State::new();
         let op = 0xE19E;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.pc, 0x0202);
     }
 
+    #[test]
+    fn test_ex9e_skp_masks_key() {
+        let mut state = State::new();
+        let pressed_keys = [0x1; 16];
+        state.v[0x1] = 0x20;
+        let op = 0xE19E;
+        let state = execute(op, &state, pressed_keys).unwrap().0;
+        assert_eq!(state.pc, 0x0204);
+    }
+
     #[test]
     fn test_exa1_sknp_skips() {
         let state = State::new();
         let op = 0xE1A1;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.pc, 0x0204);
     }
 
@@ -419,16 +933,43 @@ mod test_instruction {
         pressed_keys[0xE] = 0x1;
         state.v[0x1] = 0xE;
         let op = 0xE1A1;
-        let state = from_op(&op)(&op, &state, pressed_keys);
+        let state = execute(op, &state, pressed_keys).unwrap().0;
         assert_eq!(state.pc, 0x0202);
     }
 
+    #[test]
+    fn test_exa1_sknp_masks_key() {
+        let mut state = State::new();
+        state.v[0x1] = 0x20;
+        let op = 0xE1A1;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.pc, 0x0204);
+    }
+
+    #[test]
+    fn test_f000_nnnn_ld() {
+        let mut state = State::new();
+        state.memory[0x200..0x204].copy_from_slice(&[0xF0, 0x00, 0xAB, 0xCD]);
+        let op = 0xF000;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.i, 0xABCD);
+        assert_eq!(state.pc, 0x0204);
+    }
+
+    #[test]
+    fn test_fn01_plane() {
+        let state = State::new();
+        let op = 0xF201;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.plane, 0x2);
+    }
+
     #[test]
     fn test_fx07_ld() {
         let mut state = State::new();
         state.delay_timer = 0xF;
         let op = 0xF107;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.v[0x1], 0xF);
     }
 
@@ -436,7 +977,7 @@ mod test_instruction {
     fn test_fx0a_ld_setsregisterneedingkey() {
         let state = State::new();
         let op = 0xF10A;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.register_needing_key, Some(0x1));
     }
 
@@ -445,7 +986,7 @@ mod test_instruction {
         let mut state = State::new();
         state.v[0x1] = 0xF;
         let op = 0xf115;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.delay_timer, 0xF);
     }
 
@@ -454,7 +995,7 @@ mod test_instruction {
         let mut state = State::new();
         state.v[0x1] = 0xF;
         let op = 0xf118;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.sound_timer, 0xF);
     }
 
@@ -464,7 +1005,7 @@ mod test_instruction {
         state.i = 0x1;
         state.v[0x1] = 0x1;
         let op = 0xF11E;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.i, 0x2);
     }
 
@@ -473,10 +1014,19 @@ mod test_instruction {
         let mut state = State::new();
         state.v[0x1] = 0x2;
         let op = 0xF129;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.i, 0xA);
     }
 
+    #[test]
+    fn test_fx30_ld() {
+        let mut state = State::new();
+        state.v[0x1] = 0x2;
+        let op = 0xF130;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.i, BIG_SPRITE_SHEET_ADDRESS + 0x14);
+    }
+
     #[test]
     fn test_fx33_ld() {
         let mut state = State::new();
@@ -484,27 +1034,147 @@ mod test_instruction {
         state.v[0x1] = 0x7B;
         state.i = 0x200;
         let op = 0xF133;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.memory[0x200..0x203], [0x1, 0x2, 0x3]);
     }
 
+    #[test]
+    fn test_fx33_ld_out_of_range() {
+        let mut state = State::new();
+        state.i = 0xFFFE;
+        let op = 0xF133;
+        assert!(execute(op, &state, [0; 16]).is_err());
+    }
+
     #[test]
     fn test_fx_55_ld() {
         let mut state = State::new();
         state.i = 0x200;
         state.v[0x0..0x5].copy_from_slice(&[0x1, 0x2, 0x3, 0x4, 0x5]);
         let op = 0xF455;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.memory[0x200..0x205], [0x1, 0x2, 0x3, 0x4, 0x5]);
     }
 
+    #[test]
+    fn test_fx_55_ld_above_4k() {
+        let mut state = State::new();
+        state.i = 0xF000;
+        state.v[0x0] = 0x1;
+        let op = 0xF055;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.memory[0xF000], 0x1);
+    }
+
+    #[test]
+    fn test_fx_55_ld_out_of_range() {
+        let mut state = State::new();
+        state.i = 0xFFFE;
+        let op = 0xF255;
+        let error = execute(op, &state, [0; 16]).err().unwrap();
+        assert_eq!(
+            error.kind,
+            ErrorKind::MemoryOutOfRange {
+                address: 0xFFFE,
+                len: 0x3
+            }
+        );
+    }
+
+    #[test]
+    fn test_fx_55_ld_leaves_i_without_quirk() {
+        let mut state = State::new();
+        state.i = 0x200;
+        let op = 0xF455;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.i, 0x200);
+    }
+
+    #[test]
+    fn test_fx_55_ld_memory_increment_x_quirk() {
+        let mut state = State::with_quirks(Quirks {
+            memory_increment: MemoryIncrement::X,
+            ..Quirks::default()
+        });
+        state.i = 0x200;
+        let op = 0xF455;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.i, 0x204);
+    }
+
+    #[test]
+    fn test_fx_55_ld_memory_increment_x_plus_one_quirk() {
+        let mut state = State::with_quirks(Quirks {
+            memory_increment: MemoryIncrement::XPlusOne,
+            ..Quirks::default()
+        });
+        state.i = 0x200;
+        let op = 0xF455;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.i, 0x205);
+    }
+
     #[test]
     fn test_fx_65_ld() {
         let mut state = State::new();
         state.i = 0x200;
         state.memory[0x200..0x205].copy_from_slice(&[0x1, 0x2, 0x3, 0x4, 0x5]);
         let op = 0xF465;
-        let state = from_op(&op)(&op, &state, [0; 16]);
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.v[0x0..0x5], [0x1, 0x2, 0x3, 0x4, 0x5]);
+    }
+
+    #[test]
+    fn test_fx_65_ld_out_of_range() {
+        let mut state = State::new();
+        state.i = 0xFFFF;
+        let op = 0xF165;
+        assert!(execute(op, &state, [0; 16]).is_err());
+    }
+
+    #[test]
+    fn test_fx75_ld() {
+        let mut state = State::new();
+        state.v[0x0..0x3].copy_from_slice(&[0x1, 0x2, 0x3]);
+        let op = 0xF275;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.rpl[0x0..0x4], [0x1, 0x2, 0x3, 0x0]);
+    }
+
+    #[test]
+    fn test_fx85_ld() {
+        let mut state = State::new();
+        state.rpl[0x0..0x3].copy_from_slice(&[0x1, 0x2, 0x3]);
+        let op = 0xF185;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.v[0x0..0x3], [0x1, 0x2, 0x0]);
+    }
+
+    #[test]
+    fn test_fx_65_ld_memory_increment_x_plus_one_quirk() {
+        let mut state = State::with_quirks(Quirks {
+            memory_increment: MemoryIncrement::XPlusOne,
+            ..Quirks::default()
+        });
+        state.i = 0x200;
+        state.memory[0x200..0x205].copy_from_slice(&[0x1, 0x2, 0x3, 0x4, 0x5]);
+        let op = 0xF465;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
         assert_eq!(state.v[0x0..0x5], [0x1, 0x2, 0x3, 0x4, 0x5]);
+        assert_eq!(state.i, 0x205);
+    }
+
+    #[test]
+    fn test_cosmac_vip_profile_quirks() {
+        let mut state = State::with_quirks(Profile::CosmacVip.quirks());
+        state.v[0x1] = 0x4;
+        state.v[0x2] = 0x5;
+        state.i = 0x200;
+        let op = 0x8126;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.v[0x1], 0x2);
+        let op = 0xF165;
+        let state = execute(op, &state, [0; 16]).unwrap().0;
+        assert_eq!(state.i, 0x202);
     }
 }
@@ -1,5 +1,12 @@
+pub mod access;
+pub mod assembler;
 pub mod constants;
+pub mod disassembler;
+pub mod error;
 pub mod instruction;
+pub mod mnemonic;
 pub mod opcode;
 mod operations;
+pub mod quirks;
+pub mod rng;
 pub mod state;
@@ -0,0 +1,213 @@
+use crate::instruction::Instruction;
+use crate::opcode::Opcode;
+use crate::operations::*;
+
+/// # Mnemonic
+/// How an instruction is written in assembly and which opcodes encode it.
+///
+/// - `name` the name of the operation that executes the instruction
+/// - `pattern` & `mask` an opcode encodes the instruction if `op & mask == pattern`
+/// - `operands` which of the opcode's fields are operands and how they're written
+/// - `instruction` the operation that executes the instruction
+pub struct Mnemonic {
+    pub name: &'static str,
+    pub pattern: u16,
+    pub mask: u16,
+    pub operands: Operands,
+    pub instruction: Instruction,
+}
+
+/// The fields of an opcode that an instruction takes as operands, in the order they're written
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Operands {
+    /// `clr`
+    None,
+    /// `scrd 4` the fourth nibble as a number
+    N,
+    /// `plane 3` the second nibble as a number
+    X,
+    /// `jump 0x234` a 12-bit address
+    Addr,
+    /// `loadil 0x1234` a 16-bit address in the word following the opcode
+    Long,
+    /// `keyd v1`
+    Vx,
+    /// `ske v1, 0x20`
+    VxKk,
+    /// `mv v1, v2`
+    VxVy,
+    /// `draw v1, v2, 5`
+    VxVyN,
+}
+
+macro_rules! mnemonic {
+    ($name:ident, $pattern:expr, $mask:expr, $operands:ident) => {
+        Mnemonic {
+            name: stringify!($name),
+            pattern: $pattern,
+            mask: $mask,
+            operands: Operands::$operands,
+            instruction: $name,
+        }
+    };
+}
+
+/// Every instruction, matching the opcodes `instruction::from_op` recognizes
+pub const MNEMONICS: [Mnemonic; 48] = [
+    mnemonic!(scrd, 0x00C0, 0xFFF0, N),
+    mnemonic!(scru, 0x00D0, 0xFFF0, N),
+    mnemonic!(clr, 0x00E0, 0xFFFF, None),
+    mnemonic!(rts, 0x00EE, 0xFFFF, None),
+    mnemonic!(scrr, 0x00FB, 0xFFFF, None),
+    mnemonic!(scrl, 0x00FC, 0xFFFF, None),
+    mnemonic!(exit, 0x00FD, 0xFFFF, None),
+    mnemonic!(extd, 0x00FE, 0xFFFF, None),
+    mnemonic!(exte, 0x00FF, 0xFFFF, None),
+    mnemonic!(jump, 0x1000, 0xF000, Addr),
+    mnemonic!(call, 0x2000, 0xF000, Addr),
+    mnemonic!(ske, 0x3000, 0xF000, VxKk),
+    mnemonic!(skne, 0x4000, 0xF000, VxKk),
+    mnemonic!(skre, 0x5000, 0xF00F, VxVy),
+    mnemonic!(savr, 0x5002, 0xF00F, VxVy),
+    mnemonic!(loadr, 0x5003, 0xF00F, VxVy),
+    mnemonic!(load, 0x6000, 0xF000, VxKk),
+    mnemonic!(add, 0x7000, 0xF000, VxKk),
+    mnemonic!(mv, 0x8000, 0xF00F, VxVy),
+    mnemonic!(or, 0x8001, 0xF00F, VxVy),
+    mnemonic!(and, 0x8002, 0xF00F, VxVy),
+    mnemonic!(xor, 0x8003, 0xF00F, VxVy),
+    mnemonic!(addr, 0x8004, 0xF00F, VxVy),
+    mnemonic!(sub, 0x8005, 0xF00F, VxVy),
+    mnemonic!(shr, 0x8006, 0xF00F, VxVy),
+    mnemonic!(subn, 0x8007, 0xF00F, VxVy),
+    mnemonic!(shl, 0x800E, 0xF00F, VxVy),
+    mnemonic!(skrne, 0x9000, 0xF00F, VxVy),
+    mnemonic!(loadi, 0xA000, 0xF000, Addr),
+    mnemonic!(jumpi, 0xB000, 0xF000, Addr),
+    mnemonic!(rand, 0xC000, 0xF000, VxKk),
+    mnemonic!(draw, 0xD000, 0xF000, VxVyN),
+    mnemonic!(skpr, 0xE09E, 0xF0FF, Vx),
+    mnemonic!(skup, 0xE0A1, 0xF0FF, Vx),
+    mnemonic!(loadil, 0xF000, 0xFFFF, Long),
+    mnemonic!(plane, 0xF001, 0xF0FF, X),
+    mnemonic!(moved, 0xF007, 0xF0FF, Vx),
+    mnemonic!(keyd, 0xF00A, 0xF0FF, Vx),
+    mnemonic!(loads, 0xF015, 0xF0FF, Vx),
+    mnemonic!(ld, 0xF018, 0xF0FF, Vx),
+    mnemonic!(addi, 0xF01E, 0xF0FF, Vx),
+    mnemonic!(ldspr, 0xF029, 0xF0FF, Vx),
+    mnemonic!(ldhspr, 0xF030, 0xF0FF, Vx),
+    mnemonic!(bcd, 0xF033, 0xF0FF, Vx),
+    mnemonic!(stor, 0xF055, 0xF0FF, Vx),
+    mnemonic!(read, 0xF065, 0xF0FF, Vx),
+    mnemonic!(srpl, 0xF075, 0xF0FF, Vx),
+    mnemonic!(lrpl, 0xF085, 0xF0FF, Vx),
+];
+
+/// Finds the Mnemonic of the instruction an opcode encodes, if it encodes one
+///
+/// # Arguments
+/// * `op` the opcode to decode
+pub fn decode(op: u16) -> Option<&'static Mnemonic> {
+    MNEMONICS
+        .iter()
+        .find(|mnemonic| op & mnemonic.mask == mnemonic.pattern)
+}
+
+/// Finds the Mnemonic of an instruction by name
+///
+/// # Arguments
+/// * `name` the name of the instruction
+pub fn lookup(name: &str) -> Option<&'static Mnemonic> {
+    MNEMONICS.iter().find(|mnemonic| mnemonic.name == name)
+}
+
+/// Disassembles the instruction at an address in memory, e.g. `ske v1, 0x20`
+/// Returns None if the bytes there don't encode an instruction
+///
+/// # Arguments
+/// * `memory` the memory containing the instruction
+/// * `address` where the instruction starts
+pub fn disassemble(memory: &[u8], address: u16) -> Option<String> {
+    let word = |offset: u16| {
+        let byte =
+            |i: u16| u16::from(memory[address.wrapping_add(offset + i) as usize % memory.len()]);
+        byte(0) << 8 | byte(1)
+    };
+    let op = word(0);
+    decode(op).map(|mnemonic| mnemonic.format(op, word(2)))
+}
+
+impl Mnemonic {
+    /// The number of bytes the instruction takes up
+    pub fn size(&self) -> u16 {
+        match self.operands {
+            Operands::Long => 0x4,
+            _ => 0x2,
+        }
+    }
+
+    /// Writes the instruction an opcode encodes in assembly
+    ///
+    /// # Arguments
+    /// * `op` an opcode encoding this instruction
+    /// * `next` the word following the opcode, which is only used by `Long` operands
+    pub fn format(&self, op: u16, next: u16) -> String {
+        let operands = match self.operands {
+            Operands::None => return self.name.to_string(),
+            Operands::N => format!("{}", op.n()),
+            Operands::X => format!("{}", op.x()),
+            Operands::Addr => format!("{:#05x}", op.addr()),
+            Operands::Long => format!("{:#06x}", next),
+            Operands::Vx => format!("v{:x}", op.x()),
+            Operands::VxKk => format!("v{:x}, {:#04x}", op.x(), op.kk()),
+            Operands::VxVy => format!("v{:x}, v{:x}", op.x(), op.y()),
+            Operands::VxVyN => format!("v{:x}, v{:x}, {}", op.x(), op.y(), op.n()),
+        };
+        format!("{} {}", self.name, operands)
+    }
+}
+
+#[cfg(test)]
+mod test_mnemonic {
+    use super::*;
+    use crate::instruction::from_op;
+
+    #[test]
+    fn test_mnemonics_match_from_op() {
+        for op in 0..=u16::MAX {
+            let decoded = decode(op).map(|mnemonic| mnemonic.instruction as usize);
+            let executed = from_op(&op).ok().map(|instruction| instruction as usize);
+            assert_eq!(decoded, executed, "{:#06X}", op);
+        }
+    }
+
+    #[test]
+    fn test_disassembles_operands() {
+        let memory = [
+            0x00, 0xE0, 0x3A, 0x20, 0xD1, 0x25, 0xF0, 0x00, 0x12, 0x34, 0x00, 0xC4, 0xFF, 0xFF,
+        ];
+        let lines: Vec<Option<String>> = [0x0, 0x2, 0x4, 0x6, 0xA, 0xC]
+            .iter()
+            .map(|&address| disassemble(&memory, address))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                Some("clr".to_string()),
+                Some("ske va, 0x20".to_string()),
+                Some("draw v1, v2, 5".to_string()),
+                Some("loadil 0x1234".to_string()),
+                Some("scrd 4".to_string()),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lookup_by_name() {
+        assert_eq!(lookup("call").unwrap().pattern, 0x2000);
+        assert_eq!(lookup("loadil").unwrap().size(), 4);
+        assert!(lookup("nop").is_none());
+    }
+}
@@ -1,395 +1,1008 @@
-use crate::constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use std::ops::Range;
+
+use crate::access::{Accesses, Location, Location::*};
+use crate::constants::{
+    BIG_SPRITE_SHEET_ADDRESS, HIRES_DISPLAY_HEIGHT, HIRES_DISPLAY_WIDTH, MEMORY_SIZE, PLANES,
+};
+use crate::error::ErrorKind;
 use crate::opcode::Opcode;
-use crate::state::State;
+use crate::state::{FrameBuffer, Resolution, State};
 
 /// clear
-pub fn clr(_op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
-    State {
+/// Only clears the selected planes
+pub fn clr(
+    _op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    let mut frame_buffer = state.frame_buffer;
+    frame_buffer
+        .iter_mut()
+        .flat_map(|row| row.iter_mut())
+        .for_each(|pixel| *pixel &= !state.plane);
+    let state = State {
         pc: state.pc + 0x2,
-        frame_buffer: [[0; DISPLAY_WIDTH]; DISPLAY_HEIGHT],
+        frame_buffer,
         draw_flag: true,
         ..*state
+    };
+    Ok((state, Accesses::default()))
+}
+
+/// scroll_up(n)
+/// Scrolls the display up by n pixels
+pub fn scru(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    let n = op.n() as usize;
+    let height = state.resolution.height();
+    Ok(scroll(state, |frame_buffer, x, y| {
+        if y + n < height {
+            frame_buffer[y + n][x]
+        } else {
+            0
+        }
+    }))
+}
+
+/// scroll_down(n)
+/// Scrolls the display down by n pixels
+pub fn scrd(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    let n = op.n() as usize;
+    Ok(scroll(state, |frame_buffer, x, y| {
+        if y >= n {
+            frame_buffer[y - n][x]
+        } else {
+            0
+        }
+    }))
+}
+
+/// scroll_right(4)
+/// Scrolls the display right by 4 pixels
+pub fn scrr(
+    _op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    Ok(scroll(state, |frame_buffer, x, y| {
+        if x >= 4 {
+            frame_buffer[y][x - 4]
+        } else {
+            0
+        }
+    }))
+}
+
+/// scroll_left(4)
+/// Scrolls the display left by 4 pixels
+pub fn scrl(
+    _op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    let width = state.resolution.width();
+    Ok(scroll(state, |frame_buffer, x, y| {
+        if x + 4 < width {
+            frame_buffer[y][x + 4]
+        } else {
+            0
+        }
+    }))
+}
+
+/// Rebuilds the visible portion of the selected planes by sampling the old FrameBuffer
+///
+/// # Arguments
+/// * `state` the state whose FrameBuffer should be scrolled
+/// * `source` gives the new value of the pixel at x, y from the old FrameBuffer
+fn scroll(state: &State, source: impl Fn(&FrameBuffer, usize, usize) -> u8) -> (State, Accesses) {
+    let mut frame_buffer = state.frame_buffer;
+    for (y, row) in frame_buffer
+        .iter_mut()
+        .enumerate()
+        .take(state.resolution.height())
+    {
+        for (x, pixel) in row.iter_mut().enumerate().take(state.resolution.width()) {
+            *pixel = (*pixel & !state.plane) | (source(&state.frame_buffer, x, y) & state.plane);
+        }
     }
+    let state = State {
+        pc: state.pc + 0x2,
+        frame_buffer,
+        draw_flag: true,
+        ..*state
+    };
+    (state, Accesses::default())
+}
+
+/// exit
+/// Stops the interpreter; the pc is left pointing at this instruction
+pub fn exit(
+    _op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    let state = State {
+        exited: true,
+        ..*state
+    };
+    Ok((state, Accesses::default()))
+}
+
+/// disable extended screen mode
+/// Switches to low resolution and clears every plane of the display
+pub fn extd(
+    _op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    let state = State {
+        pc: state.pc + 0x2,
+        resolution: Resolution::Low,
+        frame_buffer: [[0; HIRES_DISPLAY_WIDTH]; HIRES_DISPLAY_HEIGHT],
+        draw_flag: true,
+        ..*state
+    };
+    Ok((state, Accesses::default()))
+}
+
+/// enable extended screen mode
+/// Switches to high resolution and clears every plane of the display
+pub fn exte(
+    _op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    let state = State {
+        pc: state.pc + 0x2,
+        resolution: Resolution::High,
+        frame_buffer: [[0; HIRES_DISPLAY_WIDTH]; HIRES_DISPLAY_HEIGHT],
+        draw_flag: true,
+        ..*state
+    };
+    Ok((state, Accesses::default()))
 }
 
 /// PC = STACK.pop()
-pub fn rts(_op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
-    State {
-        pc: state.stack[state.sp as usize] + 0x2,
+/// Fails if the stack is empty
+pub fn rts(
+    _op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    if state.sp == 0x0 {
+        return Err(ErrorKind::StackUnderflow);
+    }
+    let state = State {
+        pc: state.stack[state.sp as usize].wrapping_add(0x2),
         sp: state.sp - 0x1,
         ..*state
-    }
+    };
+    Ok((state, Accesses::new(vec![Sp], vec![Sp])))
 }
 
 /// PC = addr
-pub fn jump(op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
-    State {
+pub fn jump(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    let state = State {
         pc: op.addr(),
         ..*state
-    }
+    };
+    Ok((state, Accesses::default()))
 }
 
 /// STACK.push(PC); PC = addr
-pub fn call(op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
+/// Fails if the stack is full
+pub fn call(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    if state.sp as usize + 0x1 >= state.stack.len() {
+        return Err(ErrorKind::StackOverflow);
+    }
     let mut sp = state.sp;
     sp += 0x1;
     let mut stack = state.stack;
     stack[sp as usize] = state.pc;
-    State {
+    let state = State {
         pc: op.addr(),
         sp,
         stack,
         ..*state
-    }
+    };
+    Ok((state, Accesses::new(vec![Sp], vec![Sp])))
 }
 
 /// if Vx == kk then pc += 2
-pub fn ske(op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
+pub fn ske(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
     let pc = if state.v[op.x() as usize] == op.kk() {
-        state.pc + 0x4
+        skip(state)
     } else {
         state.pc + 0x2
     };
-    State { pc, ..*state }
+    Ok((
+        State { pc, ..*state },
+        Accesses::new(vec![V(op.x())], vec![]),
+    ))
 }
 
 /// if Vx != kk then pc += 2
-pub fn skne(op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
+pub fn skne(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
     let pc = if state.v[op.x() as usize] != op.kk() {
-        state.pc + 0x4
+        skip(state)
     } else {
         state.pc + 0x2
     };
-    State { pc, ..*state }
+    Ok((
+        State { pc, ..*state },
+        Accesses::new(vec![V(op.x())], vec![]),
+    ))
+}
+
+/// mem[I..I+|x-y|] = Vx..Vy
+/// Fill memory starting at address i with Vx..Vy, which may be in descending order; I is untouched
+pub fn savr(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    let registers = register_range(op);
+    let range = memory_range(state.i, registers.len())?;
+    let mut memory = state.memory;
+    for (address, &register) in range.zip(&registers) {
+        memory[address] = state.v[register as usize];
+    }
+    let reads = [
+        vec![I],
+        registers.iter().map(|&register| V(register)).collect(),
+    ]
+    .concat();
+    let writes = vec![indexed_memory(state, registers.len())];
+    let state = State {
+        pc: state.pc + 0x2,
+        memory,
+        ..*state
+    };
+    Ok((state, Accesses::new(reads, writes)))
+}
+
+/// Vx..Vy = mem[I..I+|x-y|]
+/// Fill Vx..Vy, which may be in descending order, with memory starting at address i; I is untouched
+pub fn loadr(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    let registers = register_range(op);
+    let range = memory_range(state.i, registers.len())?;
+    let mut v = state.v;
+    for (address, &register) in range.zip(&registers) {
+        v[register as usize] = state.memory[address];
+    }
+    let reads = vec![I, indexed_memory(state, registers.len())];
+    let writes = registers.into_iter().map(V).collect();
+    let state = State {
+        pc: state.pc + 0x2,
+        v,
+        ..*state
+    };
+    Ok((state, Accesses::new(reads, writes)))
+}
+
+/// The registers Vx..Vy in the order they're accessed by `savr` and `loadr`
+fn register_range(op: &dyn Opcode) -> Vec<u8> {
+    let (x, y) = (op.x(), op.y());
+    if x <= y {
+        (x..=y).collect()
+    } else {
+        (y..=x).rev().collect()
+    }
+}
+
+/// The addresses of `len` bytes of memory starting at `address`
+/// Fails if any of them are past the end of memory
+fn memory_range(address: u16, len: usize) -> Result<Range<usize>, ErrorKind> {
+    let start = address as usize;
+    if start + len > MEMORY_SIZE {
+        Err(ErrorKind::MemoryOutOfRange { address, len })
+    } else {
+        Ok(start..start + len)
+    }
+}
+
+/// The location of `len` bytes of memory starting at I
+fn indexed_memory(state: &State, len: usize) -> Location {
+    Memory {
+        address: state.i,
+        len,
+    }
+}
+
+/// The registers V0..Vx, which `stor`, `read`, `srpl`, and `lrpl` access
+fn registers_through(op: &dyn Opcode) -> Vec<Location> {
+    (0x0..=op.x()).map(V).collect()
 }
 
 /// if Vx == Vy then pc += 2
-pub fn skre(op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
+pub fn skre(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
     let pc = if state.v[op.x() as usize] == state.v[op.y() as usize] {
-        state.pc + 0x4
+        skip(state)
     } else {
         state.pc + 0x2
     };
-    State { pc, ..*state }
+    let reads = vec![V(op.x()), V(op.y())];
+    Ok((State { pc, ..*state }, Accesses::new(reads, vec![])))
 }
 
 /// Vx = kk
-pub fn load(op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
+pub fn load(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
     let mut v = state.v;
     v[op.x() as usize] = op.kk();
-    State {
+    let state = State {
         pc: state.pc + 0x2,
         v,
         ..*state
-    }
+    };
+    Ok((state, Accesses::new(vec![], vec![V(op.x())])))
 }
 
 /// Vx += kk
 /// Add kk to Vx; allow for overflow but implicitly drop it
-pub fn add(op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
+pub fn add(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
     let (res, _) = state.v[op.x() as usize].overflowing_add(op.kk());
     let mut v = state.v;
     v[op.x() as usize] = res;
-    State {
+    let state = State {
         pc: state.pc + 0x2,
         v,
         ..*state
-    }
+    };
+    Ok((state, Accesses::new(vec![V(op.x())], vec![V(op.x())])))
 }
 
 /// Vx = Vy
-pub fn mv(op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
+pub fn mv(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
     let mut v = state.v;
     v[op.x() as usize] = v[op.y() as usize];
-    State {
+    let state = State {
         pc: state.pc + 0x2,
         v,
         ..*state
-    }
+    };
+    Ok((state, Accesses::new(vec![V(op.y())], vec![V(op.x())])))
 }
 
 /// Vx |= Vy
-pub fn or(op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
+/// VF = 0 if the `vf_reset` quirk is enabled
+pub fn or(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
     let mut v = state.v;
     v[op.x() as usize] |= v[op.y() as usize];
-    State {
-        pc: state.pc + 0x2,
-        v,
-        ..*state
-    }
+    Ok(logical(op, state, v))
 }
 
 /// Vx &= Vy
-pub fn and(op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
+/// VF = 0 if the `vf_reset` quirk is enabled
+pub fn and(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
     let mut v = state.v;
     v[op.x() as usize] &= v[op.y() as usize];
-    State {
-        pc: state.pc + 0x2,
-        v,
-        ..*state
-    }
+    Ok(logical(op, state, v))
 }
 
 /// Vx ^= Vy
-pub fn xor(op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
+/// VF = 0 if the `vf_reset` quirk is enabled
+pub fn xor(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
     let mut v = state.v;
     v[op.x() as usize] ^= v[op.y() as usize];
-    State {
+    Ok(logical(op, state, v))
+}
+
+/// Finishes `or`, `and`, and `xor`, resetting VF if the `vf_reset` quirk is enabled
+///
+/// # Arguments
+/// * `op` the logical operation's opcode
+/// * `state` the state the operation was executed against
+/// * `v` the registers with the operation's result in Vx
+fn logical(op: &dyn Opcode, state: &State, mut v: [u8; 16]) -> (State, Accesses) {
+    let mut writes = vec![V(op.x())];
+    if state.quirks.vf_reset {
+        v[0xF] = 0x0;
+        writes.push(V(0xF));
+    }
+    let state = State {
         pc: state.pc + 0x2,
         v,
         ..*state
-    }
+    };
+    (state, Accesses::new(vec![V(op.x()), V(op.y())], writes))
 }
 
 /// Vx += Vy; VF = overflow
-pub fn addr(op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
+pub fn addr(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
     let (res, over) = state.v[op.x() as usize].overflowing_add(state.v[op.y() as usize]);
     let mut v = state.v;
     v[0xF] = if over { 0x1 } else { 0x0 };
     v[op.x() as usize] = res;
-    State {
-        pc: state.pc + 0x2,
-        v,
-        ..*state
-    }
+    Ok(arithmetic(op, state, v))
 }
 
 /// Vx -= Vy; VF = !underflow
-pub fn sub(op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
+pub fn sub(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
     let (res, under) = state.v[op.x() as usize].overflowing_sub(state.v[op.y() as usize]);
     let mut v = state.v;
     v[0xF] = if under { 0x0 } else { 0x1 };
     v[op.x() as usize] = res;
-    State {
-        pc: state.pc + 0x2,
-        v,
-        ..*state
-    }
+    Ok(arithmetic(op, state, v))
 }
 
 /// Vx /= 2; VF = underflow
-pub fn shr(op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
+/// Shifts Vy into Vx instead if the `shift_vy` quirk is enabled
+pub fn shr(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    let source = shift_source(op, state);
+    let value = state.v[source as usize];
     let mut v = state.v;
-    v[0xF] = v[op.x() as usize] & 0x1;
-    v[op.x() as usize] /= 0x2;
-    State {
-        pc: state.pc + 0x2,
-        v,
-        ..*state
-    }
+    v[0xF] = value & 0x1;
+    v[op.x() as usize] = value / 0x2;
+    Ok(shift(op, state, v, source))
 }
 
 /// Vx -= Vy; VF = underflow
-pub fn subn(op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
+pub fn subn(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
     let (res, under) = state.v[op.y() as usize].overflowing_sub(state.v[op.x() as usize]);
     let mut v = state.v;
     v[0xF] = if under { 0x0 } else { 0x1 };
     v[op.x() as usize] = res;
-    State {
+    Ok(arithmetic(op, state, v))
+}
+
+/// Finishes `addr`, `sub`, and `subn`, which read Vx and Vy and write Vx and VF
+///
+/// # Arguments
+/// * `op` the arithmetic operation's opcode
+/// * `state` the state the operation was executed against
+/// * `v` the registers with the operation's result in Vx and VF
+fn arithmetic(op: &dyn Opcode, state: &State, v: [u8; 16]) -> (State, Accesses) {
+    let state = State {
         pc: state.pc + 0x2,
         v,
         ..*state
-    }
+    };
+    let reads = vec![V(op.x()), V(op.y())];
+    (state, Accesses::new(reads, vec![V(op.x()), V(0xF)]))
 }
 
 /// Vx *= 2; VF = overflow
-pub fn shl(op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
-    let (res, over) = state.v[op.x() as usize].overflowing_mul(2);
+/// Shifts Vy into Vx instead if the `shift_vy` quirk is enabled
+pub fn shl(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    let source = shift_source(op, state);
+    let (res, over) = state.v[source as usize].overflowing_mul(2);
     let mut v = state.v;
     v[0xF] = if over { 0x1 } else { 0x0 };
     v[op.x() as usize] = res;
-    State {
+    Ok(shift(op, state, v, source))
+}
+
+/// The register shifted by `shr` and `shl`
+fn shift_source(op: &dyn Opcode, state: &State) -> u8 {
+    if state.quirks.shift_vy {
+        op.y()
+    } else {
+        op.x()
+    }
+}
+
+/// Finishes `shr` and `shl`, which read the shifted register and write Vx and VF
+///
+/// # Arguments
+/// * `op` the shift's opcode
+/// * `state` the state the shift was executed against
+/// * `v` the registers with the shifted value in Vx and VF
+/// * `source` the register that was shifted
+fn shift(op: &dyn Opcode, state: &State, v: [u8; 16], source: u8) -> (State, Accesses) {
+    let state = State {
         pc: state.pc + 0x2,
         v,
         ..*state
-    }
+    };
+    (
+        state,
+        Accesses::new(vec![V(source)], vec![V(op.x()), V(0xF)]),
+    )
 }
 
 /// if Vx != Vy then pc +=2
-pub fn skrne(op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
+pub fn skrne(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
     let pc = if state.v[op.x() as usize] != state.v[op.y() as usize] {
-        state.pc + 0x4
+        skip(state)
     } else {
         state.pc + 0x2
     };
-    State { pc, ..*state }
+    let reads = vec![V(op.x()), V(op.y())];
+    Ok((State { pc, ..*state }, Accesses::new(reads, vec![])))
+}
+
+/// The pc after a skip instruction skips
+/// Skipping steps over the whole next instruction, which is 4 bytes if it's the XO-CHIP long load
+fn skip(state: &State) -> u16 {
+    let next = state.pc.wrapping_add(0x2);
+    if state.memory[next as usize] == 0xF0 && state.memory[next.wrapping_add(0x1) as usize] == 0x00
+    {
+        state.pc.wrapping_add(0x6)
+    } else {
+        state.pc.wrapping_add(0x4)
+    }
 }
 
 /// I = addr
-pub fn loadi(op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
-    State {
+pub fn loadi(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    let state = State {
         pc: state.pc + 0x2,
         i: op.addr(),
         ..*state
-    }
+    };
+    Ok((state, Accesses::new(vec![], vec![I])))
 }
 
 /// PC = V0 + addr
-pub fn jumpi(op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
-    State {
-        pc: u16::from(state.v[0x0]) + op.addr(),
+/// Jumps relative to Vx instead if the `jump_vx` quirk is enabled
+pub fn jumpi(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    let register = if state.quirks.jump_vx { op.x() } else { 0x0 };
+    let state = State {
+        pc: u16::from(state.v[register as usize]) + op.addr(),
         ..*state
-    }
+    };
+    Ok((state, Accesses::new(vec![V(register)], vec![])))
 }
 
-/// Vx = rand_byte + kk
-pub fn rand(op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
-    let rand_byte: u8 = rand::random();
+/// Vx = rand_byte & kk
+/// The random byte is drawn from the State's deterministic generator
+pub fn rand(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    let (rand_byte, rng) = state.rng.next_byte();
     let mut v = state.v;
     v[op.x() as usize] = rand_byte & op.kk();
-    State {
+    let state = State {
         pc: state.pc + 0x2,
         v,
+        rng,
         ..*state
-    }
+    };
+    Ok((state, Accesses::new(vec![], vec![V(op.x())])))
 }
 
 /// draw_sprite(x=Vx y=Vy size=n)
-/// XORs a sprite from memory i..n at position x, y on the FrameBuffer with wrapping.
+/// XORs a sprite from memory i..n at position x, y on the FrameBuffer.
+/// A size of 0 draws a 16x16 sprite from memory i..32 instead.
+/// Each selected plane is drawn in turn with its own sprite, read from memory following the last.
+/// Sprites wrap around the edges of the screen unless the `clip_sprites` quirk is enabled.
 /// Sets VF if any pixels would be erased
-pub fn draw(op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
+pub fn draw(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
     let mut v = state.v;
     let mut frame_buffer = state.frame_buffer;
 
     // Reset the carry flag (used for collision detection)
     v[0xF] = 0x0;
 
-    for byte in 0..op.n() as usize {
-        let y = (state.v[op.y() as usize] as usize + byte) % DISPLAY_HEIGHT;
-        for bit in 0..8 {
-            let x = (state.v[op.x() as usize] as usize + bit) % DISPLAY_WIDTH;
-            let pixel_value = (state.memory[state.i as usize + byte] >> (7 - bit) as u8) & 1;
-            v[0xF] |= pixel_value & state.frame_buffer[y as usize][x as usize];
-            frame_buffer[y as usize][x as usize] ^= pixel_value;
+    let width = state.resolution.width();
+    let height = state.resolution.height();
+    let (rows, bytes_per_row) = if op.n() == 0 {
+        (16, 2)
+    } else {
+        (op.n() as usize, 1)
+    };
+
+    // The sprite's origin always wraps, only the pixels past the edges are clipped
+    let origin_x = state.v[op.x() as usize] as usize % width;
+    let origin_y = state.v[op.y() as usize] as usize % height;
+    let clip = state.quirks.clip_sprites;
+
+    let planes = (0..PLANES)
+        .filter(|plane| state.plane & (1 << plane) != 0)
+        .count();
+    let sprites_len = planes * rows * bytes_per_row;
+    let mut sprite = memory_range(state.i, sprites_len)?.start;
+    for mask in (0..PLANES).map(|plane| 1 << plane) {
+        if state.plane & mask == 0 {
+            continue;
+        }
+        for row in 0..rows {
+            if clip && origin_y + row >= height {
+                break;
+            }
+            let y = (origin_y + row) % height;
+            for bit in 0..bytes_per_row * 8 {
+                if clip && origin_x + bit >= width {
+                    break;
+                }
+                let x = (origin_x + bit) % width;
+                let byte = state.memory[sprite + row * bytes_per_row + bit / 8];
+                if (byte >> (7 - bit % 8) as u8) & 1 == 1 {
+                    if state.frame_buffer[y][x] & mask != 0 {
+                        v[0xF] = 0x1;
+                    }
+                    frame_buffer[y][x] ^= mask;
+                }
+            }
         }
+        sprite += rows * bytes_per_row;
     }
 
-    State {
+    let reads = vec![V(op.x()), V(op.y()), I, indexed_memory(state, sprites_len)];
+    let state = State {
         pc: state.pc + 0x2,
         draw_flag: true,
         v,
         frame_buffer,
         ..*state
-    }
+    };
+    Ok((state, Accesses::new(reads, vec![V(0xF)])))
 }
 
 /// if Vx.pressed then pc += 2
-pub fn skpr(op: &dyn Opcode, state: &State, pressed_keys: [u8; 16]) -> State {
-    let pc = if pressed_keys[state.v[op.x() as usize] as usize] == 0x1 {
-        state.pc + 0x4
+/// Only the low nibble of Vx selects a key, as there are just 16 of them
+pub fn skpr(
+    op: &dyn Opcode,
+    state: &State,
+    pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    let pc = if pressed_keys[(state.v[op.x() as usize] & 0xF) as usize] == 0x1 {
+        skip(state)
     } else {
         state.pc + 0x2
     };
-    State { pc, ..*state }
+    Ok((
+        State { pc, ..*state },
+        Accesses::new(vec![V(op.x())], vec![]),
+    ))
 }
 
 /// if !Vx.pressed then pc += 2
-pub fn skup(op: &dyn Opcode, state: &State, pressed_keys: [u8; 16]) -> State {
-    let pc = if pressed_keys[state.v[op.x() as usize] as usize] == 0x0 {
-        state.pc + 0x4
+/// Only the low nibble of Vx selects a key, as there are just 16 of them
+pub fn skup(
+    op: &dyn Opcode,
+    state: &State,
+    pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    let pc = if pressed_keys[(state.v[op.x() as usize] & 0xF) as usize] == 0x0 {
+        skip(state)
     } else {
         state.pc + 0x2
     };
-    State { pc, ..*state }
+    Ok((
+        State { pc, ..*state },
+        Accesses::new(vec![V(op.x())], vec![]),
+    ))
+}
+
+/// I = next_word
+/// Loads I with the 16-bit address that makes up the second half of this 4 byte instruction
+pub fn loadil(
+    _op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    let address = state.pc.wrapping_add(0x2);
+    let high = state.memory[address as usize];
+    let low = state.memory[state.pc.wrapping_add(0x3) as usize];
+    let reads = vec![Memory { address, len: 2 }];
+    let state = State {
+        pc: state.pc.wrapping_add(0x4),
+        i: u16::from(high) << 8 | u16::from(low),
+        ..*state
+    };
+    Ok((state, Accesses::new(reads, vec![I])))
+}
+
+/// plane(x)
+/// Selects the planes drawn to by `clr`, `draw`, and the scroll instructions as a bitmask
+pub fn plane(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    let state = State {
+        pc: state.pc + 0x2,
+        plane: op.x() & 0x3,
+        ..*state
+    };
+    Ok((state, Accesses::default()))
 }
 
 /// Vx = DT
-pub fn moved(op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
+pub fn moved(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
     let mut v = state.v;
     v[op.x() as usize] = state.delay_timer;
-    State {
+    let state = State {
         pc: state.pc + 0x2,
         v,
         ..*state
-    }
+    };
+    Ok((state, Accesses::new(vec![DelayTimer], vec![V(op.x())])))
 }
 
 /// await keypress for Vx
-pub fn keyd(op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
-    State {
+/// Vx is reported as written by this instruction even though it's filled in once a key is pressed
+pub fn keyd(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    let state = State {
         pc: state.pc + 0x2,
         register_needing_key: Some(op.x()),
         ..*state
-    }
+    };
+    Ok((state, Accesses::new(vec![], vec![V(op.x())])))
 }
 
 /// DT = Vx
-pub fn loads(op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
-    State {
+pub fn loads(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    let state = State {
         pc: state.pc + 0x2,
         delay_timer: state.v[op.x() as usize],
         ..*state
-    }
+    };
+    Ok((state, Accesses::new(vec![V(op.x())], vec![DelayTimer])))
 }
 
 /// ST = Vx
-pub fn ld(op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
-    State {
+pub fn ld(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    let state = State {
         pc: state.pc + 0x2,
         sound_timer: state.v[op.x() as usize],
         ..*state
-    }
+    };
+    Ok((state, Accesses::new(vec![V(op.x())], vec![SoundTimer])))
 }
 
 /// I += Vx
-pub fn addi(op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
-    State {
+pub fn addi(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    let state = State {
         pc: state.pc + 0x2,
-        i: state.i + u16::from(state.v[op.x() as usize]),
+        i: state.i.wrapping_add(u16::from(state.v[op.x() as usize])),
         ..*state
-    }
+    };
+    Ok((state, Accesses::new(vec![V(op.x()), I], vec![I])))
 }
 
 /// I = Vx * 5
 /// Set I to the memory address of the sprite for Vx
 /// See sprites::SPRITE_SHEET for more details
-pub fn ldspr(op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
-    State {
+pub fn ldspr(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    let state = State {
         pc: state.pc + 0x2,
         i: u16::from(state.v[op.x() as usize]) * 5,
         ..*state
-    }
+    };
+    Ok((state, Accesses::new(vec![V(op.x())], vec![I])))
+}
+
+/// I = BIG_SPRITE_SHEET_ADDRESS + Vx * 10
+/// Set I to the memory address of the large sprite for Vx
+/// See constants::BIG_SPRITE_SHEET for more details
+pub fn ldhspr(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    let state = State {
+        pc: state.pc + 0x2,
+        i: BIG_SPRITE_SHEET_ADDRESS + u16::from(state.v[op.x() as usize] & 0xF) * 10,
+        ..*state
+    };
+    Ok((state, Accesses::new(vec![V(op.x())], vec![I])))
 }
 
 /// mem[I..I+3] = bcd(Vx)
 /// Store BCD repr of Vx in memory starting at address i
-pub fn bcd(op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
+pub fn bcd(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
     let bcd = [
         (state.v[op.x() as usize] / 100 % 10),
         (state.v[op.x() as usize] / 10 % 10),
         (state.v[op.x() as usize] % 10),
     ];
     let mut memory = state.memory;
-    memory[state.i as usize..(state.i + 0x3) as usize].copy_from_slice(&bcd);
-    State {
+    memory[memory_range(state.i, 0x3)?].copy_from_slice(&bcd);
+    let writes = vec![indexed_memory(state, 0x3)];
+    let state = State {
         pc: state.pc + 0x2,
         memory,
         ..*state
-    }
+    };
+    Ok((state, Accesses::new(vec![V(op.x()), I], writes)))
 }
 
 /// mem[I..I+x] = V0..Vx
 /// Fill memory starting at address i with V0..Vx+1
-pub fn stor(op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
+/// I is then advanced according to the `memory_increment` quirk
+pub fn stor(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    let len = op.x() as usize + 0x1;
     let mut memory = state.memory;
-    memory[state.i as usize..=(state.i + u16::from(op.x())) as usize]
-        .copy_from_slice(&state.v[0x0 as usize..=op.x() as usize]);
-    State {
+    memory[memory_range(state.i, len)?].copy_from_slice(&state.v[0x0..len]);
+    let increment = state.quirks.memory_increment.amount(op.x());
+    let reads = [vec![I], registers_through(op)].concat();
+    let mut writes = vec![indexed_memory(state, len)];
+    if increment != 0 {
+        writes.push(I);
+    }
+    let state = State {
         pc: state.pc + 0x2,
+        i: state.i.wrapping_add(increment),
         memory,
         ..*state
-    }
+    };
+    Ok((state, Accesses::new(reads, writes)))
 }
 
 /// V0..Vx = mem[I..I+x]
 /// Fill V0..Vx+1 with memory starting at address i
-pub fn read(op: &dyn Opcode, state: &State, _pressed_keys: [u8; 16]) -> State {
+/// I is then advanced according to the `memory_increment` quirk
+pub fn read(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    let len = op.x() as usize + 0x1;
     let mut v = state.v;
-    v[0x0 as usize..=op.x() as usize]
-        .copy_from_slice(&state.memory[state.i as usize..=(state.i + u16::from(op.x())) as usize]);
-    State {
+    v[0x0..len].copy_from_slice(&state.memory[memory_range(state.i, len)?]);
+    let increment = state.quirks.memory_increment.amount(op.x());
+    let reads = vec![I, indexed_memory(state, len)];
+    let mut writes = registers_through(op);
+    if increment != 0 {
+        writes.push(I);
+    }
+    let state = State {
         pc: state.pc + 0x2,
+        i: state.i.wrapping_add(increment),
         v,
         ..*state
-    }
+    };
+    Ok((state, Accesses::new(reads, writes)))
+}
+
+/// RPL[0..x] = V0..Vx
+/// Save V0..Vx+1 to the SUPER-CHIP user flags
+pub fn srpl(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    let mut rpl = state.rpl;
+    rpl[0x0..=op.x() as usize].copy_from_slice(&state.v[0x0..=op.x() as usize]);
+    let state = State {
+        pc: state.pc + 0x2,
+        rpl,
+        ..*state
+    };
+    Ok((state, Accesses::new(registers_through(op), vec![])))
+}
+
+/// V0..Vx = RPL[0..x]
+/// Restore V0..Vx+1 from the SUPER-CHIP user flags
+pub fn lrpl(
+    op: &dyn Opcode,
+    state: &State,
+    _pressed_keys: [u8; 16],
+) -> Result<(State, Accesses), ErrorKind> {
+    let mut v = state.v;
+    v[0x0..=op.x() as usize].copy_from_slice(&state.rpl[0x0..=op.x() as usize]);
+    let state = State {
+        pc: state.pc + 0x2,
+        v,
+        ..*state
+    };
+    Ok((state, Accesses::new(vec![], registers_through(op))))
 }
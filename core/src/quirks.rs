@@ -0,0 +1,209 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// # Quirks
+/// The Chip-8 was never formally specified and its descendants disagree on how several opcodes behave.
+/// Each field toggles one of these contested behaviors; every operation consults them via `State.quirks`.
+///
+/// The default reproduces this interpreter's original behavior:
+/// - `8xy6`/`8xyE` shift Vx in place
+/// - `Fx55`/`Fx65` leave I untouched
+/// - `Bnnn` jumps relative to V0
+/// - `8xy1`/`8xy2`/`8xy3` leave VF untouched
+/// - `Dxyn` wraps sprites around the edges of the screen
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Quirks {
+    /// `8xy6`/`8xyE` shift Vy and store the result in Vx rather than shifting Vx in place
+    pub shift_vy: bool,
+    /// How far `Fx55`/`Fx65` advance I after accessing memory
+    pub memory_increment: MemoryIncrement,
+    /// `Bnnn` is read as `Bxnn` and jumps to Vx + nnn rather than V0 + nnn
+    pub jump_vx: bool,
+    /// `8xy1`/`8xy2`/`8xy3` reset VF to 0
+    pub vf_reset: bool,
+    /// `Dxyn` clips sprites at the edges of the screen rather than wrapping them
+    pub clip_sprites: bool,
+}
+
+/// The number of bytes Quirks are serialized into
+pub const QUIRKS_SIZE: usize = 5;
+
+impl Quirks {
+    /// Serializes the Quirks into `QUIRKS_SIZE` bytes
+    pub fn to_bytes(&self) -> [u8; QUIRKS_SIZE] {
+        [
+            self.shift_vy as u8,
+            self.memory_increment as u8,
+            self.jump_vx as u8,
+            self.vf_reset as u8,
+            self.clip_sprites as u8,
+        ]
+    }
+
+    /// Deserializes Quirks from the bytes produced by `to_bytes`
+    /// Returns None if the bytes aren't valid Quirks
+    ///
+    /// # Arguments
+    /// * `bytes` serialized Quirks
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let bool = |byte: u8| match byte {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        };
+        match *bytes {
+            [shift_vy, memory_increment, jump_vx, vf_reset, clip_sprites] => Some(Quirks {
+                shift_vy: bool(shift_vy)?,
+                memory_increment: match memory_increment {
+                    0 => MemoryIncrement::None,
+                    1 => MemoryIncrement::X,
+                    2 => MemoryIncrement::XPlusOne,
+                    _ => return None,
+                },
+                jump_vx: bool(jump_vx)?,
+                vf_reset: bool(vf_reset)?,
+                clip_sprites: bool(clip_sprites)?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// How I is left after `Fx55`/`Fx65`
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum MemoryIncrement {
+    /// I is left untouched
+    #[default]
+    None,
+    /// I += x
+    X,
+    /// I += x + 1
+    XPlusOne,
+}
+
+impl MemoryIncrement {
+    /// The amount I should be advanced by after accessing V0..Vx
+    ///
+    /// # Arguments
+    /// * `x` the index of the last register accessed
+    pub fn amount(self, x: u8) -> u16 {
+        match self {
+            MemoryIncrement::None => 0,
+            MemoryIncrement::X => u16::from(x),
+            MemoryIncrement::XPlusOne => u16::from(x) + 1,
+        }
+    }
+}
+
+/// # Profile
+/// Named sets of quirks matching well known interpreters.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Profile {
+    /// The original interpreter for the RCA COSMAC VIP
+    CosmacVip,
+    /// The CHIP-48 interpreter for the HP-48 calculators
+    Chip48,
+    /// SUPER-CHIP 1.1, also for the HP-48 calculators
+    SuperChip,
+    /// Modern interpreters such as Octo
+    Modern,
+}
+
+impl Profile {
+    pub fn quirks(self) -> Quirks {
+        match self {
+            Profile::CosmacVip => Quirks {
+                shift_vy: true,
+                memory_increment: MemoryIncrement::XPlusOne,
+                jump_vx: false,
+                vf_reset: true,
+                clip_sprites: true,
+            },
+            Profile::Chip48 => Quirks {
+                shift_vy: false,
+                memory_increment: MemoryIncrement::X,
+                jump_vx: true,
+                vf_reset: false,
+                clip_sprites: true,
+            },
+            Profile::SuperChip => Quirks {
+                shift_vy: false,
+                memory_increment: MemoryIncrement::None,
+                jump_vx: true,
+                vf_reset: false,
+                clip_sprites: true,
+            },
+            Profile::Modern => Quirks {
+                shift_vy: true,
+                memory_increment: MemoryIncrement::XPlusOne,
+                jump_vx: false,
+                vf_reset: false,
+                clip_sprites: false,
+            },
+        }
+    }
+}
+
+impl From<Profile> for Quirks {
+    fn from(profile: Profile) -> Self {
+        profile.quirks()
+    }
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "vip" | "cosmac-vip" => Ok(Profile::CosmacVip),
+            "chip48" | "chip-48" => Ok(Profile::Chip48),
+            "schip" | "super-chip" => Ok(Profile::SuperChip),
+            "modern" => Ok(Profile::Modern),
+            other => Err(format!(
+                "unknown quirk profile {:?}; expected one of vip, chip48, schip, modern",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Profile::CosmacVip => "vip",
+            Profile::Chip48 => "chip48",
+            Profile::SuperChip => "schip",
+            Profile::Modern => "modern",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod test_quirks {
+    use super::*;
+
+    #[test]
+    fn test_profile_round_trips_through_its_name() {
+        for profile in [
+            Profile::CosmacVip,
+            Profile::Chip48,
+            Profile::SuperChip,
+            Profile::Modern,
+        ] {
+            assert_eq!(profile.to_string().parse::<Profile>(), Ok(profile));
+        }
+    }
+
+    #[test]
+    fn test_unknown_profile_is_rejected() {
+        assert!("vintage".parse::<Profile>().is_err());
+    }
+
+    #[test]
+    fn test_memory_increment_amount() {
+        assert_eq!(MemoryIncrement::None.amount(0x4), 0x0);
+        assert_eq!(MemoryIncrement::X.amount(0x4), 0x4);
+        assert_eq!(MemoryIncrement::XPlusOne.amount(0x4), 0x5);
+    }
+}
@@ -0,0 +1,90 @@
+/// # Rng
+/// A small deterministic pseudorandom number generator (xorshift64*).
+///
+/// It's stored in the State so that random numbers are reproducible:
+/// rewinding, restoring a save state, or replaying input all yield the same sequence.
+/// Like the rest of the State it's immutable; drawing a number returns the next generator.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl Rng {
+    /// Creates a generator from a seed
+    /// The seed is scrambled first since xorshift generators can't have an all-zero state
+    ///
+    /// # Arguments
+    /// * `seed` any 64-bit value
+    pub fn new(seed: u64) -> Self {
+        // splitmix64 finalizer
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        Rng {
+            state: if z == 0 { 0x9E37_79B9_7F4A_7C15 } else { z },
+        }
+    }
+
+    /// Returns the generator's internal state, e.g. for serializing it
+    pub fn to_bits(self) -> u64 {
+        self.state
+    }
+
+    /// Restores a generator from the internal state returned by `to_bits`
+    /// Returns None for states that `to_bits` can't produce
+    ///
+    /// # Arguments
+    /// * `bits` the internal state of a generator
+    pub fn from_bits(bits: u64) -> Option<Self> {
+        if bits == 0 {
+            None
+        } else {
+            Some(Rng { state: bits })
+        }
+    }
+
+    /// Returns a random byte along with the generator that should be used next
+    pub fn next_byte(self) -> (u8, Rng) {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        let byte = (x.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8;
+        (byte, Rng { state: x })
+    }
+}
+
+#[cfg(test)]
+mod test_rng {
+    use super::*;
+
+    #[test]
+    fn test_same_seed_same_sequence() {
+        let (a, rng_a) = Rng::new(42).next_byte();
+        let (b, rng_b) = Rng::new(42).next_byte();
+        assert_eq!(a, b);
+        assert_eq!(rng_a.next_byte().0, rng_b.next_byte().0);
+    }
+
+    #[test]
+    fn test_different_seeds_diverge() {
+        let sequence = |seed| {
+            let mut rng = Rng::new(seed);
+            (0..8)
+                .map(|_| {
+                    let (byte, next) = rng.next_byte();
+                    rng = next;
+                    byte
+                })
+                .collect::<Vec<u8>>()
+        };
+        assert_ne!(sequence(1), sequence(2));
+    }
+}
@@ -1,4 +1,9 @@
-use crate::constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH, SPRITE_SHEET};
+use crate::constants::{
+    BIG_SPRITE_SHEET, BIG_SPRITE_SHEET_ADDRESS, DISPLAY_HEIGHT, DISPLAY_WIDTH,
+    HIRES_DISPLAY_HEIGHT, HIRES_DISPLAY_WIDTH, MEMORY_SIZE, ROM_ADDRESS, RPL_FLAGS, SPRITE_SHEET,
+};
+use crate::quirks::{Quirks, QUIRKS_SIZE};
+use crate::rng::Rng;
 
 /// # State
 /// A snapshot of the Chip8 internal state
@@ -19,18 +24,26 @@ use crate::constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH, SPRITE_SHEET};
 /// Timers
 /// - `delay_timer` & `sound_timer` 8-bit timers that decrement at 60Hz
 ///     - each time the sound timer is decremented a beep sound is generated
-/// - `delay_counter` how many more CPU cycles should pass before the timers are decremented
+/// - `cycles` how many CPU cycles have elapsed, which determines when a frame ends and the timers tick
 ///
 /// ## Memory
 /// - `stack` a 32 byte stack
 ///     - stores return addresses when subroutines are called
 ///     - different sources cite the as being anywhere from 32-64 bytes
-/// - `memory` 4096 bytes of addressable memory
-/// - `frame_buffer` 32x64 bytes of vram
+/// - `memory` 64K bytes of addressable memory
+///     - the original Chip-8 only had 4K, XO-CHIP extends this
+/// - `frame_buffer` 64x128 bytes of vram, of which only the top left 32x64 are used in low resolution
+///     - each pixel is a bitmask of the XO-CHIP planes it's set in
+/// - `plane` a bitmask of the XO-CHIP planes that drawing, clearing, and scrolling affect
+/// - `rpl` 16 bytes of SUPER-CHIP user flags that registers can be saved to and restored from
 ///
 /// ## Other
+/// - `resolution` whether the display is in low (64x32) or SUPER-CHIP high (128x64) resolution
+/// - `exited` set once the SUPER-CHIP exit instruction has been executed
 /// - `draw_flag` tracks frame buffer updates since the last draw to prevent unnecessary redraws
 /// - `register_needing_key` if a register is awaiting a keypress its index is stored here
+/// - `quirks` which interpretation of ambiguous opcodes to follow
+/// - `rng` the generator that random numbers are drawn from
 #[derive(Copy, Clone)]
 pub struct State {
     pub v: [u8; 16],
@@ -39,12 +52,18 @@ pub struct State {
     pub sp: u8,
     pub delay_timer: u8,
     pub sound_timer: u8,
-    pub delay_counter: u8,
+    pub cycles: u64,
     pub stack: [u16; 16],
-    pub memory: [u8; 4096],
+    pub memory: [u8; MEMORY_SIZE],
     pub frame_buffer: FrameBuffer,
+    pub plane: u8,
+    pub rpl: [u8; RPL_FLAGS],
+    pub resolution: Resolution,
+    pub exited: bool,
     pub draw_flag: bool,
     pub register_needing_key: Option<u8>,
+    pub quirks: Quirks,
+    pub rng: Rng,
 }
 
 impl Default for State {
@@ -55,12 +74,23 @@ impl Default for State {
 
 impl State {
     pub fn new() -> Self {
-        // 0x000 - 0x080 is reserved for a sprite sheet
-        let mut memory = [0; 4096];
+        Self::with_quirks(Quirks::default())
+    }
+
+    /// Creates a new State whose operations follow the given quirks
+    ///
+    /// # Arguments
+    /// * `quirks` which interpretation of ambiguous opcodes to follow
+    pub fn with_quirks(quirks: Quirks) -> Self {
+        // 0x000 - 0x050 is reserved for a sprite sheet and 0x050 - 0x0F0 for a large one
+        let mut memory = [0; MEMORY_SIZE];
         memory[0..80].copy_from_slice(&SPRITE_SHEET);
+        let big_sprites = BIG_SPRITE_SHEET_ADDRESS as usize;
+        memory[big_sprites..big_sprites + BIG_SPRITE_SHEET.len()]
+            .copy_from_slice(&BIG_SPRITE_SHEET);
 
         // 0x200 is where ROMs are loaded into memory
-        let pc: u16 = 0x200;
+        let pc: u16 = ROM_ADDRESS;
 
         State {
             v: [0; 16],
@@ -69,20 +99,240 @@ impl State {
             sp: 0,
             delay_timer: 0,
             sound_timer: 0,
-            delay_counter: 0,
+            cycles: 0,
             stack: [0; 16],
             memory,
-            frame_buffer: [[0; DISPLAY_WIDTH]; DISPLAY_HEIGHT],
+            frame_buffer: [[0; HIRES_DISPLAY_WIDTH]; HIRES_DISPLAY_HEIGHT],
+            plane: 0x1,
+            rpl: [0; RPL_FLAGS],
+            resolution: Resolution::Low,
+            exited: false,
             draw_flag: false,
             register_needing_key: None,
+            quirks,
+            rng: Rng::default(),
+        }
+    }
+
+    /// Serializes the State into `STATE_SIZE` bytes
+    /// Every field is stored at a fixed offset so that the bytes of similar States can be diffed
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(STATE_SIZE);
+        bytes.extend_from_slice(&self.v);
+        bytes.extend_from_slice(&self.i.to_be_bytes());
+        bytes.extend_from_slice(&self.pc.to_be_bytes());
+        bytes.extend_from_slice(&[self.sp, self.delay_timer, self.sound_timer]);
+        bytes.extend_from_slice(&self.cycles.to_be_bytes());
+        self.stack
+            .iter()
+            .for_each(|address| bytes.extend_from_slice(&address.to_be_bytes()));
+        bytes.extend_from_slice(&self.memory);
+        self.frame_buffer
+            .iter()
+            .for_each(|row| bytes.extend_from_slice(row));
+        bytes.push(self.plane);
+        bytes.extend_from_slice(&self.rpl);
+        bytes.extend_from_slice(&[
+            self.resolution as u8,
+            self.exited as u8,
+            self.draw_flag as u8,
+            self.register_needing_key.unwrap_or(NO_REGISTER),
+        ]);
+        bytes.extend_from_slice(&self.quirks.to_bytes());
+        bytes.extend_from_slice(&self.rng.to_bits().to_be_bytes());
+        bytes
+    }
+
+    /// Deserializes a State from the bytes produced by `to_bytes`
+    /// Returns None if the bytes aren't a valid State
+    ///
+    /// # Arguments
+    /// * `bytes` a serialized State
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != STATE_SIZE {
+            return None;
+        }
+        let mut reader = Reader { bytes };
+        let mut state = State::new();
+        state.v.copy_from_slice(reader.take(16));
+        state.i = reader.u16();
+        state.pc = reader.u16();
+        state.sp = reader.u8();
+        state.delay_timer = reader.u8();
+        state.sound_timer = reader.u8();
+        state.cycles = reader.u64();
+        for address in state.stack.iter_mut() {
+            *address = reader.u16();
+        }
+        state.memory.copy_from_slice(reader.take(MEMORY_SIZE));
+        for row in state.frame_buffer.iter_mut() {
+            row.copy_from_slice(reader.take(HIRES_DISPLAY_WIDTH));
+        }
+        state.plane = reader.u8();
+        state.rpl.copy_from_slice(reader.take(RPL_FLAGS));
+        state.resolution = match reader.u8() {
+            0 => Resolution::Low,
+            1 => Resolution::High,
+            _ => return None,
+        };
+        state.exited = reader.bool()?;
+        state.draw_flag = reader.bool()?;
+        state.register_needing_key = match reader.u8() {
+            NO_REGISTER => None,
+            register if register < 16 => Some(register),
+            _ => return None,
+        };
+        state.quirks = Quirks::from_bytes(reader.take(QUIRKS_SIZE))?;
+        state.rng = Rng::from_bits(reader.u64())?;
+        if usize::from(state.sp) >= state.stack.len()
+            // Calls only push addresses that have a whole instruction after them
+            || state.stack.iter().any(|&address| address as usize + 0x2 >= MEMORY_SIZE)
+            || state.plane > 0x3
+        {
+            return None;
+        }
+        Some(state)
+    }
+}
+
+/// The number of bytes a State is serialized into
+pub const STATE_SIZE: usize = 16 // v
+    + 2 + 2 + 3 // i, pc, sp & timers
+    + 8 // cycles
+    + 2 * 16 // stack
+    + MEMORY_SIZE
+    + HIRES_DISPLAY_WIDTH * HIRES_DISPLAY_HEIGHT // frame_buffer
+    + 1 + RPL_FLAGS // plane, rpl
+    + 4 // resolution, exited, draw_flag, register_needing_key
+    + QUIRKS_SIZE
+    + 8; // rng
+
+/// How `register_needing_key` is serialized when no register needs a key
+const NO_REGISTER: u8 = 0xFF;
+
+/// Reads the fields of a serialized State in order
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> &'a [u8] {
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        taken
+    }
+
+    fn u8(&mut self) -> u8 {
+        self.take(1)[0]
+    }
+
+    fn u16(&mut self) -> u16 {
+        u16::from_be_bytes([self.u8(), self.u8()])
+    }
+
+    fn u64(&mut self) -> u64 {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8));
+        u64::from_be_bytes(bytes)
+    }
+
+    fn bool(&mut self) -> Option<bool> {
+        match self.u8() {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
         }
     }
 }
 
 /// # Frame Buffer
 /// Represents the contents of the Chip-8 vram indexed as [y][x].
+/// It's always large enough for high resolution; low resolution only uses its top left corner.
 ///
 /// Used for:
 /// - storing a frame to be drawn to the display
 /// - comparisons against the currently drawn sprites for detecting collisions
-pub type FrameBuffer = [[u8; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+pub type FrameBuffer = [[u8; HIRES_DISPLAY_WIDTH]; HIRES_DISPLAY_HEIGHT];
+
+/// # Resolution
+/// Which portion of the FrameBuffer is in use.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Resolution {
+    /// The original 64x32 display
+    Low,
+    /// The SUPER-CHIP 128x64 display
+    High,
+}
+
+impl Resolution {
+    /// The number of pixels in each row of the display
+    pub fn width(self) -> usize {
+        match self {
+            Resolution::Low => DISPLAY_WIDTH,
+            Resolution::High => HIRES_DISPLAY_WIDTH,
+        }
+    }
+
+    /// The number of rows of pixels in the display
+    pub fn height(self) -> usize {
+        match self {
+            Resolution::Low => DISPLAY_HEIGHT,
+            Resolution::High => HIRES_DISPLAY_HEIGHT,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_state {
+    use super::*;
+    use crate::quirks::Profile;
+
+    #[test]
+    fn test_state_round_trips_through_bytes() {
+        let mut state = State::with_quirks(Profile::SuperChip.into());
+        state.v[0xF] = 0x1;
+        state.i = 0x1234;
+        state.pc = 0xFFFE;
+        state.sp = 0x2;
+        state.stack[0x2] = 0x0ABC;
+        state.cycles = 1 << 40;
+        state.memory[MEMORY_SIZE - 1] = 0xAA;
+        state.frame_buffer[63][127] = 0x3;
+        state.resolution = Resolution::High;
+        state.register_needing_key = Some(0xE);
+        state.rng = Rng::new(99);
+
+        let bytes = state.to_bytes();
+        assert_eq!(bytes.len(), STATE_SIZE);
+        let restored = State::from_bytes(&bytes).unwrap();
+        assert_eq!(restored.to_bytes(), bytes);
+        assert_eq!(restored.quirks, state.quirks);
+        assert_eq!(restored.rng, state.rng);
+    }
+
+    #[test]
+    fn test_state_rejects_invalid_bytes() {
+        let bytes = State::new().to_bytes();
+        assert!(State::from_bytes(&bytes[1..]).is_none());
+        let mut bytes = bytes;
+        // resolution
+        bytes[STATE_SIZE - 17] = 0x2;
+        assert!(State::from_bytes(&bytes).is_none());
+    }
+
+    #[test]
+    fn test_state_rejects_out_of_range_stack_and_plane() {
+        let stack = 16 + 2 + 2 + 3 + 8;
+        let plane = stack + 2 * 16 + MEMORY_SIZE + HIRES_DISPLAY_WIDTH * HIRES_DISPLAY_HEIGHT;
+        let valid = State::new().to_bytes();
+        assert!(State::from_bytes(&valid).is_some());
+
+        let mut bytes = valid.clone();
+        bytes[stack..stack + 2].copy_from_slice(&0xFFFEu16.to_be_bytes());
+        assert!(State::from_bytes(&bytes).is_none());
+
+        let mut bytes = valid;
+        bytes[plane] = 0x4;
+        assert!(State::from_bytes(&bytes).is_none());
+    }
+}
@@ -0,0 +1,269 @@
+use std::fs;
+use std::io::Error;
+use std::path::Path;
+
+use core::constants::FRAME_RATE;
+use core::state::{FrameBuffer, Resolution};
+
+use crate::palette::Palette;
+use crate::screenshot::{scaled, scaled_size};
+
+/// How long each GIF frame is shown for at the least, in hundredths of a second.
+/// Viewers show frames with shorter delays for much longer, so 60Hz frames are sampled at this interval.
+const TICK: u64 = 2;
+
+/// # Gif
+/// How gameplay is recorded as an animated GIF.
+///
+/// - `palette` the colors pixels are drawn with
+/// - `scale` how many image pixels wide and tall each Chip-8 pixel is
+/// - `trim` whether frames that stay the same at the start and end are cut down to a single frame
+/// - `max_bytes` the largest the GIF may be; frames past the limit are left out
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Gif {
+    pub palette: Palette,
+    pub scale: usize,
+    pub trim: bool,
+    pub max_bytes: Option<usize>,
+}
+
+impl Default for Gif {
+    fn default() -> Self {
+        Gif {
+            palette: Palette::default(),
+            scale: 2,
+            trim: false,
+            max_bytes: None,
+        }
+    }
+}
+
+/// A frame that was shown for a number of consecutive 60Hz frames
+struct Run {
+    frame: Box<FrameBuffer>,
+    resolution: Resolution,
+    frames: u64,
+}
+
+/// # GifRecorder
+/// Collects a frame every 60Hz frame and encodes them as an animated GIF.
+///
+/// Frames that don't change are merged as they're captured, so a recording only grows while the
+/// picture changes. Delays are rounded to `TICK` hundredths of a second without the rounding
+/// building up, so the GIF lasts as long as the gameplay did.
+pub struct GifRecorder {
+    gif: Gif,
+    runs: Vec<Run>,
+}
+
+impl GifRecorder {
+    /// # Arguments
+    /// * `gif` how the recording is encoded
+    pub fn new(gif: Gif) -> Self {
+        GifRecorder {
+            gif,
+            runs: Vec::new(),
+        }
+    }
+
+    /// Changes the colors the recording is encoded with, e.g. when the display's palette changes
+    ///
+    /// # Arguments
+    /// * `palette` the new colors
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.gif.palette = palette;
+    }
+
+    /// The number of 60Hz frames captured so far
+    pub fn frames(&self) -> u64 {
+        self.runs.iter().map(|run| run.frames).sum()
+    }
+
+    /// Adds the frame shown during a 60Hz frame to the recording
+    ///
+    /// # Arguments
+    /// * `frame` a Chip-8 FrameBuffer
+    /// * `resolution` the portion of the FrameBuffer in use
+    pub fn capture(&mut self, frame: &FrameBuffer, resolution: Resolution) {
+        match self.runs.last_mut() {
+            Some(run) if run.resolution == resolution && *run.frame == *frame => run.frames += 1,
+            _ => self.runs.push(Run {
+                frame: Box::new(*frame),
+                resolution,
+                frames: 1,
+            }),
+        }
+    }
+
+    /// Encodes the frames captured so far as an animated GIF that loops forever
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        // Low resolution frames are drawn twice as large if the recording switches to high resolution
+        let resolution = match self
+            .runs
+            .iter()
+            .any(|run| run.resolution == Resolution::High)
+        {
+            true => Resolution::High,
+            false => Resolution::Low,
+        };
+        let scale = self.gif.scale.max(1);
+        let (width, height) = scaled_size(resolution, scale)?;
+        let palette: Vec<u8> = self.gif.palette.colors.concat();
+        let mut encoder =
+            gif::Encoder::new(Vec::new(), width, height, &palette).map_err(Error::other)?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(Error::other)?;
+        for (run, ticks) in self.sampled() {
+            let scale = scale * resolution.width() / run.resolution.width();
+            let pixels: Vec<u8> = scaled(&run.frame, run.resolution, scale)
+                .into_iter()
+                .map(|pixel| pixel & 0x3)
+                .collect();
+            let mut frame = gif::Frame::from_indexed_pixels(width, height, pixels, None);
+            frame.delay = (ticks * TICK).min(u16::MAX as u64) as u16;
+            let written = encoder.get_ref().len();
+            encoder.write_frame(&frame).map_err(Error::other)?;
+            // Leave the frame out if it and the trailer don't fit
+            if let Some(max_bytes) = self.gif.max_bytes {
+                if encoder.get_ref().len() + 1 > max_bytes {
+                    encoder.get_mut().truncate(written);
+                    break;
+                }
+            }
+        }
+        encoder.into_inner()
+    }
+
+    /// Encodes the recording and writes it to a file, replacing it if it exists
+    ///
+    /// # Arguments
+    /// * `path` the file to write
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        fs::write(path, self.encode()?)
+    }
+
+    /// Samples the runs every `TICK` hundredths of a second, pairing each frame that's shown with
+    /// the number of ticks it's shown for
+    /// - runs too short to be sampled are left out
+    /// - with `trim`, the first and last runs are cut down to a single 60Hz frame
+    fn sampled(&self) -> Vec<(&Run, u64)> {
+        let last = self.runs.len().saturating_sub(1);
+        let frames = |i: usize, run: &Run| match self.gif.trim && (i == 0 || i == last) {
+            true => 1,
+            false => run.frames,
+        };
+        // The first tick at or after a number of 60Hz frames have passed
+        let tick = |frame: u64| (frame * 100).div_ceil(FRAME_RATE as u64 * TICK);
+        let mut sampled: Vec<(&Run, u64)> = Vec::new();
+        let mut start = 0;
+        for (i, run) in self.runs.iter().enumerate() {
+            let end = start + frames(i, run);
+            let ticks = tick(end) - tick(start);
+            start = end;
+            match sampled.last_mut() {
+                _ if ticks == 0 => (),
+                // A run that wasn't sampled may have separated two of the same frame
+                Some((previous, previous_ticks))
+                    if previous.resolution == run.resolution && previous.frame == run.frame =>
+                {
+                    *previous_ticks += ticks
+                }
+                _ => sampled.push((run, ticks)),
+            }
+        }
+        sampled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(pixel: u8) -> FrameBuffer {
+        let mut frame: FrameBuffer = [[0; 128]; 64];
+        frame[0][0] = pixel;
+        frame
+    }
+
+    fn delays(recorder: &GifRecorder) -> Vec<(u8, u16)> {
+        let gif = recorder.encode().unwrap();
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = options.read_info(&gif[..]).unwrap();
+        let mut frames = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            frames.push((frame.buffer[0], frame.delay));
+        }
+        frames
+    }
+
+    #[test]
+    fn test_delays_add_up_to_the_recording() {
+        let mut recorder = GifRecorder::new(Gif::default());
+        for _ in 0..5 {
+            recorder.capture(&frame(1), Resolution::Low);
+        }
+        // Too short to be sampled between two of the same frame
+        recorder.capture(&frame(2), Resolution::Low);
+        for _ in 0..54 {
+            recorder.capture(&frame(1), Resolution::Low);
+        }
+        recorder.capture(&frame(3), Resolution::Low);
+        recorder.capture(&frame(3), Resolution::Low);
+        recorder.capture(&frame(3), Resolution::Low);
+        assert_eq!(recorder.frames(), 63);
+        assert_eq!(delays(&recorder), [(1, 100), (3, 6)]);
+    }
+
+    #[test]
+    fn test_trims_idle_frames_at_the_start_and_end() {
+        let mut recorder = GifRecorder::new(Gif {
+            trim: true,
+            ..Gif::default()
+        });
+        for pixel in [0, 1, 2] {
+            for _ in 0..120 {
+                recorder.capture(&frame(pixel), Resolution::Low);
+            }
+        }
+        assert_eq!(delays(&recorder), [(0, 2), (1, 200), (2, 2)]);
+    }
+
+    #[test]
+    fn test_frames_past_the_size_limit_are_left_out() {
+        let mut recorder = GifRecorder::new(Gif::default());
+        for pixel in 0..4 {
+            recorder.capture(&frame(pixel), Resolution::Low);
+            recorder.capture(&frame(pixel), Resolution::Low);
+        }
+        let full = recorder.encode().unwrap().len();
+        recorder.gif.max_bytes = Some(full - 1);
+        let limited = recorder.encode().unwrap();
+        assert!(limited.len() < full);
+        assert_eq!(delays(&recorder).len(), 3);
+    }
+
+    #[test]
+    fn test_low_resolution_frames_are_scaled_to_high_resolution() {
+        let mut recorder = GifRecorder::new(Gif {
+            scale: 1,
+            ..Gif::default()
+        });
+        recorder.capture(&frame(1), Resolution::Low);
+        recorder.capture(&frame(1), Resolution::High);
+        let gif = recorder.encode().unwrap();
+        let decoder = gif::DecodeOptions::new().read_info(&gif[..]).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (128, 64));
+    }
+
+    #[test]
+    fn test_scales_too_large_for_a_gif_are_rejected() {
+        let mut recorder = GifRecorder::new(Gif {
+            scale: 1024,
+            ..Gif::default()
+        });
+        recorder.capture(&frame(1), Resolution::Low);
+        assert!(recorder.encode().is_err());
+    }
+}
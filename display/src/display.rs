@@ -1,18 +1,31 @@
-use sdl2::pixels::PixelFormatEnum;
+use std::io::Error;
 
-use core::constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
-use core::state::FrameBuffer;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::video::FullscreenType;
 
-const SCALE: usize = 10;
+use core::state::{FrameBuffer, Resolution};
+
+use crate::palette::Palette;
+use crate::phosphor::Persistence;
+use crate::renderer::{Renderer, Screen};
+use crate::window::{Scaling, Viewport, Window};
 
 /// # Display
 /// The Chip-8 display is composed of 64x32 pixels black/white pixels.
-/// The on/off state of these pixels is encoded as 1/0 respectively in a 2d array of 64x32 bits.
-/// The display only gets a call to `render` when the Chip-8 FrameBuffer is updated.
+/// The SUPER-CHIP can switch it to a high resolution mode of 128x64 pixels.
+/// The on/off state of these pixels is encoded as 1/0 respectively in a 2d array of 128x64 bits.
+/// XO-CHIP adds a second plane, so pixels are really a bitmask that selects one of four colors.
+/// The display is the SDL Renderer: it draws frames in a window when they're presented.
+///
+/// The window can be resized or made fullscreen, and frames are letterboxed to fit it.
+/// High resolution frames are drawn with pixels half as large so that the frame doesn't change
+/// size when the mode is switched.
 pub struct Display {
     canvas: sdl2::render::WindowCanvas,
-    width: usize,
-    height: usize,
+    screen: Screen,
+    scaling: Scaling,
+    windowed_size: (u32, u32),
 }
 
 // TODO handle errors better
@@ -21,91 +34,113 @@ impl Display {
     ///
     /// # Arguments
     /// * `sdl` an sdl2 context with which to draw
-    /// * `width` the horizontal size of the display measured in pixels
-    /// * `height` the vertical size of the display measured in pixels
-    /// * `scale` the size multiplier for each pixel
-    pub fn new(sdl: &sdl2::Sdl) -> Self {
+    /// * `palette` the colors to draw pixels with
+    /// * `window` the size the window opens with and how frames are fit into it
+    /// * `persistence` how long pixels stay visible after they're turned off, if at all
+    pub fn new(
+        sdl: &sdl2::Sdl,
+        palette: Palette,
+        window: &Window,
+        persistence: Option<Persistence>,
+    ) -> Self {
         let video_subsystem = sdl.video().unwrap();
-        let window = video_subsystem
-            .window(
-                "Emu-8",
-                (DISPLAY_WIDTH * SCALE) as u32,
-                (DISPLAY_HEIGHT * SCALE) as u32,
-            )
+        let sdl_window = video_subsystem
+            .window("Emu-8", window.size.0, window.size.1)
             .position_centered()
+            .resizable()
             .opengl()
             .build()
             .unwrap();
-        let canvas = window.into_canvas().build().unwrap();
+        let canvas = sdl_window.into_canvas().build().unwrap();
 
         Display {
             canvas,
-            width: DISPLAY_WIDTH,
-            height: DISPLAY_HEIGHT,
+            screen: Screen::new(palette, persistence),
+            scaling: window.scaling,
+            windowed_size: window.size,
         }
     }
 
-    /// Formats a Chip-8 FrameBuffer for rendering as an SDL2 texture.
-    ///
-    /// An SDL2 texture is a 1D array of ints that represent concatenated rows of RGB pixels.
-    ///
-    /// This creates a black and white rendering by:
-    /// - Flattening the 2D frame buffer into a 1D array by concatenating its rows
-    /// - Triplicating each element of that 1D array to represent the RGB values of each pixel
-    /// - Multiplying each value by 255 to convert from a binary state to 0-255 intensity
-    ///
-    /// # Arguments
-    /// * `frame` a Chip-8 FrameBuffer
-    fn frame_to_sdl_texture(frame: &FrameBuffer) -> Vec<u8> {
-        frame
-            .iter()
-            .flat_map(|a| a.iter())
-            .flat_map(|a| std::iter::repeat(a).take(3))
-            .map(|a| a * 255)
-            .collect()
+    /// The size of the window, or the size it was before it was made fullscreen
+    pub fn windowed_size(&self) -> (u32, u32) {
+        let window = self.canvas.window();
+        match window.fullscreen_state() {
+            FullscreenType::Off => window.size(),
+            _ => self.windowed_size,
+        }
     }
+}
 
-    /// Formats the Chip-8 FrameBuffer as an SDL2 RGB24 texture and renders it.
-    ///
-    /// # Arguments
-    /// * `frame` a Chip-8 FrameBuffer
-    pub fn render(&mut self, frame: &FrameBuffer) {
+impl Renderer for Display {
+    fn render(&mut self, frame: &FrameBuffer, resolution: Resolution) {
+        self.screen.update(frame, resolution);
+    }
+
+    fn set_palette(&mut self, palette: Palette) {
+        self.screen.set_palette(palette);
+    }
+
+    /// Formats the last frame as an SDL2 RGB24 texture and letterboxes it in the window.
+    fn present(&mut self) -> Result<(), Error> {
+        let resolution = self.screen.resolution();
+        let rgb = self.screen.to_rgb();
         let texture_creator = self.canvas.texture_creator();
 
         let mut texture = texture_creator
             .create_texture_streaming(
                 PixelFormatEnum::RGB24,
-                self.width as u32,
-                self.height as u32,
+                resolution.width() as u32,
+                resolution.height() as u32,
             )
-            .unwrap();
+            .map_err(Error::other)?;
 
         texture
             .with_lock(None, |buffer: &mut [u8], _pitch: usize| {
-                buffer.copy_from_slice(&Display::frame_to_sdl_texture(frame));
+                buffer.copy_from_slice(&rgb);
             })
-            .unwrap();
+            .map_err(Error::other)?;
 
-        self.canvas.copy(&texture, None, None).unwrap();
-        self.canvas.present()
+        // Letterbox the frame in black
+        let viewport = Viewport::fit(
+            self.canvas.output_size().map_err(Error::other)?,
+            self.scaling,
+        );
+        let viewport = Rect::new(
+            viewport.x as i32,
+            viewport.y as i32,
+            viewport.width,
+            viewport.height,
+        );
+        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        self.canvas.clear();
+        self.canvas
+            .copy(&texture, None, viewport)
+            .map_err(Error::other)?;
+        self.canvas.present();
+        Ok(())
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
-    #[test]
-    fn test_frame_to_sdl_texture() {
-        let mut frame: FrameBuffer = [[0; 64]; 32];
-        frame[0][0..2].copy_from_slice(&[0, 1]);
-        frame[1][0..2].copy_from_slice(&[1, 0]);
-        let frame = Display::frame_to_sdl_texture(&frame);
+    fn is_fading(&self) -> bool {
+        self.screen.is_fading()
+    }
 
-        let mut expected: Vec<u8> = vec![0; 6144];
-        expected[0..6].copy_from_slice(&[0, 0, 0, 255, 255, 255]);
-        expected[192..198].copy_from_slice(&[255, 255, 255, 0, 0, 0]);
+    fn set_title(&mut self, title: &str) -> Result<(), Error> {
+        self.canvas
+            .window_mut()
+            .set_title(title)
+            .map_err(Error::other)
+    }
 
-        assert_eq!(frame, expected);
+    /// Switches between a window and filling the screen
+    fn toggle_fullscreen(&mut self) {
+        let window = self.canvas.window_mut();
+        let fullscreen = match window.fullscreen_state() {
+            FullscreenType::Off => {
+                self.windowed_size = window.size();
+                FullscreenType::Desktop
+            }
+            _ => FullscreenType::Off,
+        };
+        window.set_fullscreen(fullscreen).unwrap();
     }
 }
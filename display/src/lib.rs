@@ -1,3 +1,21 @@
+pub use crate::animation::{Gif, GifRecorder};
+#[cfg(feature = "sdl")]
 pub use crate::display::Display;
+pub use crate::palette::{parse_color, Color, Palette, Theme};
+pub use crate::phosphor::{Persistence, Phosphor};
+pub use crate::renderer::{to_rgb, ImageRenderer, NullRenderer, Renderer};
+pub use crate::screenshot::{Format, Screenshot};
+#[cfg(feature = "terminal")]
+pub use crate::terminal::Terminal;
+pub use crate::terminal::{lines, Glyphs};
+pub use crate::window::{Scaling, Viewport, Window};
 
+mod animation;
+#[cfg(feature = "sdl")]
 mod display;
+mod palette;
+mod phosphor;
+mod renderer;
+mod screenshot;
+mod terminal;
+mod window;
@@ -0,0 +1,231 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// An RGB color
+pub type Color = [u8; 3];
+
+/// # Palette
+/// The colors that FrameBuffer pixels are drawn with.
+///
+/// Each pixel is a bitmask of the XO-CHIP planes it's set in, which indexes the palette:
+/// - `0` neither plane, the background
+/// - `1` only the first plane, the only color used by Chip-8 and SUPER-CHIP ROMs
+/// - `2` only the second plane
+/// - `3` both planes
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Palette {
+    pub colors: [Color; 4],
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Theme::Classic.palette()
+    }
+}
+
+impl Palette {
+    /// The color a pixel should be drawn with
+    ///
+    /// # Arguments
+    /// * `pixel` a FrameBuffer pixel
+    pub fn color(&self, pixel: u8) -> Color {
+        self.colors[(pixel & 0x3) as usize]
+    }
+}
+
+/// Parses the name of a theme (e.g. `amber`) or a comma separated list of up to 4 hex colors
+/// (e.g. `000000,#FFFFFF`). Colors are assigned in order and any that are omitted keep their default.
+impl FromStr for Palette {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let not_a_theme = match s.parse::<Theme>() {
+            Ok(theme) => return Ok(theme.palette()),
+            Err(e) => e,
+        };
+        let mut palette = Palette::default();
+        let colors: Vec<&str> = s.split(',').map(str::trim).collect();
+        if let [color] = colors[..] {
+            parse_color(color)
+                .map_err(|_| format!("{} or a color of the form RRGGBB", not_a_theme))?;
+        }
+        if colors.len() > palette.colors.len() {
+            return Err(format!(
+                "expected at most {} colors but got {}",
+                palette.colors.len(),
+                colors.len()
+            ));
+        }
+        for (slot, color) in palette.colors.iter_mut().zip(colors) {
+            *slot = parse_color(color)?;
+        }
+        Ok(palette)
+    }
+}
+
+/// # Theme
+/// Named palettes that imitate the screens Chip-8 games were played on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Theme {
+    /// White on black
+    Classic,
+    /// The green glow of a P1 phosphor monitor
+    GreenPhosphor,
+    /// The orange glow of a P3 phosphor monitor
+    Amber,
+    /// Dark green on the pale green of an unlit LCD
+    Lcd,
+    /// White, yellow, and cyan on black, for legibility
+    HighContrast,
+}
+
+impl Theme {
+    /// Every theme, in the order the theme hotkey cycles through them
+    pub const ALL: [Theme; 5] = [
+        Theme::Classic,
+        Theme::GreenPhosphor,
+        Theme::Amber,
+        Theme::Lcd,
+        Theme::HighContrast,
+    ];
+
+    /// The colors of the theme
+    pub fn palette(&self) -> Palette {
+        let colors = match self {
+            Theme::Classic => [
+                [0x00, 0x00, 0x00],
+                [0xFF, 0xFF, 0xFF],
+                [0xAA, 0xAA, 0xAA],
+                [0x55, 0x55, 0x55],
+            ],
+            Theme::GreenPhosphor => [
+                [0x0A, 0x14, 0x0A],
+                [0x33, 0xFF, 0x66],
+                [0x1A, 0x80, 0x33],
+                [0x99, 0xFF, 0xB3],
+            ],
+            Theme::Amber => [
+                [0x1A, 0x0F, 0x00],
+                [0xFF, 0xB0, 0x00],
+                [0x80, 0x58, 0x00],
+                [0xFF, 0xD7, 0x80],
+            ],
+            Theme::Lcd => [
+                [0x9B, 0xBC, 0x0F],
+                [0x0F, 0x38, 0x0F],
+                [0x8B, 0xAC, 0x0F],
+                [0x30, 0x62, 0x30],
+            ],
+            Theme::HighContrast => [
+                [0x00, 0x00, 0x00],
+                [0xFF, 0xFF, 0xFF],
+                [0xFF, 0xFF, 0x00],
+                [0x00, 0xFF, 0xFF],
+            ],
+        };
+        Palette { colors }
+    }
+
+    /// The theme after the one a palette has the colors of, or the first theme if it isn't one
+    ///
+    /// # Arguments
+    /// * `palette` the palette in use
+    pub fn after(palette: &Palette) -> Theme {
+        let next = Theme::ALL
+            .iter()
+            .position(|theme| theme.palette() == *palette)
+            .map_or(0, |i| i + 1);
+        Theme::ALL[next % Theme::ALL.len()]
+    }
+}
+
+impl fmt::Display for Theme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Theme::Classic => "classic",
+            Theme::GreenPhosphor => "green",
+            Theme::Amber => "amber",
+            Theme::Lcd => "lcd",
+            Theme::HighContrast => "high-contrast",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Theme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Theme::ALL
+            .iter()
+            .find(|theme| theme.to_string() == s)
+            .copied()
+            .ok_or_else(|| {
+                let names: Vec<String> = Theme::ALL.iter().map(Theme::to_string).collect();
+                format!("{:?} is not a theme ({})", s, names.join(", "))
+            })
+    }
+}
+
+/// Parses a single `RRGGBB` hex color, optionally prefixed with `#`
+pub fn parse_color(s: &str) -> Result<Color, String> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    let invalid = || format!("{:?} is not a color of the form RRGGBB", s);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return Err(invalid());
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+    Ok([channel(0)?, channel(2)?, channel(4)?])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_palette_color_uses_plane_bits() {
+        let palette = Palette::default();
+        assert_eq!(palette.color(0x0), [0x00, 0x00, 0x00]);
+        assert_eq!(palette.color(0x1), [0xFF, 0xFF, 0xFF]);
+        assert_eq!(palette.color(0x3), [0x55, 0x55, 0x55]);
+    }
+
+    #[test]
+    fn test_palette_parses_partial_list() {
+        let palette: Palette = "#102030, a0b0c0".parse().unwrap();
+        assert_eq!(palette.colors[0], [0x10, 0x20, 0x30]);
+        assert_eq!(palette.colors[1], [0xA0, 0xB0, 0xC0]);
+        assert_eq!(palette.colors[2], Palette::default().colors[2]);
+    }
+
+    #[test]
+    fn test_palette_parses_theme_names() {
+        let palette: Palette = "amber".parse().unwrap();
+        assert_eq!(palette, Theme::Amber.palette());
+        assert_eq!("high-contrast".parse(), Ok(Theme::HighContrast));
+        assert_eq!(
+            "sepia".parse::<Palette>(),
+            Err("\"sepia\" is not a theme (classic, green, amber, lcd, high-contrast) or a color of the form RRGGBB".to_string())
+        );
+    }
+
+    #[test]
+    fn test_theme_after_cycles_through_themes() {
+        assert_eq!(
+            Theme::after(&Theme::Classic.palette()),
+            Theme::GreenPhosphor
+        );
+        assert_eq!(Theme::after(&Theme::HighContrast.palette()), Theme::Classic);
+        let custom: Palette = "123456".parse().unwrap();
+        assert_eq!(Theme::after(&custom), Theme::Classic);
+    }
+
+    #[test]
+    fn test_palette_rejects_bad_colors() {
+        assert!("12345".parse::<Palette>().is_err());
+        assert!("GGGGGG".parse::<Palette>().is_err());
+        assert!("000000,000000,000000,000000,000000"
+            .parse::<Palette>()
+            .is_err());
+    }
+}
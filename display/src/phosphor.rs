@@ -0,0 +1,199 @@
+use std::str::FromStr;
+
+use core::state::{FrameBuffer, Resolution};
+
+use crate::palette::{Color, Palette};
+
+/// How many frames pixels fade over by default
+pub const FADE_FRAMES: u8 = 4;
+
+/// # Persistence
+/// How long pixels stay visible after they're turned off, hiding the flicker of sprites that are
+/// erased and redrawn every frame.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Persistence {
+    /// Pixels fade to the background over a number of frames, like the phosphor of a CRT
+    Fade(u8),
+    /// Pixels are shown if they're on in this frame or the one before
+    Blend,
+}
+
+/// Parses `blend`, `fade`, or `fade:N` to fade over N frames
+impl FromStr for Persistence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "blend" => Ok(Persistence::Blend),
+            None if s == "fade" => Ok(Persistence::Fade(FADE_FRAMES)),
+            Some(("fade", frames)) => match frames.parse() {
+                Ok(frames) if frames > 0 => Ok(Persistence::Fade(frames)),
+                _ => Err(format!(
+                    "{:?} is not a number of frames from 1 to 255",
+                    frames
+                )),
+            },
+            _ => Err(format!(
+                "{:?} is not a persistence (blend, fade, fade:N)",
+                s
+            )),
+        }
+    }
+}
+
+/// # Phosphor
+/// Blends each frame with the ones before it, following a Persistence.
+///
+/// Frames must be given to `update` once every 60Hz frame, whether they've changed or not, for
+/// pixels to fade at the right rate. The FrameBuffer itself is never changed, so emulation stays exact.
+///
+/// - `persistence` how pixels are blended
+/// - `resolution` the portion of the FrameBuffers in use
+/// - `frame` the latest frame
+/// - `previous` the frame before it
+/// - `glow` the last color each pixel was lit with and how brightly it still glows, from 0 to one
+///   more than the number of frames it fades over while it's lit
+pub struct Phosphor {
+    persistence: Persistence,
+    resolution: Resolution,
+    frame: Box<FrameBuffer>,
+    previous: Box<FrameBuffer>,
+    glow: Box<[[(u8, u16); 128]; 64]>,
+}
+
+impl Phosphor {
+    /// # Arguments
+    /// * `persistence` how pixels are blended
+    pub fn new(persistence: Persistence) -> Self {
+        Phosphor {
+            persistence,
+            resolution: Resolution::Low,
+            frame: Box::new([[0; 128]; 64]),
+            previous: Box::new([[0; 128]; 64]),
+            glow: Box::new([[(0, 0); 128]; 64]),
+        }
+    }
+
+    /// Adds the frame shown during a 60Hz frame; switching resolution clears what was glowing
+    ///
+    /// # Arguments
+    /// * `frame` a Chip-8 FrameBuffer
+    /// * `resolution` the portion of the FrameBuffer in use
+    pub fn update(&mut self, frame: &FrameBuffer, resolution: Resolution) {
+        if resolution != self.resolution {
+            *self = Phosphor {
+                resolution,
+                ..Phosphor::new(self.persistence)
+            };
+        }
+        *self.previous = *self.frame;
+        *self.frame = *frame;
+        let frames = match self.persistence {
+            Persistence::Fade(frames) => frames,
+            Persistence::Blend => return,
+        };
+        for (glow, &pixel) in self.glow.iter_mut().flatten().zip(frame.iter().flatten()) {
+            *glow = match pixel & 0x3 {
+                0 => (glow.0, glow.1.saturating_sub(1)),
+                pixel => (pixel, frames as u16 + 1),
+            };
+        }
+    }
+
+    /// Whether the blended frame will change even if the frames given to `update` don't
+    pub fn is_fading(&self) -> bool {
+        match self.persistence {
+            Persistence::Fade(_) => self
+                .glow
+                .iter()
+                .flatten()
+                .zip(self.frame.iter().flatten())
+                .any(|(&(_, level), &pixel)| pixel & 0x3 == 0 && level > 0),
+            Persistence::Blend => self.frame != self.previous,
+        }
+    }
+
+    /// The blended frame as concatenated rows of RGB pixels, cropped to the portion in use
+    ///
+    /// # Arguments
+    /// * `palette` the colors to draw pixels with
+    pub fn to_rgb(&self, palette: &Palette) -> Vec<u8> {
+        let (width, height) = (self.resolution.width(), self.resolution.height());
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .flat_map(|(x, y)| self.color(x, y, palette))
+            .collect()
+    }
+
+    /// The color of a blended pixel
+    fn color(&self, x: usize, y: usize, palette: &Palette) -> Color {
+        let pixel = self.frame[y][x];
+        match self.persistence {
+            Persistence::Blend => palette.color(pixel | self.previous[y][x]),
+            Persistence::Fade(_) if pixel & 0x3 != 0 => palette.color(pixel),
+            Persistence::Fade(frames) => {
+                // Pixels that turn off dim a step every frame
+                let (lit, level) = self.glow[y][x];
+                let (background, color) = (palette.color(0), palette.color(lit));
+                let mut blended = background;
+                for (channel, (&from, &to)) in blended.iter_mut().zip(background.iter().zip(&color))
+                {
+                    let (from, to) = (from as i32, to as i32);
+                    *channel = (from + (to - from) * level as i32 / (frames as i32 + 1)) as u8;
+                }
+                blended
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(pixel: u8) -> FrameBuffer {
+        let mut frame: FrameBuffer = [[0; 128]; 64];
+        frame[0][0] = pixel;
+        frame
+    }
+
+    #[test]
+    fn test_pixels_fade_over_frames() {
+        let mut phosphor = Phosphor::new(Persistence::Fade(3));
+        let palette: Palette = "000000,FFFFFF".parse().unwrap();
+        phosphor.update(&frame(1), Resolution::Low);
+        assert!(!phosphor.is_fading());
+        let mut levels = Vec::new();
+        for _ in 0..4 {
+            phosphor.update(&frame(0), Resolution::Low);
+            levels.push((phosphor.to_rgb(&palette)[0], phosphor.is_fading()));
+        }
+        assert_eq!(
+            levels,
+            [(0xBF, true), (0x7F, true), (0x3F, true), (0, false)]
+        );
+    }
+
+    #[test]
+    fn test_blend_shows_the_last_two_frames() {
+        let mut phosphor = Phosphor::new(Persistence::Blend);
+        let palette = Palette::default();
+        phosphor.update(&frame(1), Resolution::Low);
+        phosphor.update(&frame(2), Resolution::Low);
+        assert_eq!(phosphor.to_rgb(&palette)[0..3], palette.color(3));
+        assert!(phosphor.is_fading());
+        phosphor.update(&frame(2), Resolution::Low);
+        assert_eq!(phosphor.to_rgb(&palette)[0..3], palette.color(2));
+        assert!(!phosphor.is_fading());
+        assert_eq!(phosphor.to_rgb(&palette).len(), 64 * 32 * 3);
+    }
+
+    #[test]
+    fn test_parses_persistence() {
+        assert_eq!("blend".parse(), Ok(Persistence::Blend));
+        assert_eq!("fade".parse(), Ok(Persistence::Fade(FADE_FRAMES)));
+        assert_eq!("fade:8".parse(), Ok(Persistence::Fade(8)));
+        assert!("fade:0".parse::<Persistence>().is_err());
+        assert!("glow".parse::<Persistence>().is_err());
+    }
+}
@@ -0,0 +1,300 @@
+use std::io::Error;
+
+use core::state::{FrameBuffer, Resolution};
+
+use crate::palette::Palette;
+use crate::phosphor::{Persistence, Phosphor};
+
+/// # Renderer
+/// Something frames can be drawn to, so the emulator loop doesn't depend on how they're shown.
+///
+/// A renderer is given a frame with `render` whenever the Chip-8 FrameBuffer is updated, or every
+/// frame while pixels are fading if it's drawn with persistence, and shows it once `present` is called.
+/// `present` can also be called on its own to show the last frame again, e.g. after the palette changes.
+pub trait Renderer {
+    /// Takes a new frame to show the next time the renderer presents
+    ///
+    /// # Arguments
+    /// * `frame` a Chip-8 FrameBuffer
+    /// * `resolution` the portion of the FrameBuffer in use
+    fn render(&mut self, frame: &FrameBuffer, resolution: Resolution);
+
+    /// Changes the colors pixels are drawn with from the next time the renderer presents
+    ///
+    /// # Arguments
+    /// * `palette` the new colors
+    fn set_palette(&mut self, palette: Palette);
+
+    /// Shows the last frame that was rendered
+    fn present(&mut self) -> Result<(), Error>;
+
+    /// Whether pixels are still fading, so frames should be rendered even if they haven't changed
+    fn is_fading(&self) -> bool {
+        false
+    }
+
+    /// Shows a title alongside the frames, e.g. to report why execution halted
+    ///
+    /// # Arguments
+    /// * `title` the new title
+    fn set_title(&mut self, _title: &str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Switches between a window and filling the screen, for renderers that draw in a window
+    fn toggle_fullscreen(&mut self) {}
+}
+
+/// Formats the portion of a Chip-8 FrameBuffer in use as concatenated rows of RGB pixels.
+///
+/// This creates a colored rendering by:
+/// - Cropping the frame buffer to the portion used by the current resolution
+/// - Flattening the 2D frame buffer into a 1D array by concatenating its rows
+/// - Looking up the RGB values of each pixel's color in the palette
+///
+/// # Arguments
+/// * `frame` a Chip-8 FrameBuffer
+/// * `resolution` the portion of the FrameBuffer in use
+/// * `palette` the colors to draw pixels with
+pub fn to_rgb(frame: &FrameBuffer, resolution: Resolution, palette: &Palette) -> Vec<u8> {
+    frame
+        .iter()
+        .take(resolution.height())
+        .flat_map(|a| a.iter().take(resolution.width()))
+        .flat_map(|&a| palette.color(a))
+        .collect()
+}
+
+/// # Screen
+/// The last frame a renderer was given and the colors it's drawn in, blended with the frames
+/// before it if pixels persist.
+///
+/// - `palette` the colors to draw pixels with
+/// - `phosphor` the blended frames, if pixels persist
+/// - `frame` the last frame
+/// - `resolution` the portion of the frame in use
+pub(crate) struct Screen {
+    palette: Palette,
+    phosphor: Option<Phosphor>,
+    frame: Box<FrameBuffer>,
+    resolution: Resolution,
+}
+
+impl Screen {
+    /// # Arguments
+    /// * `palette` the colors to draw pixels with
+    /// * `persistence` how long pixels stay visible after they're turned off, if at all
+    pub fn new(palette: Palette, persistence: Option<Persistence>) -> Self {
+        Screen {
+            palette,
+            phosphor: persistence.map(Phosphor::new),
+            frame: Box::new([[0; 128]; 64]),
+            resolution: Resolution::Low,
+        }
+    }
+
+    /// Adds a new frame
+    ///
+    /// # Arguments
+    /// * `frame` a Chip-8 FrameBuffer
+    /// * `resolution` the portion of the FrameBuffer in use
+    pub fn update(&mut self, frame: &FrameBuffer, resolution: Resolution) {
+        if let Some(phosphor) = self.phosphor.as_mut() {
+            phosphor.update(frame, resolution);
+        }
+        *self.frame = *frame;
+        self.resolution = resolution;
+    }
+
+    /// The colors pixels are drawn with
+    #[cfg(feature = "terminal")]
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    /// Changes the colors pixels are drawn with
+    ///
+    /// # Arguments
+    /// * `palette` the new colors
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    /// The portion of the last frame in use
+    pub fn resolution(&self) -> Resolution {
+        self.resolution
+    }
+
+    /// Whether pixels are still fading
+    pub fn is_fading(&self) -> bool {
+        self.phosphor.as_ref().is_some_and(Phosphor::is_fading)
+    }
+
+    /// The last frame as concatenated rows of RGB pixels, cropped to the portion in use
+    pub fn to_rgb(&self) -> Vec<u8> {
+        match &self.phosphor {
+            Some(phosphor) => phosphor.to_rgb(&self.palette),
+            None => to_rgb(&self.frame, self.resolution, &self.palette),
+        }
+    }
+}
+
+/// # NullRenderer
+/// Draws frames nowhere, for running the emulator loop without any video.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct NullRenderer;
+
+impl Renderer for NullRenderer {
+    fn render(&mut self, _frame: &FrameBuffer, _resolution: Resolution) {}
+
+    fn set_palette(&mut self, _palette: Palette) {}
+
+    fn present(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// # ImageRenderer
+/// Draws frames into an RGB image in memory, pixel for pixel, e.g. to check what a frontend shows in tests.
+///
+/// - `screen` the last frame rendered and its colors
+/// - `image` the last frame presented, as concatenated rows of RGB pixels
+/// - `size` the width and height of the image in pixels
+/// - `title` the last title set
+pub struct ImageRenderer {
+    screen: Screen,
+    image: Vec<u8>,
+    size: (usize, usize),
+    title: String,
+}
+
+impl ImageRenderer {
+    /// # Arguments
+    /// * `palette` the colors to draw pixels with
+    /// * `persistence` how long pixels stay visible after they're turned off, if at all
+    pub fn new(palette: Palette, persistence: Option<Persistence>) -> Self {
+        ImageRenderer {
+            screen: Screen::new(palette, persistence),
+            image: Vec::new(),
+            size: (0, 0),
+            title: String::new(),
+        }
+    }
+
+    /// The last frame presented as concatenated rows of RGB pixels, or nothing if none has been
+    pub fn image(&self) -> &[u8] {
+        &self.image
+    }
+
+    /// The width and height of the last frame presented, in pixels
+    pub fn size(&self) -> (usize, usize) {
+        self.size
+    }
+
+    /// The color of a pixel in the last frame presented
+    ///
+    /// # Arguments
+    /// * `x` the pixel's column
+    /// * `y` the pixel's row
+    pub fn pixel(&self, x: usize, y: usize) -> Option<[u8; 3]> {
+        if x >= self.size.0 || y >= self.size.1 {
+            return None;
+        }
+        let i = (y * self.size.0 + x) * 3;
+        Some([self.image[i], self.image[i + 1], self.image[i + 2]])
+    }
+
+    /// The last title set
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+}
+
+impl Renderer for ImageRenderer {
+    fn render(&mut self, frame: &FrameBuffer, resolution: Resolution) {
+        self.screen.update(frame, resolution);
+    }
+
+    fn set_palette(&mut self, palette: Palette) {
+        self.screen.set_palette(palette);
+    }
+
+    fn present(&mut self) -> Result<(), Error> {
+        let resolution = self.screen.resolution();
+        self.image = self.screen.to_rgb();
+        self.size = (resolution.width(), resolution.height());
+        Ok(())
+    }
+
+    fn is_fading(&self) -> bool {
+        self.screen.is_fading()
+    }
+
+    fn set_title(&mut self, title: &str) -> Result<(), Error> {
+        title.clone_into(&mut self.title);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_rgb() {
+        let mut frame: FrameBuffer = [[0; 128]; 64];
+        frame[0][0..2].copy_from_slice(&[0, 1]);
+        frame[1][0..2].copy_from_slice(&[1, 0]);
+        // Outside of the low resolution display
+        frame[32][64] = 1;
+        let frame = to_rgb(&frame, Resolution::Low, &Palette::default());
+
+        let mut expected: Vec<u8> = vec![0; 6144];
+        expected[0..6].copy_from_slice(&[0, 0, 0, 255, 255, 255]);
+        expected[192..198].copy_from_slice(&[255, 255, 255, 0, 0, 0]);
+
+        assert_eq!(frame, expected);
+    }
+
+    #[test]
+    fn test_to_rgb_high_resolution() {
+        let mut frame: FrameBuffer = [[0; 128]; 64];
+        frame[1][127] = 1;
+        let frame = to_rgb(&frame, Resolution::High, &Palette::default());
+
+        let mut expected: Vec<u8> = vec![0; 24576];
+        expected[765..768].copy_from_slice(&[255, 255, 255]);
+
+        assert_eq!(frame, expected);
+    }
+
+    #[test]
+    fn test_to_rgb_planes() {
+        let mut frame: FrameBuffer = [[0; 128]; 64];
+        frame[0][0..4].copy_from_slice(&[0, 1, 2, 3]);
+        let palette: Palette = "000000,111111,222222,333333".parse().unwrap();
+        let frame = to_rgb(&frame, Resolution::Low, &palette);
+
+        assert_eq!(
+            frame[0..12],
+            [0x00, 0x00, 0x00, 0x11, 0x11, 0x11, 0x22, 0x22, 0x22, 0x33, 0x33, 0x33]
+        );
+    }
+
+    #[test]
+    fn test_image_renderer_shows_frames_once_presented() {
+        let mut renderer = ImageRenderer::new(Palette::default(), None);
+        let mut frame: FrameBuffer = [[0; 128]; 64];
+        frame[1][2] = 1;
+        renderer.render(&frame, Resolution::High);
+        assert_eq!(renderer.image(), []);
+        renderer.present().unwrap();
+        assert_eq!(renderer.size(), (128, 64));
+        assert_eq!(renderer.pixel(2, 1), Some([0xFF, 0xFF, 0xFF]));
+        assert_eq!(renderer.pixel(128, 0), None);
+        // Presenting again shows the same frame in the new colors
+        renderer.set_palette("000000,FF0000".parse().unwrap());
+        renderer.present().unwrap();
+        assert_eq!(renderer.pixel(2, 1), Some([0xFF, 0x00, 0x00]));
+    }
+}
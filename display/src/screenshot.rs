@@ -0,0 +1,257 @@
+use std::convert::TryFrom;
+use std::fs;
+use std::io::Error;
+use std::path::Path;
+use std::str::FromStr;
+
+use core::state::{FrameBuffer, Resolution};
+
+use crate::palette::Palette;
+
+/// # Format
+/// The image formats screenshots can be saved in.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// A compressed PNG in the palette's colors
+    Png,
+    /// An uncompressed binary PPM (`P6`) in the palette's colors
+    Ppm,
+    /// An uncompressed binary PBM (`P4`), which is black and white: lit pixels in any plane are black
+    Pbm,
+}
+
+impl Format {
+    /// The format a file should be saved in, going by its extension
+    ///
+    /// # Arguments
+    /// * `path` the file the screenshot will be saved to
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()?
+            .to_str()?
+            .to_ascii_lowercase()
+            .parse()
+            .ok()
+    }
+
+    /// The extension files in this format are saved with
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Png => "png",
+            Format::Ppm => "ppm",
+            Format::Pbm => "pbm",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "png" => Ok(Format::Png),
+            "ppm" => Ok(Format::Ppm),
+            "pbm" => Ok(Format::Pbm),
+            _ => Err(format!("{:?} is not an image format (png, ppm, pbm)", s)),
+        }
+    }
+}
+
+/// # Screenshot
+/// How frames are saved as images, pixel for pixel rather than as the window shows them.
+///
+/// - `palette` the colors pixels are drawn with
+/// - `scale` how many image pixels wide and tall each Chip-8 pixel is
+/// - `format` the image format
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Screenshot {
+    pub palette: Palette,
+    pub scale: usize,
+    pub format: Format,
+}
+
+impl Default for Screenshot {
+    fn default() -> Self {
+        Screenshot {
+            palette: Palette::default(),
+            scale: 1,
+            format: Format::Png,
+        }
+    }
+}
+
+impl Screenshot {
+    /// Encodes the portion of a FrameBuffer in use as an image
+    ///
+    /// # Arguments
+    /// * `frame` a Chip-8 FrameBuffer
+    /// * `resolution` the portion of the FrameBuffer in use
+    pub fn encode(&self, frame: &FrameBuffer, resolution: Resolution) -> Result<Vec<u8>, Error> {
+        let scale = self.scale.max(1);
+        let (width, height) = scaled_size(resolution, scale)?;
+        let pixels = scaled(frame, resolution, scale);
+        match self.format {
+            Format::Png => {
+                let rgb: Vec<u8> = pixels.iter().flat_map(|&p| self.palette.color(p)).collect();
+                let mut image = Vec::new();
+                let mut encoder = png::Encoder::new(&mut image, width.into(), height.into());
+                encoder.set_color(png::ColorType::Rgb);
+                encoder.set_depth(png::BitDepth::Eight);
+                let mut writer = encoder.write_header()?;
+                writer.write_image_data(&rgb)?;
+                writer.finish()?;
+                Ok(image)
+            }
+            Format::Ppm => {
+                let mut image = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+                image.extend(pixels.iter().flat_map(|&p| self.palette.color(p)));
+                Ok(image)
+            }
+            Format::Pbm => {
+                let mut image = format!("P4\n{} {}\n", width, height).into_bytes();
+                // Each row is packed 8 pixels to a byte, most significant bit first
+                for row in pixels.chunks(width.into()) {
+                    image.extend(row.chunks(8).map(|byte| {
+                        byte.iter()
+                            .enumerate()
+                            .filter(|(_, &p)| p & 0x3 != 0)
+                            .fold(0u8, |bits, (i, _)| bits | (0x80 >> i))
+                    }));
+                }
+                Ok(image)
+            }
+        }
+    }
+
+    /// Saves the portion of a FrameBuffer in use as an image, replacing the file if it exists
+    ///
+    /// # Arguments
+    /// * `frame` a Chip-8 FrameBuffer
+    /// * `resolution` the portion of the FrameBuffer in use
+    /// * `path` the file to write
+    pub fn save(
+        &self,
+        frame: &FrameBuffer,
+        resolution: Resolution,
+        path: &Path,
+    ) -> Result<(), Error> {
+        fs::write(path, self.encode(frame, resolution)?)
+    }
+}
+
+/// The width and height of the portion of a FrameBuffer in use once each pixel is scaled up
+/// Fails if either is too large for an image, which keeps a huge scale from exhausting memory
+///
+/// # Arguments
+/// * `resolution` the portion of the FrameBuffer in use
+/// * `scale` how many pixels wide and tall each pixel becomes
+pub(crate) fn scaled_size(resolution: Resolution, scale: usize) -> Result<(u16, u16), Error> {
+    let side = |pixels: usize| {
+        pixels
+            .checked_mul(scale)
+            .and_then(|side| u16::try_from(side).ok())
+            .ok_or_else(|| Error::other(format!("a scale of {} is too large", scale)))
+    };
+    Ok((side(resolution.width())?, side(resolution.height())?))
+}
+
+/// Crops a FrameBuffer to the portion in use and repeats each pixel into a square of `scale` pixels,
+/// returning the rows concatenated
+///
+/// # Arguments
+/// * `frame` a Chip-8 FrameBuffer
+/// * `resolution` the portion of the FrameBuffer in use
+/// * `scale` how many pixels wide and tall each pixel becomes
+pub(crate) fn scaled(frame: &FrameBuffer, resolution: Resolution, scale: usize) -> Vec<u8> {
+    frame
+        .iter()
+        .take(resolution.height())
+        .flat_map(|row| {
+            let row: Vec<u8> = row
+                .iter()
+                .take(resolution.width())
+                .flat_map(|&p| std::iter::repeat_n(p, scale))
+                .collect();
+            std::iter::repeat_n(row, scale).flatten()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame() -> FrameBuffer {
+        let mut frame: FrameBuffer = [[0; 128]; 64];
+        frame[0][0..3].copy_from_slice(&[1, 0, 2]);
+        frame
+    }
+
+    #[test]
+    fn test_ppm_scales_pixels_in_palette_colors() {
+        let screenshot = Screenshot {
+            palette: "000000,111111,222222".parse().unwrap(),
+            scale: 2,
+            format: Format::Ppm,
+        };
+        let image = screenshot.encode(&frame(), Resolution::Low).unwrap();
+        let header = b"P6\n128 64\n255\n";
+        assert_eq!(&image[..header.len()], header);
+        assert_eq!(image.len(), header.len() + 128 * 64 * 3);
+        // Both image rows of the first Chip-8 row
+        for row in 0..2 {
+            let start = header.len() + row * 128 * 3;
+            assert_eq!(
+                image[start..start + 18],
+                [
+                    0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x22,
+                    0x22, 0x22, 0x22, 0x22, 0x22
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_pbm_packs_lit_pixels_into_bits() {
+        let screenshot = Screenshot {
+            format: Format::Pbm,
+            ..Screenshot::default()
+        };
+        let image = screenshot.encode(&frame(), Resolution::Low).unwrap();
+        let header = b"P4\n64 32\n";
+        assert_eq!(&image[..header.len()], header);
+        assert_eq!(image.len(), header.len() + 8 * 32);
+        assert_eq!(image[header.len()..header.len() + 2], [0b1010_0000, 0]);
+    }
+
+    #[test]
+    fn test_png_decodes_to_the_frame() {
+        let image = Screenshot::default()
+            .encode(&frame(), Resolution::High)
+            .unwrap();
+        let mut reader = png::Decoder::new(&image[..]).read_info().unwrap();
+        let mut rgb = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut rgb).unwrap();
+        assert_eq!((info.width, info.height), (128, 64));
+        assert_eq!(
+            rgb[0..9],
+            [0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0xAA, 0xAA, 0xAA]
+        );
+    }
+
+    #[test]
+    fn test_scales_too_large_for_an_image_are_rejected() {
+        let screenshot = Screenshot {
+            scale: 1024,
+            ..Screenshot::default()
+        };
+        assert!(screenshot.encode(&frame(), Resolution::Low).is_err());
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(Format::from_path(Path::new("a/b.PNG")), Some(Format::Png));
+        assert_eq!(Format::from_path(Path::new("b.pbm")), Some(Format::Pbm));
+        assert_eq!(Format::from_path(Path::new("b.gif")), None);
+        assert_eq!(Format::from_path(Path::new("b")), None);
+    }
+}
@@ -0,0 +1,319 @@
+#[cfg(feature = "terminal")]
+use std::io::{BufWriter, Error, Stdout, Write};
+use std::str::FromStr;
+
+#[cfg(feature = "terminal")]
+use crossterm::{
+    cursor, event, execute, queue,
+    style::{Print, ResetColor},
+    terminal::{self, ClearType},
+};
+
+use crate::palette::Color;
+#[cfg(feature = "terminal")]
+use crate::palette::Palette;
+#[cfg(feature = "terminal")]
+use crate::phosphor::Persistence;
+#[cfg(feature = "terminal")]
+use crate::renderer::{Renderer, Screen};
+#[cfg(feature = "terminal")]
+use core::state::{FrameBuffer, Resolution};
+
+/// The bit of each braille dot in its pattern, by the dot's column and row in the character
+const DOTS: [[u32; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+
+/// # Glyphs
+/// The characters frames are drawn with in a terminal.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Glyphs {
+    /// Upper half blocks (`▀`) colored with the top pixel and backed with the bottom one, so each
+    /// character is 1x2 pixels in full color
+    HalfBlock,
+    /// Braille patterns, so each character is 2x4 pixels; lit pixels share one color per character
+    Braille,
+}
+
+impl Glyphs {
+    /// How many pixels wide and tall each character is
+    pub fn cell(&self) -> (usize, usize) {
+        match self {
+            Glyphs::HalfBlock => (1, 2),
+            Glyphs::Braille => (2, 4),
+        }
+    }
+}
+
+impl FromStr for Glyphs {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "half-block" => Ok(Glyphs::HalfBlock),
+            "braille" => Ok(Glyphs::Braille),
+            _ => Err(format!("{:?} is not a glyph set (half-block, braille)", s)),
+        }
+    }
+}
+
+/// Draws rows of RGB pixels as lines of text colored with 24-bit ANSI escape codes.
+///
+/// Colors are only written when they change from the character before, and each line ends by
+/// resetting them, so lines can be redrawn independently.
+///
+/// # Arguments
+/// * `rgb` concatenated rows of RGB pixels
+/// * `width` how many pixels wide each row is
+/// * `glyphs` the characters to draw pixels with
+/// * `background` the color of unlit pixels, which braille leaves blank
+pub fn lines(rgb: &[u8], width: usize, glyphs: Glyphs, background: Color) -> Vec<String> {
+    let pixel = |x: usize, y: usize| -> Color {
+        let i = (y * width + x) * 3;
+        [rgb[i], rgb[i + 1], rgb[i + 2]]
+    };
+    let (cell_width, cell_height) = glyphs.cell();
+    let height = rgb.len() / 3 / width.max(1);
+    (0..height / cell_height)
+        .map(|row| {
+            let (top, mut line) = (row * cell_height, String::new());
+            let (mut foreground, mut back) = (None, None);
+            for column in 0..width / cell_width {
+                let left = column * cell_width;
+                let (glyph, fore, behind) = match glyphs {
+                    Glyphs::HalfBlock => ('▀', pixel(left, top), pixel(left, top + 1)),
+                    Glyphs::Braille => {
+                        let (mut dots, mut fore) = (0, None);
+                        for (dx, column) in DOTS.iter().enumerate() {
+                            for (dy, &dot) in column.iter().enumerate() {
+                                let color = pixel(left + dx, top + dy);
+                                if color != background {
+                                    dots |= dot;
+                                    fore.get_or_insert(color);
+                                }
+                            }
+                        }
+                        let glyph = std::char::from_u32(0x2800 + dots).unwrap_or(' ');
+                        (glyph, fore.unwrap_or(background), background)
+                    }
+                };
+                if back != Some(behind) {
+                    line.push_str(&format!(
+                        "\x1b[48;2;{};{};{}m",
+                        behind[0], behind[1], behind[2]
+                    ));
+                    back = Some(behind);
+                }
+                // Blank braille doesn't show its color, so don't switch to it
+                if foreground != Some(fore) && glyph != '\u{2800}' {
+                    line.push_str(&format!("\x1b[38;2;{};{};{}m", fore[0], fore[1], fore[2]));
+                    foreground = Some(fore);
+                }
+                line.push(glyph);
+            }
+            line.push_str("\x1b[0m");
+            line
+        })
+        .collect()
+}
+
+/// # Terminal
+/// Draws the Chip-8 display in the terminal it's run from, as a drop in alternative to the SDL
+/// Display for playing over SSH or without a desktop.
+///
+/// The terminal is switched to raw mode and an alternate screen while it's open, and restored when
+/// it's dropped. The first line shows the title, and frames are centered below it.
+/// Only the lines of text that change between presents are redrawn, unless the terminal is resized.
+#[cfg(feature = "terminal")]
+pub struct Terminal {
+    out: BufWriter<Stdout>,
+    screen: Screen,
+    glyphs: Glyphs,
+    title: String,
+    drawn: Vec<String>,
+    layout: Option<((u16, u16), Resolution)>,
+    releases: bool,
+}
+
+#[cfg(feature = "terminal")]
+impl Terminal {
+    /// Takes over the terminal the emulator was run from.
+    ///
+    /// # Arguments
+    /// * `palette` the colors to draw pixels with
+    /// * `glyphs` the characters to draw pixels with
+    /// * `persistence` how long pixels stay visible after they're turned off, if at all
+    pub fn new(
+        palette: Palette,
+        glyphs: Glyphs,
+        persistence: Option<Persistence>,
+    ) -> Result<Self, Error> {
+        let mut out = BufWriter::new(std::io::stdout());
+        terminal::enable_raw_mode()?;
+        execute!(out, terminal::EnterAlternateScreen, cursor::Hide)?;
+        // Ask for key releases where the terminal can report them
+        let releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if releases {
+            execute!(
+                out,
+                event::PushKeyboardEnhancementFlags(
+                    event::KeyboardEnhancementFlags::REPORT_EVENT_TYPES
+                )
+            )?;
+        }
+        let mut terminal = Terminal {
+            out,
+            screen: Screen::new(palette, persistence),
+            glyphs,
+            title: String::from("Emu-8"),
+            drawn: Vec::new(),
+            layout: None,
+            releases,
+        };
+        terminal.present()?;
+        Ok(terminal)
+    }
+
+    /// Whether the terminal reports when keys are released; otherwise only presses and repeats are
+    pub fn reports_releases(&self) -> bool {
+        self.releases
+    }
+
+    /// Writes the title over the first line
+    fn draw_title(&mut self) -> Result<(), Error> {
+        queue!(
+            self.out,
+            cursor::MoveTo(0, 0),
+            terminal::Clear(ClearType::CurrentLine),
+            Print(&self.title)
+        )
+    }
+}
+
+#[cfg(feature = "terminal")]
+impl Renderer for Terminal {
+    fn render(&mut self, frame: &FrameBuffer, resolution: Resolution) {
+        self.screen.update(frame, resolution);
+    }
+
+    fn set_palette(&mut self, palette: Palette) {
+        self.screen.set_palette(palette);
+    }
+
+    /// Writes the lines of the frame that differ from what's already shown, blanking the terminal
+    /// first if it's been resized or the resolution has changed
+    fn present(&mut self) -> Result<(), Error> {
+        let resolution = self.screen.resolution();
+        let size = terminal::size()?;
+        if self.layout != Some((size, resolution)) {
+            self.layout = Some((size, resolution));
+            self.drawn.clear();
+            queue!(self.out, ResetColor, terminal::Clear(ClearType::All))?;
+            self.draw_title()?;
+        }
+        let width = resolution.width();
+        let lines = lines(
+            &self.screen.to_rgb(),
+            width,
+            self.glyphs,
+            self.screen.palette().color(0),
+        );
+        let left = (size.0 as usize).saturating_sub(width / self.glyphs.cell().0) / 2;
+        for (row, line) in lines.iter().enumerate() {
+            if self.drawn.get(row) != Some(line) {
+                queue!(
+                    self.out,
+                    cursor::MoveTo(left as u16, row as u16 + 1),
+                    Print(line)
+                )?;
+            }
+        }
+        self.drawn = lines;
+        self.out.flush()
+    }
+
+    fn is_fading(&self) -> bool {
+        self.screen.is_fading()
+    }
+
+    fn set_title(&mut self, title: &str) -> Result<(), Error> {
+        title.clone_into(&mut self.title);
+        self.draw_title()?;
+        self.out.flush()
+    }
+}
+
+#[cfg(feature = "terminal")]
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        if self.releases {
+            let _ = execute!(self.out, event::PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(
+            self.out,
+            ResetColor,
+            cursor::Show,
+            terminal::LeaveAlternateScreen
+        );
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: Color = [0, 0, 0];
+    const WHITE: Color = [0xFF, 0xFF, 0xFF];
+
+    /// A 4x4 image with the given pixels lit in white
+    fn rgb(lit: &[(usize, usize)]) -> Vec<u8> {
+        (0..16)
+            .flat_map(|i| match lit.contains(&(i % 4, i / 4)) {
+                true => WHITE,
+                false => BLACK,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_half_blocks_color_the_top_and_back_the_bottom() {
+        let lines = lines(
+            &rgb(&[(0, 0), (1, 1), (2, 0), (2, 1)]),
+            4,
+            Glyphs::HalfBlock,
+            BLACK,
+        );
+        let (white, black) = ("\x1b[38;2;255;255;255m", "\x1b[38;2;0;0;0m");
+        let (back_white, back_black) = ("\x1b[48;2;255;255;255m", "\x1b[48;2;0;0;0m");
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            format!(
+                "{}{}▀{}{}▀{}▀{}{}▀\x1b[0m",
+                back_black, white, back_white, black, white, back_black, black
+            )
+        );
+        // Colors that don't change aren't written again
+        assert_eq!(lines[1], format!("{}{}▀▀▀▀\x1b[0m", back_black, black));
+    }
+
+    #[test]
+    fn test_braille_sets_a_dot_for_each_lit_pixel() {
+        let lines = lines(
+            &rgb(&[(0, 0), (1, 1), (0, 3), (1, 3)]),
+            4,
+            Glyphs::Braille,
+            BLACK,
+        );
+        assert_eq!(
+            lines,
+            ["\x1b[48;2;0;0;0m\x1b[38;2;255;255;255m\u{28D1}\u{2800}\x1b[0m"]
+        );
+    }
+
+    #[test]
+    fn test_parses_glyphs() {
+        assert_eq!("braille".parse(), Ok(Glyphs::Braille));
+        assert_eq!("half-block".parse(), Ok(Glyphs::HalfBlock));
+        assert!("ascii".parse::<Glyphs>().is_err());
+    }
+}
@@ -0,0 +1,205 @@
+use std::fs;
+use std::io::Error;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use core::constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH, HIRES_DISPLAY_HEIGHT, HIRES_DISPLAY_WIDTH};
+
+/// How many window pixels wide and tall each low resolution pixel is by default
+pub const SCALE: u32 = 10;
+
+/// # Scaling
+/// How frames are fit into a window whose shape doesn't match the display's.
+/// Either way the frame is centered and the rest of the window is left black.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Scaling {
+    /// Scale by the largest whole number that fits, so every pixel is the same size
+    Integer,
+    /// Scale to fill as much of the window as the display's aspect ratio allows
+    Aspect,
+}
+
+impl FromStr for Scaling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "integer" => Ok(Scaling::Integer),
+            "aspect" => Ok(Scaling::Aspect),
+            _ => Err(format!("{:?} is not a scaling (integer, aspect)", s)),
+        }
+    }
+}
+
+/// # Viewport
+/// The part of the window frames are drawn in, measured in window pixels from its top left corner.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Viewport {
+    /// Fits the display into a window.
+    ///
+    /// Frames are fit as if they were in high resolution, with low resolution pixels twice as large,
+    /// so the viewport doesn't change when the resolution is switched.
+    ///
+    /// # Arguments
+    /// * `window` the width and height of the window
+    /// * `scaling` how the display is fit into it
+    pub fn fit(window: (u32, u32), scaling: Scaling) -> Self {
+        let (display_width, display_height) =
+            (HIRES_DISPLAY_WIDTH as u32, HIRES_DISPLAY_HEIGHT as u32);
+        let scale = (window.0 / display_width).min(window.1 / display_height);
+        let (width, height) = match scaling {
+            // Windows too small to fit the display at all are filled as if scaling by aspect
+            Scaling::Integer if scale > 0 => (display_width * scale, display_height * scale),
+            _ if window.0 * display_height <= window.1 * display_width => {
+                (window.0, window.0 * display_height / display_width)
+            }
+            _ => (window.1 * display_width / display_height, window.1),
+        };
+        Viewport {
+            x: (window.0 - width) / 2,
+            y: (window.1 - height) / 2,
+            width,
+            height,
+        }
+    }
+}
+
+/// # Window
+/// How the window is sized.
+///
+/// - `size` the width and height the window opens with
+/// - `scaling` how frames are fit into the window once it's resized
+/// - `remember` where the window's size is saved when it's closed, so it can be reopened at that size
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Window {
+    pub size: (u32, u32),
+    pub scaling: Scaling,
+    pub remember: Option<PathBuf>,
+}
+
+impl Default for Window {
+    fn default() -> Self {
+        Window {
+            size: Self::scaled(SCALE),
+            scaling: Scaling::Integer,
+            remember: None,
+        }
+    }
+}
+
+impl Window {
+    /// The size of a window that shows each low resolution pixel as a square of pixels
+    ///
+    /// # Arguments
+    /// * `scale` how many window pixels wide and tall each low resolution pixel is
+    pub fn scaled(scale: u32) -> (u32, u32) {
+        let scale = scale.max(1);
+        (
+            (DISPLAY_WIDTH as u32).saturating_mul(scale),
+            (DISPLAY_HEIGHT as u32).saturating_mul(scale),
+        )
+    }
+
+    /// The file emu8 remembers the window's size in, e.g. `~/.config/emu8/window`
+    pub fn in_config_directory() -> PathBuf {
+        let config = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+            .unwrap_or_default();
+        config.join("emu8").join("window")
+    }
+
+    /// Reads a remembered window size, written as `WIDTHxHEIGHT`
+    ///
+    /// # Arguments
+    /// * `path` the file the size was saved to
+    pub fn load_size(path: &Path) -> Option<(u32, u32)> {
+        let size = fs::read_to_string(path).ok()?;
+        let (width, height) = size.trim().split_once('x')?;
+        match (width.parse().ok()?, height.parse().ok()?) {
+            (0, _) | (_, 0) => None,
+            size => Some(size),
+        }
+    }
+
+    /// Saves a window size so it can be reopened at that size
+    ///
+    /// # Arguments
+    /// * `path` the file to save the size to
+    /// * `size` the width and height of the window
+    pub fn save_size(path: &Path, size: (u32, u32)) -> Result<(), Error> {
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        fs::write(path, format!("{}x{}\n", size.0, size.1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_integer_scaling_letterboxes_whole_multiples() {
+        let viewport = Viewport::fit((1000, 700), Scaling::Integer);
+        assert_eq!(
+            viewport,
+            Viewport {
+                x: 52,
+                y: 126,
+                width: 896,
+                height: 448
+            }
+        );
+        // Too small for a whole multiple
+        let viewport = Viewport::fit((100, 100), Scaling::Integer);
+        assert_eq!((viewport.width, viewport.height), (100, 50));
+    }
+
+    #[test]
+    fn test_aspect_scaling_fills_one_side() {
+        let wide = Viewport::fit((1000, 300), Scaling::Aspect);
+        assert_eq!(
+            wide,
+            Viewport {
+                x: 200,
+                y: 0,
+                width: 600,
+                height: 300
+            }
+        );
+        let tall = Viewport::fit((1000, 700), Scaling::Aspect);
+        assert_eq!(
+            tall,
+            Viewport {
+                x: 0,
+                y: 100,
+                width: 1000,
+                height: 500
+            }
+        );
+    }
+
+    #[test]
+    fn test_scaled_size_saturates() {
+        assert_eq!(Window::scaled(0), (64, 32));
+        assert_eq!(Window::scaled(u32::MAX), (u32::MAX, u32::MAX));
+    }
+
+    #[test]
+    fn test_window_size_is_remembered() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("window");
+        assert_eq!(Window::load_size(&path), None);
+        Window::save_size(&path, (1280, 720)).unwrap();
+        assert_eq!(Window::load_size(&path), Some((1280, 720)));
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// # Config
+/// Per-ROM settings, read from a file next to the ROM with the extension `emu8` (e.g. `pong.emu8` for `pong.ch8`).
+///
+/// Each line is a `key = value` pair; blank lines and lines starting with `#` are ignored.
+/// Settings given on the command line take precedence over the ones in the file.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Config {
+    values: HashMap<String, String>,
+}
+
+impl Config {
+    /// Reads the config for a ROM, which is empty if the ROM doesn't have one
+    ///
+    /// # Arguments
+    /// * `rom` the path of the ROM
+    pub fn for_rom(rom: &Path) -> Result<Self, String> {
+        let path = Self::path(rom);
+        match fs::read_to_string(&path) {
+            Ok(text) => text
+                .parse()
+                .map_err(|e| format!("{}: {}", path.display(), e)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    /// The path of the config for a ROM
+    ///
+    /// # Arguments
+    /// * `rom` the path of the ROM
+    pub fn path(rom: &Path) -> PathBuf {
+        rom.with_extension("emu8")
+    }
+
+    /// Parses the value of a setting, if it's set
+    ///
+    /// # Arguments
+    /// * `key` the name of the setting
+    pub fn get<T>(&self, key: &str) -> Result<Option<T>, String>
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        self.values
+            .get(key)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|e| format!("invalid value {:?} for {}: {}", value, key, e))
+            })
+            .transpose()
+    }
+}
+
+impl FromStr for Config {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut values = HashMap::new();
+        for (number, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected `key = value`", number + 1))?;
+            values.insert(key.trim().to_string(), value.trim().to_string());
+        }
+        Ok(Config { values })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_parses_settings() {
+        let config: Config = "# comment\n\ninstructions_per_frame = 15\n"
+            .parse()
+            .unwrap();
+        assert_eq!(config.get::<usize>("instructions_per_frame"), Ok(Some(15)));
+        assert_eq!(config.get::<usize>("quirks"), Ok(None));
+    }
+
+    #[test]
+    fn test_config_reports_bad_lines_and_values() {
+        assert_eq!(
+            "\nfast".parse::<Config>(),
+            Err("line 2: expected `key = value`".to_string())
+        );
+        let config: Config = "instructions_per_frame = lots".parse().unwrap();
+        assert!(config.get::<usize>("instructions_per_frame").is_err());
+    }
+
+    #[test]
+    fn test_config_path_is_next_to_rom() {
+        assert_eq!(
+            Config::path(Path::new("roms/pong.ch8")),
+            PathBuf::from("roms/pong.emu8")
+        );
+    }
+}
@@ -1,6 +1,35 @@
+use chip8::debug::debug;
+use chip8::gdb::gdb;
+use chip8::headless::{headless, ExitReason, Limit, Outputs};
+use chip8::movie::Movie;
+#[cfg(feature = "sdl")]
 use chip8::run;
-use clap::{Parser, ValueHint};
-use std::path::PathBuf;
+#[cfg(any(feature = "sdl", feature = "terminal"))]
+use chip8::savestate::Slots;
+#[cfg(feature = "terminal")]
+use chip8::terminal;
+#[cfg(any(feature = "sdl", feature = "terminal"))]
+use chip8::tone::{parse_frequency, parse_volume};
+use chip8::trace::parse_range;
+#[cfg(any(feature = "sdl", feature = "terminal"))]
+use chip8::{Frontend, Tone, Waveform};
+use chip8::{Options, Session, Trace};
+use clap::{ArgGroup, Args as ClapArgs, Parser, ValueHint};
+use config::Config;
+use core::assembler::assemble;
+#[cfg(any(feature = "sdl", feature = "terminal"))]
+use core::constants::{FRAME_RATE, REWIND_FRAMES, REWIND_MEMORY};
+use core::disassembler::disassemble;
+use core::quirks::{Profile, Quirks};
+use display::{parse_color, Color, Format, Gif, Palette, Screenshot};
+#[cfg(any(feature = "sdl", feature = "terminal"))]
+use display::{Glyphs, Persistence, Scaling, Window};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+#[cfg(any(feature = "sdl", feature = "terminal"))]
+use std::str::FromStr;
+
+mod config;
 
 #[derive(Parser)]
 struct Args {
@@ -10,17 +39,461 @@ struct Args {
 
 #[derive(Parser)]
 enum SubCommand {
+    #[cfg(any(feature = "sdl", feature = "terminal"))]
     Run(Run),
+    /// Run a ROM without video or audio and report the final frame, registers, and exit reason
+    Headless(Headless),
+    /// Step through a ROM with breakpoints, printing registers and memory
+    Debug(Debug),
+    /// Let a GDB remote serial protocol client control a ROM over a local TCP port
+    Gdb(Gdb),
+    /// Disassemble a ROM, labelling branch targets and writing unreachable bytes as data
+    Disasm(Disasm),
+    /// Assemble a source file into a ROM
+    Asm(Asm),
+}
+
+/// Options that configure the emulated machine
+#[derive(ClapArgs)]
+struct Machine {
+    /// Which interpreter's behavior to follow for ambiguous opcodes (vip, chip48, schip, modern)
+    #[clap(short, long)]
+    quirks: Option<Profile>,
+    /// Seed for the random number generator; defaults to a hash of the ROM
+    #[clap(short, long)]
+    seed: Option<u64>,
+    /// How many instructions to execute per 60Hz frame; can also be set per ROM with
+    /// `instructions_per_frame` in its config file (e.g. `pong.emu8` next to `pong.ch8`)
+    #[clap(short, long)]
+    instructions_per_frame: Option<usize>,
+    /// Log every executed instruction with the registers before it to this file
+    #[clap(long, value_hint = ValueHint::AnyPath)]
+    trace: Option<PathBuf>,
+    /// Only log instructions at addresses in this range, e.g. 0x200-0x2FF
+    #[clap(long, value_parser = parse_range, default_value = "0x000-0xFFFF", requires = "trace")]
+    trace_range: RangeInclusive<u16>,
+    /// Play back the key presses recorded in this movie instead of reading the keyboard
+    #[clap(long, value_hint = ValueHint::FilePath)]
+    play: Option<PathBuf>,
+    /// Record every key press to this movie, which is saved when the ROM stops
+    #[clap(long, value_hint = ValueHint::AnyPath)]
+    record: Option<PathBuf>,
+}
+
+impl Machine {
+    /// Combines these options with the ones in the ROM's config file
+    fn options(&self, rom: &Path) -> Result<Options, String> {
+        let config = Config::for_rom(rom)?;
+        let defaults = Options::default();
+        Ok(Options {
+            quirks: self.quirks.map_or(Quirks::default(), Quirks::from),
+            seed: self.seed,
+            instructions_per_frame: match self.instructions_per_frame {
+                Some(instructions) => instructions,
+                None => config
+                    .get("instructions_per_frame")?
+                    .unwrap_or(defaults.instructions_per_frame),
+            },
+            ..defaults
+        })
+    }
+
+    /// Creates the trace file and loads the movie to play, if they were asked for
+    fn session(&self) -> Result<Session, String> {
+        let trace = self
+            .trace
+            .as_ref()
+            .map(|path| Trace::create(path, self.trace_range.clone()))
+            .transpose()
+            .map_err(|e| format!("unable to create trace: {}", e))?;
+        let play = self
+            .play
+            .as_deref()
+            .map(Movie::load)
+            .transpose()
+            .map_err(|e| format!("unable to load movie: {}", e))?;
+        Ok(Session {
+            trace,
+            play,
+            record: self.record.clone(),
+        })
+    }
+}
+
+/// Options that configure the colors frames are drawn in
+#[derive(ClapArgs)]
+struct Colors {
+    /// A theme (classic, green, amber, lcd, high-contrast) or comma separated hex colors for the
+    /// background, plane 1, plane 2, and both planes; can also be set per ROM with `palette` in its
+    /// config file
+    #[clap(short, long)]
+    palette: Option<Palette>,
+    /// Hex color for lit pixels, replacing plane 1 of the palette; or `foreground` in the config file
+    #[clap(long, value_parser = parse_color)]
+    foreground: Option<Color>,
+    /// Hex color for unlit pixels, replacing the palette's background; or `background` in the config file
+    #[clap(long, value_parser = parse_color)]
+    background: Option<Color>,
+}
+
+impl Colors {
+    /// Combines these colors with the ones in the ROM's config file
+    fn palette(&self, rom: &Path) -> Result<Palette, String> {
+        let config = Config::for_rom(rom)?;
+        let color = |color: Option<Color>, key: &str| match color {
+            Some(color) => Ok(Some(color)),
+            None => config
+                .get::<String>(key)?
+                .map(|color| parse_color(&color).map_err(|e| format!("{}: {}", key, e)))
+                .transpose(),
+        };
+        let mut palette = match self.palette {
+            Some(palette) => palette,
+            None => config.get("palette")?.unwrap_or_default(),
+        };
+        if let Some(background) = color(self.background, "background")? {
+            palette.colors[0] = background;
+        }
+        if let Some(foreground) = color(self.foreground, "foreground")? {
+            palette.colors[1] = foreground;
+        }
+        Ok(palette)
+    }
+}
+
+/// Options that configure how screenshots are saved
+#[derive(ClapArgs)]
+struct Screenshots {
+    /// How many image pixels wide and tall each Chip-8 pixel is in screenshots
+    #[clap(long, default_value_t = 1)]
+    screenshot_scale: usize,
+    /// The image format of screenshots (png, ppm, pbm); defaults to the screenshot's extension, or png
+    #[clap(long)]
+    screenshot_format: Option<Format>,
+}
+
+impl Screenshots {
+    /// How screenshots are saved, given the palette and the path if there is one
+    fn screenshot(&self, palette: Palette, path: Option<&Path>) -> Screenshot {
+        Screenshot {
+            palette,
+            scale: self.screenshot_scale,
+            format: self
+                .screenshot_format
+                .or_else(|| path.and_then(Format::from_path))
+                .unwrap_or(Format::Png),
+        }
+    }
+}
+
+/// Options that configure how GIFs are recorded
+#[derive(ClapArgs)]
+struct Gifs {
+    /// How many image pixels wide and tall each Chip-8 pixel is in GIFs
+    #[clap(long, default_value_t = 2)]
+    gif_scale: usize,
+    /// Cut frames that stay the same at the start and end of GIFs down to a single frame
+    #[clap(long)]
+    gif_trim: bool,
+    /// The largest a GIF may be, in kilobytes; frames past the limit are left out
+    #[clap(long)]
+    gif_max_size: Option<usize>,
 }
 
+impl Gifs {
+    /// How GIFs are recorded, given the palette
+    fn gif(&self, palette: Palette) -> Gif {
+        Gif {
+            palette,
+            scale: self.gif_scale,
+            trim: self.gif_trim,
+            max_bytes: self.gif_max_size.map(|kilobytes| kilobytes * 1024),
+        }
+    }
+}
+
+/// The frontend ROMs are played in unless another is chosen
+#[cfg(any(feature = "sdl", feature = "terminal"))]
+const DEFAULT_FRONTEND: &str = if cfg!(feature = "sdl") {
+    "sdl"
+} else {
+    "terminal"
+};
+
+/// How many bytes `--rewind-memory` counts in each of its units
+#[cfg(any(feature = "sdl", feature = "terminal"))]
+const MEGABYTE: usize = 1024 * 1024;
+
+/// Where `run` plays a ROM
+#[cfg(any(feature = "sdl", feature = "terminal"))]
+#[derive(Clone, Copy)]
+enum FrontendKind {
+    /// In a window, with sound
+    #[cfg(feature = "sdl")]
+    Sdl,
+    /// In the terminal emu8 is run from
+    #[cfg(feature = "terminal")]
+    Terminal,
+}
+
+#[cfg(any(feature = "sdl", feature = "terminal"))]
+impl FromStr for FrontendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            #[cfg(feature = "sdl")]
+            "sdl" => Ok(FrontendKind::Sdl),
+            #[cfg(feature = "terminal")]
+            "terminal" => Ok(FrontendKind::Terminal),
+            _ if s == "sdl" || s == "terminal" => {
+                Err(format!("emu8 was built without the {} frontend", s))
+            }
+            _ => Err(format!("{:?} is not a frontend (sdl, terminal)", s)),
+        }
+    }
+}
+
+#[cfg(any(feature = "sdl", feature = "terminal"))]
 #[derive(Parser)]
 struct Run {
     #[clap(short, long, value_hint = ValueHint::AnyPath)]
     file: PathBuf,
+    #[clap(flatten)]
+    machine: Machine,
+    /// Where to play the ROM: in a window (sdl) or in the terminal emu8 is run from (terminal)
+    #[clap(long, default_value = DEFAULT_FRONTEND)]
+    frontend: FrontendKind,
+    /// The characters frames are drawn with in the terminal (half-block, braille)
+    #[clap(long, default_value = "half-block")]
+    glyphs: Glyphs,
+    #[clap(flatten)]
+    colors: Colors,
+    /// How many window pixels wide and tall each low resolution pixel is when the window opens;
+    /// defaults to the size the window was when it was last closed
+    #[clap(long)]
+    scale: Option<u32>,
+    /// How frames fit into the window once it's resized (integer, aspect)
+    #[clap(long, default_value = "integer")]
+    scaling: Scaling,
+    /// Keep pixels visible after they're turned off to reduce flicker: `fade` over 4 frames, `fade:N`
+    /// over N frames, or `blend` the last two frames
+    #[clap(long)]
+    persistence: Option<Persistence>,
+    /// Pitch of the tone played while the sound timer is active, in Hz
+    #[clap(long, default_value = "440", value_parser = parse_frequency)]
+    frequency: f32,
+    /// Volume of the tone, from 0.0 to 1.0
+    #[clap(long, default_value = "0.25", value_parser = parse_volume)]
+    volume: f32,
+    /// Shape of the tone (square, sine, triangle)
+    #[clap(long, default_value = "square")]
+    waveform: Waveform,
+    /// How many seconds of play can be rewound
+    #[clap(long, default_value_t = REWIND_FRAMES / FRAME_RATE as usize)]
+    rewind_seconds: usize,
+    /// The most memory the rewind history may use, in megabytes
+    #[clap(long, default_value_t = REWIND_MEMORY / MEGABYTE)]
+    rewind_memory: usize,
+    /// Where save states are kept; defaults to emu8's data directory, e.g. `~/.local/share/emu8/states`
+    #[clap(long, value_hint = ValueHint::DirPath)]
+    states: Option<PathBuf>,
+    /// Where screenshots taken with F12 and GIFs recorded with G are saved
+    #[clap(long, default_value = ".", value_hint = ValueHint::DirPath)]
+    screenshots: PathBuf,
+    #[clap(flatten)]
+    screenshot: Screenshots,
+    #[clap(flatten)]
+    gif: Gifs,
+}
+
+#[derive(Parser)]
+struct Debug {
+    #[clap(short, long, value_hint = ValueHint::AnyPath)]
+    file: PathBuf,
+    #[clap(flatten)]
+    machine: Machine,
+}
+
+#[derive(Parser)]
+struct Gdb {
+    #[clap(short, long, value_hint = ValueHint::AnyPath)]
+    file: PathBuf,
+    #[clap(flatten)]
+    machine: Machine,
+    /// The local port to listen for a client on
+    #[clap(short, long, default_value_t = 1234)]
+    port: u16,
+}
+
+#[derive(Parser)]
+struct Disasm {
+    #[clap(short, long, value_hint = ValueHint::AnyPath)]
+    file: PathBuf,
+}
+
+#[derive(Parser)]
+struct Asm {
+    /// The assembly source
+    #[clap(value_hint = ValueHint::AnyPath)]
+    input: PathBuf,
+    /// Where to write the ROM
+    #[clap(short, long, value_hint = ValueHint::AnyPath)]
+    output: PathBuf,
+}
+
+#[derive(Parser)]
+#[clap(group(ArgGroup::new("limit").required(true).args(["cycles", "frames"])))]
+struct Headless {
+    #[clap(short, long, value_hint = ValueHint::AnyPath)]
+    file: PathBuf,
+    #[clap(flatten)]
+    machine: Machine,
+    /// How many CPU cycles to execute
+    #[clap(short, long)]
+    cycles: Option<usize>,
+    /// How many 60Hz frames to execute
+    #[clap(long)]
+    frames: Option<usize>,
+    /// Save the report to this file instead of printing it
+    #[clap(short, long, value_hint = ValueHint::AnyPath)]
+    output: Option<PathBuf>,
+    /// Save the final frame as an image to this file, e.g. `frame.png`
+    #[clap(long, value_hint = ValueHint::AnyPath)]
+    screenshot: Option<PathBuf>,
+    #[clap(flatten)]
+    screenshots: Screenshots,
+    /// Record every frame as an animated GIF to this file, e.g. `demo.gif`
+    #[clap(long, value_hint = ValueHint::AnyPath)]
+    gif: Option<PathBuf>,
+    #[clap(flatten)]
+    gifs: Gifs,
+    #[clap(flatten)]
+    colors: Colors,
 }
 
 fn main() {
     match Args::parse().subcmd {
-        SubCommand::Run(args) => run(args.file),
+        #[cfg(any(feature = "sdl", feature = "terminal"))]
+        SubCommand::Run(args) => {
+            let options = Options {
+                rewind_frames: args.rewind_seconds.saturating_mul(FRAME_RATE as usize),
+                rewind_memory: args.rewind_memory.saturating_mul(MEGABYTE),
+                ..args
+                    .machine
+                    .options(&args.file)
+                    .unwrap_or_else(|e| fail(&e))
+            };
+            let tone = Tone {
+                frequency: args.frequency,
+                volume: args.volume,
+                waveform: args.waveform,
+            };
+            let session = args.machine.session().unwrap_or_else(|e| fail(&e));
+            let palette = args.colors.palette(&args.file).unwrap_or_else(|e| fail(&e));
+            let remember = Window::in_config_directory();
+            let window = Window {
+                size: match args.scale {
+                    Some(scale) => Window::scaled(scale),
+                    None => Window::load_size(&remember).unwrap_or(Window::default().size),
+                },
+                scaling: args.scaling,
+                remember: Some(remember),
+            };
+            let frontend = Frontend {
+                palette,
+                window,
+                persistence: args.persistence,
+                tone,
+                slots: args
+                    .states
+                    .map_or_else(Slots::in_data_directory, Slots::new),
+                screenshot: args.screenshot.screenshot(palette, None),
+                gif: args.gif.gif(palette),
+                screenshots: args.screenshots,
+            };
+            match args.frontend {
+                #[cfg(feature = "sdl")]
+                FrontendKind::Sdl => run(args.file, options, session, frontend),
+                #[cfg(feature = "terminal")]
+                FrontendKind::Terminal => {
+                    if let Err(e) = terminal(args.file, options, session, frontend, args.glyphs) {
+                        fail(&format!("unable to run ROM: {}", e));
+                    }
+                }
+            }
+        }
+        SubCommand::Headless(args) => {
+            let limit = match (args.cycles, args.frames) {
+                (Some(cycles), _) => Limit::Cycles(cycles),
+                (_, frames) => Limit::Frames(frames.unwrap_or_default()),
+            };
+            let options = args
+                .machine
+                .options(&args.file)
+                .unwrap_or_else(|e| fail(&e));
+            let session = args.machine.session().unwrap_or_else(|e| fail(&e));
+            let palette = args.colors.palette(&args.file).unwrap_or_else(|e| fail(&e));
+            let screenshots = &args.screenshots;
+            let gif = args.gifs.gif(palette);
+            let outputs = Outputs {
+                report: args.output,
+                screenshot: args.screenshot.map(|path| {
+                    let screenshot = screenshots.screenshot(palette, Some(&path));
+                    (path, screenshot)
+                }),
+                gif: args.gif.map(|path| (path, gif)),
+            };
+            match headless(args.file, options, session, limit, outputs) {
+                Ok(ExitReason::Halted(_)) => std::process::exit(1),
+                Ok(_) => (),
+                Err(e) => fail(&format!("unable to run ROM: {}", e)),
+            }
+        }
+        SubCommand::Debug(args) => {
+            let options = args
+                .machine
+                .options(&args.file)
+                .unwrap_or_else(|e| fail(&e));
+            let session = args.machine.session().unwrap_or_else(|e| fail(&e));
+            let stdin = std::io::stdin();
+            if let Err(e) = debug(
+                args.file,
+                options,
+                session,
+                &mut stdin.lock(),
+                &mut std::io::stdout(),
+            ) {
+                fail(&format!("unable to debug ROM: {}", e));
+            }
+        }
+        SubCommand::Gdb(args) => {
+            let options = args
+                .machine
+                .options(&args.file)
+                .unwrap_or_else(|e| fail(&e));
+            let session = args.machine.session().unwrap_or_else(|e| fail(&e));
+            if let Err(e) = gdb(args.file, options, session, args.port) {
+                fail(&format!("unable to debug ROM: {}", e));
+            }
+        }
+        SubCommand::Disasm(args) => match std::fs::read(&args.file) {
+            Ok(rom) => print!("{}", disassemble(&rom)),
+            Err(e) => fail(&format!("unable to read ROM: {}", e)),
+        },
+        SubCommand::Asm(args) => {
+            let source = std::fs::read_to_string(&args.input)
+                .unwrap_or_else(|e| fail(&format!("unable to read source: {}", e)));
+            let rom = assemble(&source, &args.input).unwrap_or_else(|e| fail(&e.to_string()));
+            if let Err(e) = std::fs::write(&args.output, rom) {
+                fail(&format!("unable to write ROM: {}", e));
+            }
+        }
     };
 }
+
+/// Reports an error that prevented a ROM from being run and exits
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(2)
+}